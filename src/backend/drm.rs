@@ -0,0 +1,567 @@
+use super::{vrr_capability, DisplayBackend, DisplayMode, Output, VrrCapability};
+use crate::edid::{cta, Edid};
+use crate::error::HertzRateError;
+use crate::refresh_rate::RefreshRate;
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_SYSFS_ROOT: &str = "/sys/class/drm";
+
+/// Linux DRM/KMS backend that reads connector state from sysfs.
+///
+/// On this machine the modes, with the exact rates of their timings, and the
+/// current mode come from KMS. A copied tree has no KMS behind it, and its
+/// sysfs `modes` only lists resolutions, so there the refresh rates come
+/// from the timings in each connector's EDID and the first entry of `modes`,
+/// the kernel's preferred mode, stands in for the current one. Modes are tested
+/// and applied with atomic commits, which the kernel only accepts from the
+/// DRM master: while a compositor or X server runs, use its backend instead.
+/// The mode lasts as long as nobody else modesets; when hertzrate closes the
+/// card, the console's fbdev emulation may put its own mode back.
+///
+/// Device names are connector names without the card (`eDP-1`), unless the
+/// tree has connectors of several cards: then the card stays in the name
+/// (`card1-DP-1`) so that names are unique.
+pub struct DrmBackend {
+    root: PathBuf,
+}
+
+impl DrmBackend {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn connectors(&self) -> Result<Vec<PathBuf>> {
+        let entries = fs::read_dir(&self.root)
+            .with_context(|| format!("Failed to read {}", self.root.display()))?;
+
+        // Connectors look like card0-eDP-1; bare cardN and renderD nodes are skipped
+        let mut connectors: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with("card") && name.contains('-'))
+            })
+            .collect();
+
+        connectors.sort();
+        Ok(connectors)
+    }
+
+    /// Each connector with its device name.
+    fn named_connectors(&self) -> Result<Vec<(String, PathBuf)>> {
+        let connectors = self.connectors()?;
+        let mut cards: Vec<&str> = connectors.iter().map(|path| card_name(path)).collect();
+        cards.dedup();
+        let several_cards = cards.len() > 1;
+
+        Ok(connectors
+            .iter()
+            .map(|path| {
+                let name = if several_cards {
+                    file_name(path).to_string()
+                } else {
+                    connector_name(path)
+                };
+                (name, path.clone())
+            })
+            .collect())
+    }
+
+    /// The connector named `device_name`. Names without the card, as
+    /// compositors use them, are accepted as long as only one card has
+    /// such a connector.
+    fn connector_path(&self, device_name: &str) -> Result<PathBuf> {
+        let connectors = self.named_connectors()?;
+        if let Some((_, path)) = connectors.iter().find(|(name, _)| name == device_name) {
+            return Ok(path.clone());
+        }
+        let mut matches = connectors
+            .into_iter()
+            .filter(|(_, path)| connector_name(path) == device_name);
+        match (matches.next(), matches.next()) {
+            (Some((_, path)), None) => Ok(path),
+            (Some(_), Some(_)) => Err(anyhow!(
+                "Several cards have a connector {}; name it with its card, e.g. card0-{}",
+                device_name,
+                device_name
+            )),
+            _ => Err(anyhow!("Connector {} not found", device_name)),
+        }
+    }
+
+    fn connector_modes(&self, device_name: &str) -> Result<Vec<DisplayMode>> {
+        let path = self.connector_path(device_name)?;
+        if let Some(modes) = self.kms(|| kms_connector_modes(device_name)) {
+            return Ok(modes);
+        }
+        let timings = Edid::parse(&read_edid(&path))
+            .map(|edid| edid.modes())
+            .unwrap_or_default();
+
+        let mut modes: Vec<DisplayMode> = Vec::new();
+        for (width, height, interlaced) in read_resolutions(&path)? {
+            let mut candidates: Vec<DisplayMode> = timings
+                .iter()
+                .filter(|t| t.width == width && t.height == height && t.interlaced == interlaced)
+                .copied()
+                .collect();
+
+            // Modes without an EDID timing were added by the kernel: CTA-861
+            // formats for HDMI sinks (so 720x576 runs at 50Hz), otherwise
+            // DMT fallbacks at 60Hz
+            if candidates.is_empty() {
+                candidates.push(cta::first_format(width, height, interlaced).unwrap_or(
+                    DisplayMode {
+                        interlaced,
+                        ..DisplayMode::new(width, height, RefreshRate::from_hz(60))
+                    },
+                ));
+            }
+
+            for mode in candidates {
+                match modes.iter_mut().find(|m| m.same_timing(&mode)) {
+                    Some(existing) => existing.preferred |= mode.preferred,
+                    None => modes.push(mode),
+                }
+            }
+        }
+
+        Ok(modes)
+    }
+}
+
+impl DrmBackend {
+    /// Runs a KMS query, which describes this machine and not a copied
+    /// sysfs tree, only for the default root.
+    fn kms<T>(&self, query: impl FnOnce() -> Option<T>) -> Option<T> {
+        if self.root == Path::new(DEFAULT_SYSFS_ROOT) {
+            query()
+        } else {
+            None
+        }
+    }
+}
+
+impl DrmBackend {
+    /// Commits the modes through KMS.
+    fn commit(&self, changes: &[(&str, DisplayMode)], test_only: bool) -> Result<()> {
+        for (device_name, _) in changes {
+            self.connector_path(device_name)?;
+        }
+        self.require_this_machine()?;
+        kms_commit_modes(changes, test_only)
+    }
+
+    /// KMS commits change this machine, so a copied sysfs tree can't take
+    /// them.
+    fn require_this_machine(&self) -> Result<()> {
+        if self.root != Path::new(DEFAULT_SYSFS_ROOT) {
+            return Err(HertzRateError::BackendUnsupported(format!(
+                "{} is not this machine's DRM tree; the drm backend only changes it under {}",
+                self.root.display(),
+                DEFAULT_SYSFS_ROOT
+            ))
+            .into());
+        }
+        Ok(())
+    }
+}
+
+impl Default for DrmBackend {
+    fn default() -> Self {
+        Self::new(DEFAULT_SYSFS_ROOT)
+    }
+}
+
+impl DisplayBackend for DrmBackend {
+    fn name(&self) -> &'static str {
+        "drm"
+    }
+
+    fn enumerate_outputs(&self) -> Result<Vec<Output>> {
+        let mut outputs = Vec::new();
+
+        for (device_name, path) in self.named_connectors()? {
+            // Skip connectors without a monitor or without an active CRTC
+            if read_attribute(&path, "status").as_deref() != Some("connected")
+                || read_attribute(&path, "enabled").as_deref() != Some("enabled")
+            {
+                continue;
+            }
+
+            let description = Edid::parse(&read_edid(&path))
+                .map(|edid| edid.description())
+                .unwrap_or_else(|_| device_name.clone());
+
+            outputs.push(Output {
+                device_name,
+                description,
+            });
+        }
+
+        Ok(outputs)
+    }
+
+    /// The mode of the connector's CRTC, matched to the listed mode with
+    /// the closest rate; the preferred mode for a copied tree.
+    fn current_mode(&self, device_name: &str) -> Result<DisplayMode> {
+        let modes = self.connector_modes(device_name)?;
+        if let Some(active) = self.kms(|| kms_current_mode(device_name)) {
+            let listed = modes
+                .iter()
+                .filter(|mode| {
+                    mode.resolution() == active.resolution() && mode.interlaced == active.interlaced
+                })
+                .min_by_key(|mode| {
+                    mode.refresh_rate
+                        .millihertz()
+                        .abs_diff(active.refresh_rate.millihertz())
+                });
+            return Ok(listed.copied().unwrap_or(active));
+        }
+        modes
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("Connector {} reports no modes", device_name))
+    }
+
+    fn query_modes(&self, device_name: &str) -> Result<Vec<DisplayMode>> {
+        self.connector_modes(device_name)
+    }
+
+    /// Asks the driver through an atomic test-only commit, which like every
+    /// atomic commit is only allowed to the DRM master.
+    fn test_mode(&self, device_name: &str, mode: &DisplayMode) -> Result<()> {
+        self.test_modes(&[(device_name, *mode)])
+    }
+
+    fn test_modes(&self, changes: &[(&str, DisplayMode)]) -> Result<()> {
+        self.commit(changes, true)
+    }
+
+    fn apply_mode(&self, device_name: &str, mode: &DisplayMode) -> Result<()> {
+        self.apply_modes(&[(device_name, *mode)])
+    }
+
+    /// Switches every output in one atomic commit per card, after testing
+    /// all of them.
+    fn apply_modes(&self, changes: &[(&str, DisplayMode)]) -> Result<()> {
+        self.commit(changes, false)
+    }
+
+    fn edid(&self, device_name: &str) -> Result<Option<Vec<u8>>> {
+        let edid = read_edid(&self.connector_path(device_name)?);
+        Ok((!edid.is_empty()).then_some(edid))
+    }
+
+    fn vrr(&self, device_name: &str) -> Result<Option<VrrCapability>> {
+        let edid = Edid::parse(&read_edid(&self.connector_path(device_name)?)).ok();
+        let driver_capable = self.kms(|| kms_vrr_capable(device_name));
+        Ok(vrr_capability(edid.as_ref(), driver_capable))
+    }
+
+    fn vrr_enabled(&self, device_name: &str) -> Result<Option<bool>> {
+        self.connector_path(device_name)?;
+        Ok(self.kms(|| kms_vrr_enabled(device_name)))
+    }
+
+    /// Sets the `VRR_ENABLED` property of the connector's CRTC with an
+    /// atomic commit, which like a modeset needs the DRM master.
+    fn set_vrr(&self, device_name: &str, enabled: bool) -> Result<()> {
+        self.connector_path(device_name)?;
+        self.require_this_machine()?;
+        kms_commit_vrr(device_name, enabled)
+    }
+}
+
+/// EDID of a connector from the default sysfs tree, for compositor backends
+/// whose output names are the kernel's connector names.
+pub(super) fn sysfs_edid(device_name: &str) -> Option<Vec<u8>> {
+    DrmBackend::default().edid(device_name).ok().flatten()
+}
+
+#[cfg(target_os = "linux")]
+fn kms_vrr_capable(device_name: &str) -> Option<bool> {
+    super::kms::vrr_capable(device_name)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn kms_vrr_capable(_device_name: &str) -> Option<bool> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn kms_connector_modes(device_name: &str) -> Option<Vec<DisplayMode>> {
+    super::kms::connector_modes(device_name)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn kms_connector_modes(_device_name: &str) -> Option<Vec<DisplayMode>> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn kms_current_mode(device_name: &str) -> Option<DisplayMode> {
+    super::kms::current_mode(device_name)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn kms_current_mode(_device_name: &str) -> Option<DisplayMode> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn kms_vrr_enabled(device_name: &str) -> Option<bool> {
+    super::kms::vrr_enabled(device_name)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn kms_vrr_enabled(_device_name: &str) -> Option<bool> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn kms_commit_vrr(device_name: &str, enabled: bool) -> Result<()> {
+    super::kms::commit_vrr(device_name, enabled)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn kms_commit_vrr(_device_name: &str, _enabled: bool) -> Result<()> {
+    Err(HertzRateError::BackendUnsupported("KMS is only available on Linux".to_string()).into())
+}
+
+#[cfg(target_os = "linux")]
+fn kms_commit_modes(changes: &[(&str, DisplayMode)], test_only: bool) -> Result<()> {
+    super::kms::commit_modes(changes, test_only)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn kms_commit_modes(_changes: &[(&str, DisplayMode)], _test_only: bool) -> Result<()> {
+    Err(HertzRateError::BackendUnsupported("KMS is only available on Linux".to_string()).into())
+}
+
+fn file_name(path: &Path) -> &str {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+}
+
+/// card0-eDP-1 -> card0
+fn card_name(path: &Path) -> &str {
+    let name = file_name(path);
+    name.split_once('-').map_or(name, |(card, _)| card)
+}
+
+/// card0-eDP-1 -> eDP-1
+fn connector_name(path: &Path) -> String {
+    let name = file_name(path);
+    name.split_once('-')
+        .map_or(name, |(_, connector)| connector)
+        .to_string()
+}
+
+fn read_attribute(path: &Path, attribute: &str) -> Option<String> {
+    fs::read_to_string(path.join(attribute))
+        .ok()
+        .map(|value| value.trim().to_string())
+}
+
+fn read_edid(path: &Path) -> Vec<u8> {
+    fs::read(path.join("edid")).unwrap_or_default()
+}
+
+fn read_resolutions(path: &Path) -> Result<Vec<(u32, u32, bool)>> {
+    let modes = fs::read_to_string(path.join("modes"))
+        .with_context(|| format!("Failed to read modes of {}", path.display()))?;
+
+    let mut resolutions = Vec::new();
+    for line in modes.lines() {
+        // Interlaced modes carry an "i" suffix, e.g. 1920x1080i
+        let line = line.trim();
+        let interlaced = line.ends_with('i');
+        let Some((width, height)) = line.trim_end_matches('i').split_once('x') else {
+            continue;
+        };
+        if let (Ok(width), Ok(height)) = (width.parse(), height.parse()) {
+            if !resolutions.contains(&(width, height, interlaced)) {
+                resolutions.push((width, height, interlaced));
+            }
+        }
+    }
+
+    Ok(resolutions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(name: &str) -> DrmBackend {
+        DrmBackend::new(
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("fixtures/drm/synthetic")
+                .join(name),
+        )
+    }
+
+    fn rates(backend: &DrmBackend, device_name: &str, width: u32, height: u32) -> Vec<String> {
+        backend
+            .query_modes(device_name)
+            .unwrap()
+            .iter()
+            .filter(|mode| mode.width == width && mode.height == height)
+            .map(|mode| mode.refresh_rate.to_string())
+            .collect()
+    }
+
+    fn copy_tree(from: &Path, to: &Path) {
+        fs::create_dir_all(to).unwrap();
+        for entry in fs::read_dir(from).unwrap() {
+            let entry = entry.unwrap();
+            if entry.file_type().unwrap().is_dir() {
+                copy_tree(&entry.path(), &to.join(entry.file_name()));
+            } else {
+                fs::copy(entry.path(), to.join(entry.file_name())).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn laptop_dock_lists_connected_ports() {
+        let backend = fixture("laptop-dock");
+        let outputs = backend.enumerate_outputs().unwrap();
+        let names: Vec<(&str, &str)> = outputs
+            .iter()
+            .map(|output| (output.device_name.as_str(), output.description.as_str()))
+            .collect();
+        // DP-1 and HDMI-A-1 are disconnected
+        assert_eq!(
+            names,
+            [("DP-2", "DELL S2721DGF"), ("eDP-1", "BOE HF NV156FHM-N61")]
+        );
+
+        assert_eq!(rates(&backend, "eDP-1", 1920, 1080), ["60", "48"]);
+        assert_eq!(rates(&backend, "eDP-1", 1680, 1050), ["60"]);
+        assert_eq!(
+            rates(&backend, "DP-2", 2560, 1440),
+            ["165", "59.951", "144"]
+        );
+        assert!(backend.query_modes("DP-1").unwrap().is_empty());
+        assert!(backend.query_modes("HDMI-A-2").is_err());
+    }
+
+    /// Trees copied from real machines must list every connected monitor
+    /// with its modes and be listed with the machine they came from.
+    #[test]
+    fn every_captured_tree_enumerates() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/drm/captured");
+        let sources = fs::read_to_string(dir.join("README.md")).unwrap();
+        for entry in fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            if !path.is_dir() {
+                continue;
+            }
+            let name = file_name(&path).to_string();
+            assert!(
+                sources.contains(&format!("`{}`", name)),
+                "{} has no source in captured/README.md",
+                name
+            );
+            let backend = DrmBackend::new(&path);
+            for output in backend.enumerate_outputs().unwrap() {
+                let modes = backend.query_modes(&output.device_name).unwrap();
+                assert!(!modes.is_empty(), "{} {}", name, output.device_name);
+                backend.current_mode(&output.device_name).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn copied_tree_reports_the_preferred_mode_as_current() {
+        let backend = fixture("laptop-dock");
+        let current = backend.current_mode("DP-2").unwrap();
+        assert!(current.preferred);
+        assert_eq!(current.to_string(), "2560x1440@165");
+        assert_eq!(backend.vrr_enabled("DP-2").unwrap(), None);
+        assert_eq!(
+            backend.vrr("DP-2").unwrap().and_then(|vrr| vrr.range),
+            Some((48, 165))
+        );
+    }
+
+    #[test]
+    fn hdmi_tv_rates_come_from_the_edid_and_cta_formats() {
+        let backend = fixture("hdmi-tv");
+        let outputs = backend.enumerate_outputs().unwrap();
+        assert_eq!(outputs.len(), 1);
+        assert_eq!(outputs[0].device_name, "HDMI-A-1");
+        assert_eq!(outputs[0].description, "SAMSUNG");
+
+        assert_eq!(
+            rates(&backend, "HDMI-A-1", 3840, 2160),
+            ["60", "50", "30", "25"]
+        );
+        let full_hd = rates(&backend, "HDMI-A-1", 1920, 1080);
+        for rate in ["60", "59.939", "120", "119.879", "50"] {
+            assert!(full_hd.contains(&rate.to_string()), "{:?}", full_hd);
+        }
+        // 720x576 has no EDID timing; the kernel adds the 50Hz CTA format
+        assert_eq!(rates(&backend, "HDMI-A-1", 720, 576), ["50"]);
+    }
+
+    #[test]
+    fn connectors_of_several_cards_keep_the_card() {
+        let root = tempfile::tempdir().unwrap();
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/drm/synthetic");
+        copy_tree(&fixtures.join("hdmi-tv"), root.path());
+        copy_tree(&fixtures.join("laptop-dock"), root.path());
+        let backend = DrmBackend::new(root.path());
+
+        let names: Vec<String> = backend
+            .enumerate_outputs()
+            .unwrap()
+            .into_iter()
+            .map(|output| output.device_name)
+            .collect();
+        assert_eq!(names, ["card0-HDMI-A-1", "card1-DP-2", "card1-eDP-1"]);
+
+        // Unique names still work without the card, ambiguous ones don't
+        assert!(backend.edid("eDP-1").unwrap().is_some());
+        assert!(backend.edid("card0-DP-1").unwrap().is_none());
+        assert!(backend.query_modes("DP-1").is_err());
+    }
+
+    #[test]
+    fn copied_trees_refuse_mode_changes() {
+        let backend = fixture("laptop-dock");
+        let mode = backend.current_mode("DP-2").unwrap();
+        for error in [
+            backend.test_mode("DP-2", &mode).unwrap_err(),
+            backend.apply_modes(&[("DP-2", mode)]).unwrap_err(),
+        ] {
+            assert!(
+                matches!(
+                    HertzRateError::find(&error),
+                    Some(HertzRateError::BackendUnsupported(_))
+                ),
+                "{:#}",
+                error
+            );
+        }
+        let error = backend.set_vrr("DP-2", true).unwrap_err();
+        assert!(
+            matches!(
+                HertzRateError::find(&error),
+                Some(HertzRateError::BackendUnsupported(_))
+            ),
+            "{:#}",
+            error
+        );
+        let error = backend.test_mode("HDMI-A-2", &mode).unwrap_err();
+        assert!(error.to_string().contains("Connector HDMI-A-2 not found"));
+    }
+}
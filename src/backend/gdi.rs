@@ -0,0 +1,483 @@
+use super::{
+    apply_one_by_one, DisplayBackend, DisplayMode, Orientation, Output, Placement, Scaling,
+};
+use crate::edid::Edid;
+use crate::error::HertzRateError;
+use crate::refresh_rate::RefreshRate;
+use anyhow::{anyhow, Result};
+use std::ffi::OsString;
+use std::os::windows::ffi::OsStringExt;
+use windows::core::PCWSTR;
+use windows::Win32::Foundation::{HWND, POINTL};
+use windows::Win32::Graphics::Gdi::{
+    ChangeDisplaySettingsExW, EnumDisplayDevicesW, EnumDisplaySettingsW, CDS_NORESET, CDS_TEST,
+    CDS_TYPE, CDS_UPDATEREGISTRY, DEVMODEW, DEVMODEW_0, DEVMODEW_0_1, DEVMODEW_1, DISPLAY_DEVICEW,
+    DISP_CHANGE, DISP_CHANGE_BADDUALVIEW, DISP_CHANGE_BADFLAGS, DISP_CHANGE_BADMODE,
+    DISP_CHANGE_BADPARAM, DISP_CHANGE_FAILED, DISP_CHANGE_NOTUPDATED, DISP_CHANGE_RESTART,
+    DISP_CHANGE_SUCCESSFUL, DMDFO_CENTER, DMDFO_STRETCH, DMDO_180, DMDO_270, DMDO_90, DMDO_DEFAULT,
+    DM_BITSPERPEL, DM_DISPLAYFIXEDOUTPUT, DM_DISPLAYFLAGS, DM_DISPLAYFREQUENCY,
+    DM_DISPLAYORIENTATION, DM_INTERLACED, DM_PELSHEIGHT, DM_PELSWIDTH, DM_POSITION,
+    ENUM_CURRENT_SETTINGS, ENUM_DISPLAY_SETTINGS_MODE,
+};
+use windows::Win32::System::Registry::{RegGetValueW, HKEY_LOCAL_MACHINE, RRF_RT_REG_BINARY};
+use windows::Win32::UI::WindowsAndMessaging::EDD_GET_DEVICE_INTERFACE_NAME;
+
+/// Windows GDI backend built on `EnumDisplayDevicesW`/`EnumDisplaySettingsW`/
+/// `ChangeDisplaySettingsExW`.
+pub struct GdiBackend {
+    persistent: bool,
+}
+
+impl GdiBackend {
+    /// With `persistent` set, changes are saved with `CDS_UPDATEREGISTRY`;
+    /// otherwise they only last until the next sign-in.
+    pub fn new(persistent: bool) -> Self {
+        Self { persistent }
+    }
+
+    fn flags(&self) -> CDS_TYPE {
+        if self.persistent {
+            CDS_UPDATEREGISTRY
+        } else {
+            CDS_TYPE(0)
+        }
+    }
+}
+
+impl DisplayBackend for GdiBackend {
+    fn name(&self) -> &'static str {
+        "gdi"
+    }
+
+    fn enumerate_outputs(&self) -> Result<Vec<Output>> {
+        let mut outputs = Vec::new();
+        let mut device_index = 0;
+
+        loop {
+            let mut display_device = DISPLAY_DEVICEW {
+                cb: std::mem::size_of::<DISPLAY_DEVICEW>() as u32,
+                ..Default::default()
+            };
+
+            let success = unsafe {
+                EnumDisplayDevicesW(PCWSTR::null(), device_index, &mut display_device, 0)
+            };
+
+            if !success.as_bool() {
+                break;
+            }
+
+            // Skip if not attached to desktop
+            if display_device.StateFlags & 0x00000001 == 0 {
+                device_index += 1;
+                continue;
+            }
+
+            // The EDID has the model name; DeviceString is often "Generic PnP Monitor"
+            let device_name = wide_string_to_string(&display_device.DeviceName);
+            let description = self
+                .edid(&device_name)
+                .ok()
+                .flatten()
+                .and_then(|edid| Edid::parse(&edid).ok())
+                .map(|edid| edid.description())
+                .unwrap_or_else(|| wide_string_to_string(&display_device.DeviceString));
+
+            outputs.push(Output {
+                device_name,
+                description,
+            });
+
+            device_index += 1;
+        }
+
+        Ok(outputs)
+    }
+
+    fn current_mode(&self, device_name: &str) -> Result<DisplayMode> {
+        Ok(to_display_mode(&current_settings(device_name)?))
+    }
+
+    fn query_modes(&self, device_name: &str) -> Result<Vec<DisplayMode>> {
+        let device_name_wide = string_to_wide(device_name);
+        let mut modes = Vec::new();
+        let mut mode_index = 0;
+
+        loop {
+            let mut mode = DEVMODEW {
+                dmSize: std::mem::size_of::<DEVMODEW>() as u16,
+                ..Default::default()
+            };
+
+            let success = unsafe {
+                EnumDisplaySettingsW(
+                    PCWSTR(device_name_wide.as_ptr()),
+                    ENUM_DISPLAY_SETTINGS_MODE(mode_index),
+                    &mut mode,
+                )
+            };
+
+            if !success.as_bool() {
+                break;
+            }
+
+            modes.push(to_display_mode(&mode));
+
+            mode_index += 1;
+        }
+
+        Ok(modes)
+    }
+
+    fn test_mode(&self, device_name: &str, mode: &DisplayMode) -> Result<()> {
+        change_display_settings(device_name, mode, CDS_TEST)
+    }
+
+    fn apply_mode(&self, device_name: &str, mode: &DisplayMode) -> Result<()> {
+        change_display_settings(device_name, mode, self.flags())
+    }
+
+    /// Stages every change in the registry with `CDS_NORESET`, then applies
+    /// them together with one final `ChangeDisplaySettingsExW` call. Staging
+    /// needs `CDS_UPDATEREGISTRY`, so temporary changes are made one output
+    /// at a time instead.
+    fn apply_modes(&self, changes: &[(&str, DisplayMode)]) -> Result<()> {
+        if !self.persistent {
+            return apply_one_by_one(self, changes);
+        }
+
+        let mut staged: Vec<(&str, DEVMODEW)> = Vec::new();
+        let mut result = Ok(());
+        for &(device_name, mode) in changes {
+            result = current_settings(device_name).and_then(|previous| {
+                change_display_settings(device_name, &mode, CDS_UPDATEREGISTRY | CDS_NORESET)?;
+                staged.push((device_name, previous));
+                Ok(())
+            });
+            if result.is_err() {
+                break;
+            }
+        }
+        let result = result.and_then(|()| commit_staged_settings());
+
+        // Nothing has taken effect if staging or the commit failed, but the
+        // registry still holds the staged modes
+        if result.is_err() {
+            for (device_name, previous) in &staged {
+                let _ = submit_settings(device_name, previous, CDS_UPDATEREGISTRY | CDS_NORESET);
+            }
+        }
+        result
+    }
+
+    /// Reads the EDID Windows caches in the monitor's registry key, found
+    /// through the device interface name of the monitor on the adapter.
+    fn edid(&self, device_name: &str) -> Result<Option<Vec<u8>>> {
+        let mut monitor = DISPLAY_DEVICEW {
+            cb: std::mem::size_of::<DISPLAY_DEVICEW>() as u32,
+            ..Default::default()
+        };
+        let adapter = string_to_wide(device_name);
+        let found = unsafe {
+            EnumDisplayDevicesW(
+                PCWSTR(adapter.as_ptr()),
+                0,
+                &mut monitor,
+                EDD_GET_DEVICE_INTERFACE_NAME,
+            )
+        };
+        if !found.as_bool() {
+            return Ok(None);
+        }
+
+        // \\?\DISPLAY#DEL41A8#5&1a2b3c4d&0&UID4353#{e6f07b5f-ee97-4a90-b076-33f57bf4eaa7}
+        let interface = wide_string_to_string(&monitor.DeviceID);
+        let parts: Vec<&str> = interface.trim_start_matches(r"\\?\").split('#').collect();
+        let [_, model, instance, ..] = parts[..] else {
+            return Ok(None);
+        };
+        let key = format!(
+            r"SYSTEM\CurrentControlSet\Enum\DISPLAY\{}\{}\Device Parameters",
+            model, instance
+        );
+        Ok(read_registry_binary(&key, "EDID"))
+    }
+
+    fn placement(&self, device_name: &str) -> Result<Option<Placement>> {
+        let current = current_settings(device_name)?;
+        let (position, orientation) = unsafe {
+            (
+                current.Anonymous1.Anonymous2.dmPosition,
+                current.Anonymous1.Anonymous2.dmDisplayOrientation,
+            )
+        };
+
+        Ok(Some(Placement {
+            x: position.x,
+            y: position.y,
+            // GDI measures rotation clockwise
+            orientation: match orientation {
+                DMDO_90 => Orientation::Rotate270,
+                DMDO_180 => Orientation::Rotate180,
+                DMDO_270 => Orientation::Rotate90,
+                _ => Orientation::Normal,
+            },
+        }))
+    }
+
+    fn apply_placement(&self, device_name: &str, placement: &Placement) -> Result<()> {
+        if placement.orientation.flipped() {
+            return Err(HertzRateError::BackendUnsupported(
+                "GDI cannot flip a display".to_string(),
+            )
+            .into());
+        }
+        let current = current_settings(device_name)?;
+        let current_orientation = unsafe { current.Anonymous1.Anonymous2.dmDisplayOrientation };
+
+        // The desktop size follows the rotation, so portrait swaps the sides
+        let was_portrait = matches!(current_orientation, DMDO_90 | DMDO_270);
+        let portrait = matches!(placement.orientation.degrees(), 90 | 270);
+        let (width, height) = if was_portrait == portrait {
+            (current.dmPelsWidth, current.dmPelsHeight)
+        } else {
+            (current.dmPelsHeight, current.dmPelsWidth)
+        };
+
+        let new_mode = DEVMODEW {
+            dmSize: std::mem::size_of::<DEVMODEW>() as u16,
+            dmFields: DM_PELSWIDTH | DM_PELSHEIGHT | DM_POSITION | DM_DISPLAYORIENTATION,
+            dmPelsWidth: width,
+            dmPelsHeight: height,
+            Anonymous1: DEVMODEW_0 {
+                Anonymous2: DEVMODEW_0_1 {
+                    dmPosition: POINTL {
+                        x: placement.x,
+                        y: placement.y,
+                    },
+                    dmDisplayOrientation: match placement.orientation.degrees() {
+                        90 => DMDO_270,
+                        180 => DMDO_180,
+                        270 => DMDO_90,
+                        _ => DMDO_DEFAULT,
+                    },
+                    ..Default::default()
+                },
+            },
+            ..Default::default()
+        };
+
+        submit_settings(device_name, &new_mode, self.flags())
+    }
+}
+
+fn current_settings(device_name: &str) -> Result<DEVMODEW> {
+    let device_name_wide = string_to_wide(device_name);
+
+    let mut current_mode = DEVMODEW {
+        dmSize: std::mem::size_of::<DEVMODEW>() as u16,
+        ..Default::default()
+    };
+
+    let success = unsafe {
+        EnumDisplaySettingsW(
+            PCWSTR(device_name_wide.as_ptr()),
+            ENUM_CURRENT_SETTINGS,
+            &mut current_mode,
+        )
+    };
+
+    if !success.as_bool() {
+        return Err(anyhow!(
+            "Failed to get current display settings for {}",
+            device_name
+        ));
+    }
+
+    Ok(current_mode)
+}
+
+fn change_display_settings(device_name: &str, mode: &DisplayMode, flags: CDS_TYPE) -> Result<()> {
+    let mut new_mode = DEVMODEW {
+        dmSize: std::mem::size_of::<DEVMODEW>() as u16,
+        dmFields: DM_PELSWIDTH | DM_PELSHEIGHT | DM_DISPLAYFREQUENCY | DM_DISPLAYFLAGS,
+        dmPelsWidth: mode.width,
+        dmPelsHeight: mode.height,
+        dmDisplayFrequency: mode.refresh_rate.round_hz(),
+        ..Default::default()
+    };
+    new_mode.Anonymous2 = DEVMODEW_1 {
+        dmDisplayFlags: if mode.interlaced { DM_INTERLACED.0 } else { 0 },
+    };
+    if let Some(bit_depth) = mode.bit_depth {
+        new_mode.dmFields |= DM_BITSPERPEL;
+        new_mode.dmBitsPerPel = bit_depth;
+    }
+    if mode.scaling != Scaling::Default {
+        new_mode.dmFields |= DM_DISPLAYFIXEDOUTPUT;
+        new_mode.Anonymous1 = DEVMODEW_0 {
+            Anonymous2: DEVMODEW_0_1 {
+                dmDisplayFixedOutput: match mode.scaling {
+                    Scaling::Centered => DMDFO_CENTER,
+                    _ => DMDFO_STRETCH,
+                },
+                ..Default::default()
+            },
+        };
+    }
+
+    submit_settings(device_name, &new_mode, flags)
+}
+
+fn submit_settings(device_name: &str, new_mode: &DEVMODEW, flags: CDS_TYPE) -> Result<()> {
+    let device_name_wide = string_to_wide(device_name);
+
+    // With CDS_TEST the driver only reports whether the mode would be accepted
+    let result = unsafe {
+        ChangeDisplaySettingsExW(
+            PCWSTR(device_name_wide.as_ptr()),
+            Some(new_mode),
+            HWND::default(),
+            flags,
+            None,
+        )
+    };
+    check_disp_change(result)
+}
+
+/// Applies the settings staged with `CDS_NORESET` to all outputs at once.
+fn commit_staged_settings() -> Result<()> {
+    let result = unsafe {
+        ChangeDisplaySettingsExW(PCWSTR::null(), None, HWND::default(), CDS_TYPE(0), None)
+    };
+    check_disp_change(result)
+}
+
+fn check_disp_change(result: DISP_CHANGE) -> Result<()> {
+    let error = match result {
+        DISP_CHANGE_SUCCESSFUL => return Ok(()),
+        DISP_CHANGE_RESTART => HertzRateError::RestartRequired,
+        DISP_CHANGE_BADMODE => HertzRateError::BadMode(
+            "The graphics mode is not supported (DISP_CHANGE_BADMODE)".to_string(),
+        ),
+        DISP_CHANGE_NOTUPDATED => HertzRateError::PermissionDenied(
+            "Unable to write the settings to the registry (DISP_CHANGE_NOTUPDATED)".to_string(),
+        ),
+        DISP_CHANGE_FAILED => HertzRateError::DriverRejected(
+            "The display driver failed the specified graphics mode (DISP_CHANGE_FAILED)"
+                .to_string(),
+        ),
+        DISP_CHANGE_BADFLAGS => HertzRateError::DriverRejected(
+            "An invalid set of flags was passed (DISP_CHANGE_BADFLAGS)".to_string(),
+        ),
+        DISP_CHANGE_BADPARAM => HertzRateError::DriverRejected(
+            "An invalid parameter was passed (DISP_CHANGE_BADPARAM)".to_string(),
+        ),
+        DISP_CHANGE_BADDUALVIEW => HertzRateError::DriverRejected(
+            "The system is DualView capable (DISP_CHANGE_BADDUALVIEW)".to_string(),
+        ),
+        _ => HertzRateError::DriverRejected(format!("Error code: {}", result.0)),
+    };
+    Err(error.into())
+}
+
+fn to_display_mode(mode: &DEVMODEW) -> DisplayMode {
+    // Display flags and fixed output live in unions; for display devices
+    // the display variants are the ones the driver fills in
+    let (display_flags, fixed_output) = unsafe {
+        (
+            mode.Anonymous2.dmDisplayFlags,
+            mode.Anonymous1.Anonymous2.dmDisplayFixedOutput,
+        )
+    };
+
+    DisplayMode {
+        bit_depth: Some(mode.dmBitsPerPel),
+        interlaced: display_flags & DM_INTERLACED.0 != 0,
+        scaling: match fixed_output {
+            DMDFO_STRETCH => Scaling::Stretched,
+            DMDFO_CENTER => Scaling::Centered,
+            _ => Scaling::Default,
+        },
+        ..DisplayMode::new(
+            mode.dmPelsWidth,
+            mode.dmPelsHeight,
+            RefreshRate::from_hz(mode.dmDisplayFrequency),
+        )
+    }
+}
+
+/// A REG_BINARY value under HKEY_LOCAL_MACHINE, or `None` if it is missing.
+fn read_registry_binary(key: &str, value: &str) -> Option<Vec<u8>> {
+    let key = string_to_wide(key);
+    let value = string_to_wide(value);
+    let read = |data: Option<*mut std::ffi::c_void>, size: &mut u32| unsafe {
+        RegGetValueW(
+            HKEY_LOCAL_MACHINE,
+            PCWSTR(key.as_ptr()),
+            PCWSTR(value.as_ptr()),
+            RRF_RT_REG_BINARY,
+            None,
+            data,
+            Some(size as *mut u32),
+        )
+    };
+
+    let mut size = 0u32;
+    read(None, &mut size).ok()?;
+    let mut data = vec![0u8; size as usize];
+    read(Some(data.as_mut_ptr().cast()), &mut size).ok()?;
+    data.truncate(size as usize);
+    Some(data)
+}
+
+fn wide_string_to_string(wide_str: &[u16]) -> String {
+    let end = wide_str
+        .iter()
+        .position(|&c| c == 0)
+        .unwrap_or(wide_str.len());
+    OsString::from_wide(&wide_str[..end])
+        .to_string_lossy()
+        .to_string()
+}
+
+fn string_to_wide(s: &str) -> Vec<u16> {
+    let mut wide: Vec<u16> = s.encode_utf16().collect();
+    wide.push(0); // null terminator
+    wide
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Changes the real display configuration and needs two monitors, so it
+    /// only runs with `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn staged_batch_is_dropped_when_the_second_monitor_rejects() {
+        let backend = GdiBackend::new(true);
+        let outputs = backend.enumerate_outputs().unwrap();
+        let [first, second, ..] = &outputs[..] else {
+            return;
+        };
+        let before = backend.current_mode(&first.device_name).unwrap();
+        let Some(other) = backend
+            .query_modes(&first.device_name)
+            .unwrap()
+            .into_iter()
+            .find(|mode| mode.resolution() == before.resolution() && !mode.same_timing(&before))
+        else {
+            return;
+        };
+        let impossible = DisplayMode::new(12345, 678, RefreshRate::from_hz(1));
+
+        let result = backend.apply_modes(&[
+            (first.device_name.as_str(), other),
+            (second.device_name.as_str(), impossible),
+        ]);
+        assert!(result.is_err());
+        let after = backend.current_mode(&first.device_name).unwrap();
+        assert!(after.same_timing(&before), "{} became {}", before, after);
+    }
+}
@@ -0,0 +1,516 @@
+use super::DisplayMode;
+use crate::error::HertzRateError;
+use crate::refresh_rate::RefreshRate;
+use anyhow::{anyhow, Context, Result};
+use drm::control::atomic::AtomicModeReq;
+use drm::control::{
+    connector, crtc, plane, property, AtomicCommitFlags, Device as ControlDevice, Mode, ModeFlags,
+    ModeTypeFlags, ResourceHandle,
+};
+use drm::{ClientCapability, Device};
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::os::fd::{AsFd, BorrowedFd};
+use std::path::{Path, PathBuf};
+
+const DRI_DIR: &str = "/dev/dri";
+
+/// A DRM card node. Reading connector properties needs no DRM master, only
+/// access to the node; modesetting needs master.
+struct Card(File);
+
+impl AsFd for Card {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0.as_fd()
+    }
+}
+
+impl Device for Card {}
+impl ControlDevice for Card {}
+
+/// The `vrr_capable` property of the connector named like the kernel's
+/// sysfs entries (e.g. `DP-1`), or `None` when no card has that connector or
+/// its driver does not expose the property.
+pub(super) fn vrr_capable(connector_name: &str) -> Option<bool> {
+    find_connector(connector_name, |card, connector| {
+        property(card, connector.handle(), b"vrr_capable").map(|value| value != 0)
+    })
+}
+
+/// The `VRR_ENABLED` property of the CRTC driving the connector, or `None`
+/// when the connector is off or the driver has no such property.
+pub(super) fn vrr_enabled(connector_name: &str) -> Option<bool> {
+    find_connector(connector_name, |card, connector| {
+        let encoder = card.get_encoder(connector.current_encoder()?).ok()?;
+        property(card, encoder.crtc()?, b"VRR_ENABLED").map(|value| value != 0)
+    })
+}
+
+/// Turns the `VRR_ENABLED` property of the CRTC driving the connector on or
+/// off with an atomic commit, which like a modeset needs the DRM master.
+pub(super) fn commit_vrr(connector_name: &str, enabled: bool) -> Result<()> {
+    let card = Card::open_for_modeset(&modeset_node(connector_name)?)?;
+    let (_, crtc) = card.driving_crtc(connector_name)?;
+    let mut request = AtomicModeReq::new();
+    request.add_raw_property(
+        crtc.into(),
+        property_handle(&card, crtc, "VRR_ENABLED")?,
+        u64::from(enabled),
+    );
+    card.atomic_commit(AtomicCommitFlags::empty(), request)
+        .map_err(|e| kms_error(e, &format!("VRR for {}", connector_name)))
+}
+
+/// The mode the CRTC driving the connector runs, with its exact rate, or
+/// `None` when the connector is off.
+pub(super) fn current_mode(connector_name: &str) -> Option<DisplayMode> {
+    find_connector(connector_name, |card, connector| {
+        let encoder = card.get_encoder(connector.current_encoder()?).ok()?;
+        let mode = card.get_crtc(encoder.crtc()?).ok()?.mode()?;
+        display_mode(&mode)
+    })
+}
+
+/// The modes the kernel lists for the connector, with the exact rates of
+/// their timings, or `None` when no card has that connector.
+pub(super) fn connector_modes(connector_name: &str) -> Option<Vec<DisplayMode>> {
+    find_connector(connector_name, |_, connector| {
+        let mut modes: Vec<DisplayMode> = Vec::new();
+        for mode in connector.modes() {
+            let Some(display_mode) = display_mode(mode) else {
+                continue;
+            };
+            let preferred = mode.mode_type().contains(ModeTypeFlags::PREFERRED);
+            match modes.iter_mut().find(|m| m.same_timing(&display_mode)) {
+                Some(existing) => existing.preferred |= preferred,
+                None => modes.push(DisplayMode {
+                    preferred,
+                    ..display_mode
+                }),
+            }
+        }
+        Some(modes)
+    })
+}
+
+/// Switches each connector's CRTC to its mode, with one atomic commit per
+/// card, or with `test_only` just asks the driver whether it would accept
+/// them (`DRM_MODE_ATOMIC_TEST_ONLY`). The kernel takes atomic commits, even
+/// test-only ones, only from the DRM master. When a card's commit fails, the
+/// cards already switched get their previous modes back.
+pub(super) fn commit_modes(changes: &[(&str, DisplayMode)], test_only: bool) -> Result<()> {
+    let mut cards: Vec<(PathBuf, Vec<(&str, DisplayMode)>)> = Vec::new();
+    for &(connector_name, mode) in changes {
+        let node = modeset_node(connector_name)?;
+        match cards.iter_mut().find(|(path, _)| *path == node) {
+            Some((_, changes)) => changes.push((connector_name, mode)),
+            None => cards.push((node, vec![(connector_name, mode)])),
+        }
+    }
+
+    // Several cards can't share a commit, so all of them are tested first
+    let mut requests = Vec::new();
+    for (node, changes) in &cards {
+        let request = ModesetRequest::new(Card::open_for_modeset(node)?, changes)?;
+        request.commit(true)?;
+        requests.push(request);
+    }
+    if test_only {
+        return Ok(());
+    }
+    for (index, request) in requests.iter().enumerate() {
+        if let Err(e) = request.commit(false) {
+            // The failed commit changed nothing on its card
+            let unrestored = requests[..index]
+                .iter()
+                .rev()
+                .filter(|request| request.restore().is_err())
+                .count();
+            if unrestored > 0 {
+                return Err(e.context(format!(
+                    "{} card(s) already switched could not be restored",
+                    unrestored
+                )));
+            }
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+/// An atomic request setting the `MODE_ID` of the CRTCs on one card, and
+/// scaling their primary planes to a new resolution, with the request that
+/// puts their previous state back. The mode blobs both use are destroyed on
+/// drop; the kernel keeps those a CRTC still runs.
+struct ModesetRequest {
+    card: Card,
+    request: AtomicModeReq,
+    previous: AtomicModeReq,
+    blobs: Vec<u64>,
+}
+
+impl ModesetRequest {
+    fn new(card: Card, changes: &[(&str, DisplayMode)]) -> Result<Self> {
+        let mut modeset = Self {
+            card,
+            request: AtomicModeReq::new(),
+            previous: AtomicModeReq::new(),
+            blobs: Vec::new(),
+        };
+        for (connector_name, mode) in changes {
+            modeset.add(connector_name, mode)?;
+        }
+        Ok(modeset)
+    }
+
+    fn add(&mut self, connector_name: &str, target: &DisplayMode) -> Result<()> {
+        let card = &self.card;
+        let (connector, crtc) = card.driving_crtc(connector_name)?;
+        let candidates = connector
+            .modes()
+            .iter()
+            .filter_map(|mode| Some((mode, display_mode(mode)?)));
+        let mode = closest_mode(candidates, target).ok_or_else(|| {
+            HertzRateError::BadMode(format!(
+                "Connector {} has no mode {}",
+                connector_name, target
+            ))
+        })?;
+
+        let current = card
+            .get_crtc(crtc)
+            .ok()
+            .and_then(|crtc| crtc.mode())
+            .ok_or_else(|| anyhow!("The CRTC of connector {} runs no mode", connector_name))?;
+
+        let mode_id = property_handle(card, crtc, "MODE_ID")?;
+        for (request, mode) in [(&mut self.request, mode), (&mut self.previous, &current)] {
+            let blob = card
+                .create_property_blob(mode)
+                .map_err(|e| kms_error(e, connector_name))?;
+            self.blobs.push(u64::from(blob));
+            request.add_property(crtc, mode_id, blob);
+        }
+
+        if current.size() == mode.size() {
+            return Ok(());
+        }
+        let (width, height) = mode.size();
+        let plane = primary_plane(card, crtc)
+            .ok_or_else(|| anyhow!("No primary plane drives connector {}", connector_name))?;
+        for (name, value) in [
+            ("CRTC_X", 0),
+            ("CRTC_Y", 0),
+            ("CRTC_W", u64::from(width)),
+            ("CRTC_H", u64::from(height)),
+        ] {
+            let handle = property_handle(card, plane, name)?;
+            let previous = property(card, plane, name.as_bytes()).ok_or_else(|| {
+                anyhow!("Failed to read {} of the plane of {}", name, connector_name)
+            })?;
+            self.request.add_raw_property(plane.into(), handle, value);
+            self.previous
+                .add_raw_property(plane.into(), handle, previous);
+        }
+        Ok(())
+    }
+
+    fn commit(&self, test_only: bool) -> Result<()> {
+        let mut flags = AtomicCommitFlags::ALLOW_MODESET;
+        if test_only {
+            flags |= AtomicCommitFlags::TEST_ONLY;
+        }
+        self.card
+            .atomic_commit(flags, self.request.clone())
+            .map_err(|e| kms_error(e, "the new modes"))
+    }
+
+    /// Puts the state from before `commit` back.
+    fn restore(&self) -> Result<()> {
+        self.card
+            .atomic_commit(AtomicCommitFlags::ALLOW_MODESET, self.previous.clone())
+            .map_err(|e| kms_error(e, "the previous modes"))
+    }
+}
+
+impl Drop for ModesetRequest {
+    fn drop(&mut self) {
+        for &blob in &self.blobs {
+            let _ = self.card.destroy_property_blob(blob);
+        }
+    }
+}
+
+impl Card {
+    /// Opens a card node for atomic modesetting.
+    fn open_for_modeset(node: &Path) -> Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(node)
+            .map_err(|e| kms_error(e, &node.display().to_string()))?;
+        let card = Card(file);
+        card.set_client_capability(ClientCapability::Atomic, true)
+            .map_err(|_| {
+                HertzRateError::BackendUnsupported(format!(
+                    "The driver of {} has no atomic modesetting",
+                    node.display()
+                ))
+            })?;
+        Ok(card)
+    }
+
+    /// The connector named like `card1-DP-1` or `DP-1` and the CRTC driving
+    /// it, for changing its state.
+    fn driving_crtc(&self, connector_name: &str) -> Result<(connector::Info, crtc::Handle)> {
+        let (_, name) = split_card(connector_name);
+        let connector = self
+            .connector(name)
+            .ok_or_else(|| anyhow!("Connector {} disappeared", connector_name))?;
+        let crtc = connector
+            .current_encoder()
+            .and_then(|encoder| self.get_encoder(encoder).ok())
+            .and_then(|encoder| encoder.crtc())
+            .ok_or_else(|| {
+                HertzRateError::DriverRejected(format!(
+                    "Connector {} is not driven by a CRTC",
+                    connector_name
+                ))
+            })?;
+        Ok((connector, crtc))
+    }
+
+    /// The connector named like `DP-1`, with its modes.
+    fn connector(&self, connector_name: &str) -> Option<connector::Info> {
+        let resources = self.resource_handles().ok()?;
+        resources.connectors().iter().find_map(|&handle| {
+            let info = self.get_connector(handle, false).ok()?;
+            let name = format!("{}-{}", info.interface().as_str(), info.interface_id());
+            (name == connector_name).then_some(info)
+        })
+    }
+}
+
+/// The `DisplayMode` of a KMS mode, with the exact rate of its timing.
+fn display_mode(mode: &Mode) -> Option<DisplayMode> {
+    let (width, height) = mode.size();
+    let (_, _, htotal) = mode.hsync();
+    let (_, _, vtotal) = mode.vsync();
+    let mut dot_clock = u64::from(mode.clock()) * 1000;
+    let mut vtotal = u64::from(vtotal);
+    if mode.flags().contains(ModeFlags::DBLSCAN) {
+        vtotal *= 2;
+    }
+    let interlaced = mode.flags().contains(ModeFlags::INTERLACE);
+    if interlaced {
+        dot_clock *= 2;
+    }
+    (htotal != 0 && vtotal != 0).then(|| DisplayMode {
+        interlaced,
+        ..DisplayMode::new(
+            u32::from(width),
+            u32::from(height),
+            RefreshRate::from_timing(dot_clock, u64::from(htotal), vtotal),
+        )
+    })
+}
+
+/// How far a kernel timing's rate may be from the rate asked for, in
+/// millihertz. EDID standard timings only give whole rates, and the DMT
+/// timing behind 640x480@72, for one, runs at 72.809Hz.
+const RATE_TOLERANCE_MILLIHERTZ: u32 = 1000;
+
+/// The mode with `target`'s resolution and scan type whose rate is closest
+/// to it, as long as it is within the tolerance. Modes listed from EDID
+/// rates may have nominal rates such as 60 where the kernel's timing runs at
+/// 60.004Hz.
+fn closest_mode<T>(
+    modes: impl IntoIterator<Item = (T, DisplayMode)>,
+    target: &DisplayMode,
+) -> Option<T> {
+    let distance = |mode: &DisplayMode| {
+        mode.refresh_rate
+            .millihertz()
+            .abs_diff(target.refresh_rate.millihertz())
+    };
+    modes
+        .into_iter()
+        .filter(|(_, mode)| mode.resolution() == target.resolution())
+        .filter(|(_, mode)| distance(mode) <= RATE_TOLERANCE_MILLIHERTZ)
+        .min_by_key(|(_, mode)| distance(mode))
+        .map(|(mode, _)| mode)
+}
+
+/// The primary plane scanning out on `crtc`.
+fn primary_plane(card: &Card, crtc: crtc::Handle) -> Option<plane::Handle> {
+    card.plane_handles().ok()?.into_iter().find(|&plane| {
+        card.get_plane(plane)
+            .is_ok_and(|info| info.crtc() == Some(crtc))
+            && property(card, plane, b"type") == Some(PLANE_TYPE_PRIMARY)
+    })
+}
+
+fn property_handle(
+    card: &Card,
+    handle: impl ResourceHandle,
+    name: &str,
+) -> Result<property::Handle> {
+    let properties = card
+        .get_properties(handle)
+        .context("Failed to read KMS properties")?;
+    let found = properties
+        .iter()
+        .map(|(&property, _)| property)
+        .find(|&property| {
+            card.get_property(property)
+                .is_ok_and(|info| info.name().to_bytes() == name.as_bytes())
+        });
+    found.ok_or_else(|| {
+        HertzRateError::BackendUnsupported(format!("The driver has no {} property", name)).into()
+    })
+}
+
+/// Classifies a failed KMS call: only the DRM master may commit, and the
+/// driver answers `EINVAL` for configurations it can't drive.
+fn kms_error(error: io::Error, what: &str) -> anyhow::Error {
+    match error.raw_os_error() {
+        Some(libc_errno::EACCES | libc_errno::EPERM) => HertzRateError::PermissionDenied(
+            "Changing modes or VRR through KMS needs DRM master, which the running compositor \
+             or display server holds; use its backend instead, or run from a text console"
+                .to_string(),
+        )
+        .into(),
+        Some(libc_errno::EINVAL | libc_errno::ERANGE | libc_errno::ENOSPC) => {
+            HertzRateError::DriverRejected(format!("The driver rejected {} ({})", what, error))
+                .into()
+        }
+        _ => anyhow::Error::new(error).context(format!("KMS call for {} failed", what)),
+    }
+}
+
+/// The `errno` values `kms_error` tells apart.
+mod libc_errno {
+    pub const EPERM: i32 = 1;
+    pub const EACCES: i32 = 13;
+    pub const EINVAL: i32 = 22;
+    pub const ENOSPC: i32 = 28;
+    pub const ERANGE: i32 = 34;
+}
+
+/// Value of a plane's `type` property for primary planes.
+const PLANE_TYPE_PRIMARY: u64 = 1;
+
+/// Runs `f` on the first card with a connector named `connector_name`, or on
+/// the named card for names like `card1-DP-1`.
+fn find_connector<T>(
+    connector_name: &str,
+    f: impl Fn(&Card, &connector::Info) -> Option<T>,
+) -> Option<T> {
+    let (card_name, connector_name) = split_card(connector_name);
+    card_nodes(card_name).iter().find_map(|node| {
+        let card = Card(File::open(node).ok()?);
+        let connector = card.connector(connector_name)?;
+        f(&card, &connector)
+    })
+}
+
+/// The card node to open for changing the connector's state.
+fn modeset_node(connector_name: &str) -> Result<PathBuf> {
+    find_node(connector_name).ok_or_else(|| {
+        anyhow!(
+            "No DRM card has a connector {}; is this the console of the machine?",
+            connector_name
+        )
+    })
+}
+
+/// The card node with a connector named `connector_name`.
+fn find_node(connector_name: &str) -> Option<PathBuf> {
+    let (card_name, name) = split_card(connector_name);
+    card_nodes(card_name)
+        .into_iter()
+        .find(|node| File::open(node).is_ok_and(|file| Card(file).connector(name).is_some()))
+}
+
+/// Splits `card1-DP-1` into the card and the connector name.
+fn split_card(connector_name: &str) -> (Option<&str>, &str) {
+    match connector_name.split_once('-') {
+        Some((card, connector))
+            if card.strip_prefix("card").is_some_and(|number| {
+                !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit())
+            }) =>
+        {
+            (Some(card), connector)
+        }
+        _ => (None, connector_name),
+    }
+}
+
+/// The card nodes under `/dev/dri`, sorted, or only `card_name`'s.
+fn card_nodes(card_name: Option<&str>) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(DRI_DIR) else {
+        return Vec::new();
+    };
+    let mut nodes: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| match card_name {
+                    Some(card_name) => name == card_name,
+                    None => name.starts_with("card"),
+                })
+        })
+        .collect();
+    nodes.sort();
+    nodes
+}
+
+fn property(card: &Card, handle: impl ResourceHandle, name: &[u8]) -> Option<u64> {
+    let properties = card.get_properties(handle).ok()?;
+    let value = properties.iter().find_map(|(&property, &value)| {
+        let property = card.get_property(property).ok()?;
+        (property.name().to_bytes() == name).then_some(value)
+    });
+    value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn connector_names_may_start_with_their_card() {
+        assert_eq!(split_card("card1-DP-1"), (Some("card1"), "DP-1"));
+        assert_eq!(split_card("DP-1"), (None, "DP-1"));
+        assert_eq!(split_card("eDP-1"), (None, "eDP-1"));
+        assert_eq!(split_card("card-DP-1"), (None, "card-DP-1"));
+    }
+
+    #[test]
+    fn nominal_rates_find_the_kernel_timing() {
+        let modes: Vec<DisplayMode> = [
+            "1024x768@60004/1000",
+            "2560x1440@143912/1000",
+            "2560x1440@59951/1000",
+            "1920x1080@60",
+            "1920x1080@60000/1001",
+            "1920x1080i@60",
+        ]
+        .iter()
+        .map(|mode| mode.parse().unwrap())
+        .collect();
+        let closest = |target: &str| {
+            closest_mode(modes.iter().copied().enumerate(), &target.parse().unwrap())
+        };
+        assert_eq!(closest("1024x768@60"), Some(0));
+        assert_eq!(closest("2560x1440@144"), Some(1));
+        assert_eq!(closest("2560x1440@60"), Some(2));
+        assert_eq!(closest("1920x1080@60"), Some(3));
+        assert_eq!(closest("1920x1080@59.94"), Some(4));
+        assert_eq!(closest("1920x1080i@60"), Some(5));
+        assert_eq!(closest("2560x1440@120"), None);
+        assert_eq!(closest("1280x720@60"), None);
+    }
+}
@@ -0,0 +1,681 @@
+mod drm;
+#[cfg(windows)]
+mod gdi;
+#[cfg(target_os = "linux")]
+mod kms;
+#[cfg(target_os = "linux")]
+mod mutter;
+mod sim;
+#[cfg(target_os = "linux")]
+mod wayland;
+#[cfg(target_os = "linux")]
+mod x11;
+
+pub use drm::{DrmBackend, DEFAULT_SYSFS_ROOT};
+#[cfg(windows)]
+pub use gdi::GdiBackend;
+#[cfg(target_os = "linux")]
+pub use mutter::MutterBackend;
+pub use sim::SimBackend;
+#[cfg(target_os = "linux")]
+pub use wayland::WaylandBackend;
+#[cfg(target_os = "linux")]
+pub use x11::X11Backend;
+
+use crate::edid::Edid;
+use crate::error::HertzRateError;
+use crate::refresh_rate::RefreshRate;
+use anyhow::{anyhow, Result};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+/// A display output as reported by a backend, before its modes are queried.
+#[derive(Debug, Clone)]
+pub struct Output {
+    pub device_name: String,
+    pub description: String,
+}
+
+/// A single display mode as reported by a backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayMode {
+    pub width: u32,
+    pub height: u32,
+    pub refresh_rate: RefreshRate,
+    /// Bits per pixel, where the backend reports it
+    pub bit_depth: Option<u32>,
+    pub interlaced: bool,
+    pub scaling: Scaling,
+    /// The monitor's preferred (usually native) timing
+    pub preferred: bool,
+}
+
+/// The size and scan type of a mode, without its refresh rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+    pub interlaced: bool,
+}
+
+/// What a monitor reports about itself: the EDID manufacturer ID, product
+/// code and serial number, or the equivalent strings a compositor passes on.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct MonitorIdentity {
+    pub manufacturer: String,
+    pub product: String,
+    pub serial: Option<String>,
+}
+
+/// Variable refresh rate support: the range the panel can follow and where
+/// the support was found.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct VrrCapability {
+    /// Lowest and highest refresh rate in Hz, when known
+    pub range: Option<(u32, u32)>,
+    pub source: VrrSource,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum VrrSource {
+    /// AMD's vendor-specific data block in the EDID
+    #[serde(rename = "freesync")]
+    FreeSync,
+    /// The HDMI Forum vendor-specific data block in the EDID
+    HdmiVrr,
+    /// Continuous-frequency range limits in the EDID, as used by
+    /// DisplayPort Adaptive-Sync (FreeSync and G-SYNC Compatible)
+    AdaptiveSync,
+    /// The driver, e.g. the DRM `vrr_capable` connector property
+    Driver,
+}
+
+/// Where an output sits on the desktop and how it is rotated.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Placement {
+    pub x: i32,
+    pub y: i32,
+    pub orientation: Orientation,
+}
+
+/// Rotation counter-clockwise and optional horizontal flip, using the
+/// numbering of Wayland's `wl_output.transform`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Orientation {
+    #[default]
+    #[serde(rename = "normal")]
+    Normal,
+    #[serde(rename = "90")]
+    Rotate90,
+    #[serde(rename = "180")]
+    Rotate180,
+    #[serde(rename = "270")]
+    Rotate270,
+    #[serde(rename = "flipped")]
+    Flipped,
+    #[serde(rename = "flipped-90")]
+    Flipped90,
+    #[serde(rename = "flipped-180")]
+    Flipped180,
+    #[serde(rename = "flipped-270")]
+    Flipped270,
+}
+
+impl Orientation {
+    const ALL: [Orientation; 8] = [
+        Orientation::Normal,
+        Orientation::Rotate90,
+        Orientation::Rotate180,
+        Orientation::Rotate270,
+        Orientation::Flipped,
+        Orientation::Flipped90,
+        Orientation::Flipped180,
+        Orientation::Flipped270,
+    ];
+
+    /// From a `wl_output.transform` value, as used by Wayland and Mutter.
+    pub fn from_transform(transform: u32) -> Option<Self> {
+        Self::ALL.get(transform as usize).copied()
+    }
+
+    pub fn transform(&self) -> u32 {
+        *self as u32
+    }
+
+    /// Counter-clockwise rotation in degrees, ignoring any flip.
+    pub fn degrees(&self) -> u32 {
+        (self.transform() % 4) * 90
+    }
+
+    pub fn flipped(&self) -> bool {
+        self.transform() >= 4
+    }
+}
+
+/// How a mode that doesn't match the panel's native timing is presented.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scaling {
+    #[default]
+    Default,
+    Stretched,
+    Centered,
+}
+
+impl DisplayMode {
+    pub fn new(width: u32, height: u32, refresh_rate: RefreshRate) -> Self {
+        Self {
+            width,
+            height,
+            refresh_rate,
+            bit_depth: None,
+            interlaced: false,
+            scaling: Scaling::Default,
+            preferred: false,
+        }
+    }
+
+    pub fn resolution(&self) -> Resolution {
+        Resolution {
+            width: self.width,
+            height: self.height,
+            interlaced: self.interlaced,
+        }
+    }
+
+    /// True when both modes drive the same timing: resolution, refresh rate
+    /// and scan type. Descriptive flags such as `preferred` are ignored.
+    pub fn same_timing(&self, other: &DisplayMode) -> bool {
+        self.width == other.width
+            && self.height == other.height
+            && self.refresh_rate == other.refresh_rate
+            && self.interlaced == other.interlaced
+    }
+
+    /// Short labels for the descriptive attributes, e.g. `["32-bit", "preferred"]`.
+    pub fn details(&self) -> Vec<String> {
+        let mut details = Vec::new();
+        if let Some(bit_depth) = self.bit_depth {
+            details.push(format!("{}-bit", bit_depth));
+        }
+        if self.interlaced {
+            details.push("interlaced".to_string());
+        }
+        if self.scaling != Scaling::Default {
+            details.push(self.scaling.to_string());
+        }
+        if self.preferred {
+            details.push("preferred".to_string());
+        }
+        details
+    }
+}
+
+impl fmt::Display for DisplayMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}x{}{}@{}",
+            self.width,
+            self.height,
+            if self.interlaced { "i" } else { "" },
+            self.refresh_rate
+        )
+    }
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}x{}{}",
+            self.width,
+            self.height,
+            if self.interlaced { "i" } else { "" }
+        )
+    }
+}
+
+impl fmt::Display for MonitorIdentity {
+    /// `DEL-41A8-5QF8K93`, with whitespace inside a part replaced by `_`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let part = |text: &str| text.split_whitespace().collect::<Vec<_>>().join("_");
+        write!(f, "{}-{}", part(&self.manufacturer), part(&self.product))?;
+        if let Some(serial) = &self.serial {
+            write!(f, "-{}", part(serial))?;
+        }
+        Ok(())
+    }
+}
+
+/// E.g. `48-165Hz (FreeSync)`, or `supported (driver)` without a range.
+impl fmt::Display for VrrCapability {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.range {
+            Some((min, max)) => write!(f, "{}-{}Hz ({})", min, max, self.source),
+            None => write!(f, "supported ({})", self.source),
+        }
+    }
+}
+
+impl fmt::Display for VrrSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            VrrSource::FreeSync => "FreeSync",
+            VrrSource::HdmiVrr => "HDMI VRR",
+            VrrSource::AdaptiveSync => "Adaptive-Sync",
+            VrrSource::Driver => "driver",
+        })
+    }
+}
+
+impl fmt::Display for Placement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:+}{:+}", self.x, self.y)?;
+        if self.orientation != Orientation::Normal {
+            write!(f, " rotated {}", self.orientation)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Orientation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Orientation::Normal => "normal",
+            Orientation::Rotate90 => "90",
+            Orientation::Rotate180 => "180",
+            Orientation::Rotate270 => "270",
+            Orientation::Flipped => "flipped",
+            Orientation::Flipped90 => "flipped-90",
+            Orientation::Flipped180 => "flipped-180",
+            Orientation::Flipped270 => "flipped-270",
+        })
+    }
+}
+
+impl fmt::Display for Scaling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Scaling::Default => "default",
+            Scaling::Stretched => "stretched",
+            Scaling::Centered => "centered",
+        })
+    }
+}
+
+impl FromStr for Resolution {
+    type Err = anyhow::Error;
+
+    /// Parses the `WIDTHxHEIGHT` form, e.g. `1920x1080`, with an optional
+    /// `i` suffix for interlaced modes.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid resolution '{}', expected WIDTHxHEIGHT", s);
+        let (width, height) = s.trim().split_once(['x', 'X']).ok_or_else(invalid)?;
+        let height = height.trim();
+
+        Ok(Resolution {
+            width: width.trim().parse().map_err(|_| invalid())?,
+            height: height
+                .trim_end_matches('i')
+                .parse()
+                .map_err(|_| invalid())?,
+            interlaced: height.ends_with('i'),
+        })
+    }
+}
+
+/// The exact rate as in config files plus a rounded `refresh_hz` for
+/// scripts that only need a number.
+impl Serialize for DisplayMode {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut mode = serializer.serialize_struct("DisplayMode", 8)?;
+        mode.serialize_field("width", &self.width)?;
+        mode.serialize_field("height", &self.height)?;
+        mode.serialize_field("refresh_rate", &self.refresh_rate)?;
+        let refresh_hz = self.refresh_rate.millihertz() as f64 / 1000.0;
+        mode.serialize_field("refresh_hz", &refresh_hz)?;
+        mode.serialize_field("interlaced", &self.interlaced)?;
+        mode.serialize_field("bit_depth", &self.bit_depth)?;
+        mode.serialize_field("scaling", &self.scaling)?;
+        mode.serialize_field("preferred", &self.preferred)?;
+        mode.end()
+    }
+}
+
+impl Serialize for Resolution {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Resolution {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl FromStr for DisplayMode {
+    type Err = anyhow::Error;
+
+    /// Parses the `WIDTHxHEIGHT@RATE` form, e.g. `1920x1080@144` or
+    /// `1920x1080@59.94`, with an optional `i` after the height for
+    /// interlaced modes.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid mode '{}', expected WIDTHxHEIGHT@RATE", s);
+        let (resolution, rate) = s.trim().split_once('@').ok_or_else(invalid)?;
+        let resolution: Resolution = resolution.parse().map_err(|_| invalid())?;
+
+        Ok(DisplayMode {
+            interlaced: resolution.interlaced,
+            ..DisplayMode::new(
+                resolution.width,
+                resolution.height,
+                rate.parse().map_err(|_| invalid())?,
+            )
+        })
+    }
+}
+
+/// Platform-neutral access to the display hardware.
+///
+/// `Monitor` and the CLI/GUI front ends only talk to this trait, so each
+/// platform API lives in its own implementation.
+pub trait DisplayBackend {
+    /// Short identifier used in messages, e.g. "gdi".
+    fn name(&self) -> &'static str;
+
+    /// Lists the outputs that are currently attached to the desktop.
+    fn enumerate_outputs(&self) -> Result<Vec<Output>>;
+
+    /// Returns the mode the output is currently running.
+    fn current_mode(&self, device_name: &str) -> Result<DisplayMode>;
+
+    /// Returns every mode the output reports, in backend order.
+    fn query_modes(&self, device_name: &str) -> Result<Vec<DisplayMode>>;
+
+    /// Asks the platform whether `mode` would be accepted, without changing
+    /// anything.
+    fn test_mode(&self, device_name: &str, mode: &DisplayMode) -> Result<()>;
+
+    /// Whether `test_mode` gets its answer from the driver. Platforms without
+    /// a check-only request can only approximate it, and dry runs say so.
+    fn tests_with_driver(&self) -> bool {
+        true
+    }
+
+    /// Switches the output to `mode` and persists it where the platform allows.
+    fn apply_mode(&self, device_name: &str, mode: &DisplayMode) -> Result<()>;
+
+    /// Asks the platform whether all `changes` together would be accepted,
+    /// without changing anything. By default each mode is tested on its own.
+    fn test_modes(&self, changes: &[(&str, DisplayMode)]) -> Result<()> {
+        for (device_name, mode) in changes {
+            self.test_mode(device_name, mode)
+                .map_err(|e| e.context(format!("{} would reject {}", device_name, mode)))?;
+        }
+        Ok(())
+    }
+
+    /// Switches several outputs as one change: either all of them take their
+    /// new mode or, as far as the platform can restore them, none does.
+    ///
+    /// Backends that can commit a whole layout at once override this. The
+    /// default switches the outputs one at a time and, when one fails, puts
+    /// the ones already switched back to their previous modes.
+    fn apply_modes(&self, changes: &[(&str, DisplayMode)]) -> Result<()> {
+        apply_one_by_one(self, changes)
+    }
+
+    /// Returns the raw EDID of the monitor on the output, or `None` when the
+    /// platform doesn't expose it.
+    fn edid(&self, _device_name: &str) -> Result<Option<Vec<u8>>> {
+        Ok(None)
+    }
+
+    /// Returns what the monitor reports about itself, by default from its
+    /// EDID.
+    fn identity(&self, device_name: &str) -> Result<Option<MonitorIdentity>> {
+        Ok(self
+            .edid(device_name)?
+            .and_then(|edid| Edid::parse(&edid).ok())
+            .map(|edid| edid.identity()))
+    }
+
+    /// Returns the monitor's variable refresh rate support, or `None` when it
+    /// has none. By default this comes from the EDID alone.
+    fn vrr(&self, device_name: &str) -> Result<Option<VrrCapability>> {
+        let edid = self
+            .edid(device_name)?
+            .and_then(|edid| Edid::parse(&edid).ok());
+        Ok(vrr_capability(edid.as_ref(), None))
+    }
+
+    /// Returns whether variable refresh rate is currently on, or `None` when
+    /// the platform doesn't say.
+    fn vrr_enabled(&self, _device_name: &str) -> Result<Option<bool>> {
+        Ok(None)
+    }
+
+    /// Turns variable refresh rate on or off, keeping the mode.
+    fn set_vrr(&self, _device_name: &str, _enabled: bool) -> Result<()> {
+        Err(HertzRateError::BackendUnsupported(format!(
+            "Toggling VRR is not supported by the {} backend",
+            self.name()
+        ))
+        .into())
+    }
+
+    /// Returns the output's position and orientation, or `None` when the
+    /// platform doesn't expose them.
+    fn placement(&self, _device_name: &str) -> Result<Option<Placement>> {
+        Ok(None)
+    }
+
+    /// Moves and rotates the output, keeping its mode.
+    fn apply_placement(&self, _device_name: &str, _placement: &Placement) -> Result<()> {
+        Err(HertzRateError::BackendUnsupported(format!(
+            "The {} backend cannot change monitor positions",
+            self.name()
+        ))
+        .into())
+    }
+}
+
+/// Switches the outputs in `changes` one at a time, restoring the ones
+/// already switched, newest first, when one fails.
+fn apply_one_by_one<B: DisplayBackend + ?Sized>(
+    backend: &B,
+    changes: &[(&str, DisplayMode)],
+) -> Result<()> {
+    let mut applied: Vec<(&str, DisplayMode)> = Vec::new();
+    for &(device_name, mode) in changes {
+        let result = backend
+            .current_mode(device_name)
+            .and_then(|previous| backend.apply_mode(device_name, &mode).map(|()| previous));
+        let Err(e) = result.map(|previous| applied.push((device_name, previous))) else {
+            continue;
+        };
+
+        let failed = format!("Failed to switch {} to {}", device_name, mode);
+        let (restored, stuck): (Vec<_>, Vec<_>) = applied
+            .iter()
+            .rev()
+            .map(|(device_name, previous)| (device_name, backend.apply_mode(device_name, previous)))
+            .partition(|(_, result)| result.is_ok());
+        let restored: Vec<&str> = restored.into_iter().map(|(name, _)| *name).collect();
+        let stuck: Vec<String> = stuck
+            .into_iter()
+            .filter_map(|(name, result)| result.err().map(|e| format!("{} ({:#})", name, e)))
+            .collect();
+        return Err(e.context(match (restored.is_empty(), stuck.is_empty()) {
+            (true, true) => failed,
+            (false, true) => format!(
+                "{}; {} went back to the previous mode",
+                failed,
+                restored.join(", ")
+            ),
+            _ => format!(
+                "{} and rolling back failed, these outputs keep their new mode: {}",
+                failed,
+                stuck.join(", ")
+            ),
+        }));
+    }
+    Ok(())
+}
+
+/// Backends selectable with the global `--backend` option.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum BackendKind {
+    /// The native backend for this platform
+    #[default]
+    Auto,
+    /// Windows GDI
+    Gdi,
+    /// Linux DRM/KMS; changes modes with atomic commits as DRM master
+    Drm,
+    /// X11 RandR
+    X11,
+    /// Wayland wlr-output-management (sway, river, Hyprland, ...)
+    Wayland,
+    /// GNOME Mutter DisplayConfig over D-Bus
+    Mutter,
+    /// In-memory simulation driven by a scenario file
+    Sim,
+}
+
+/// Everything needed to construct a backend.
+#[derive(Debug, Clone, Default)]
+pub struct BackendOptions {
+    pub kind: BackendKind,
+    pub scenario: Option<PathBuf>,
+    pub sysfs_root: Option<PathBuf>,
+    /// Apply changes without persisting them across restarts
+    pub temporary: bool,
+}
+
+/// Combines what the EDID advertises with the driver's verdict, when the
+/// platform reports one. The driver knows whether the GPU and link can do VRR
+/// but not the range, which then comes from the EDID range limits.
+fn vrr_capability(edid: Option<&Edid>, driver_capable: Option<bool>) -> Option<VrrCapability> {
+    let advertised = edid.and_then(Edid::vrr);
+    match driver_capable {
+        Some(false) => None,
+        Some(true) => advertised.or(Some(VrrCapability {
+            range: edid
+                .and_then(Edid::range_limits)
+                .map(|limits| (limits.min_vertical_hz as u32, limits.max_vertical_hz as u32)),
+            source: VrrSource::Driver,
+        })),
+        None => advertised,
+    }
+}
+
+pub fn create_backend(options: &BackendOptions) -> Result<Box<dyn DisplayBackend>> {
+    match options.kind {
+        BackendKind::Auto => default_backend(options),
+        BackendKind::Gdi => gdi_backend(options),
+        BackendKind::Drm => Ok(Box::new(drm_backend(options))),
+        BackendKind::X11 => x11_backend(),
+        BackendKind::Wayland => wayland_backend(),
+        BackendKind::Mutter => mutter_backend(options),
+        BackendKind::Sim => {
+            let scenario = options
+                .scenario
+                .as_ref()
+                .ok_or_else(|| anyhow!("The sim backend requires --scenario <FILE>"))?;
+            Ok(Box::new(SimBackend::from_file(scenario)?))
+        }
+    }
+}
+
+fn drm_backend(options: &BackendOptions) -> DrmBackend {
+    match &options.sysfs_root {
+        Some(root) => DrmBackend::new(root),
+        None => DrmBackend::default(),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn mutter_backend(options: &BackendOptions) -> Result<Box<dyn DisplayBackend>> {
+    Ok(Box::new(MutterBackend::connect(!options.temporary)?))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn mutter_backend(_options: &BackendOptions) -> Result<Box<dyn DisplayBackend>> {
+    Err(anyhow!(
+        "The mutter backend is not available on this platform"
+    ))
+}
+
+#[cfg(target_os = "linux")]
+fn wayland_backend() -> Result<Box<dyn DisplayBackend>> {
+    Ok(Box::new(WaylandBackend::connect()?))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn wayland_backend() -> Result<Box<dyn DisplayBackend>> {
+    Err(anyhow!(
+        "The wayland backend is not available on this platform"
+    ))
+}
+
+#[cfg(target_os = "linux")]
+fn x11_backend() -> Result<Box<dyn DisplayBackend>> {
+    Ok(Box::new(X11Backend::connect()?))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn x11_backend() -> Result<Box<dyn DisplayBackend>> {
+    Err(anyhow!("The x11 backend is not available on this platform"))
+}
+
+#[cfg(windows)]
+fn gdi_backend(options: &BackendOptions) -> Result<Box<dyn DisplayBackend>> {
+    Ok(Box::new(GdiBackend::new(!options.temporary)))
+}
+
+#[cfg(not(windows))]
+fn gdi_backend(_options: &BackendOptions) -> Result<Box<dyn DisplayBackend>> {
+    Err(anyhow!("The gdi backend is only available on Windows"))
+}
+
+/// Returns the native backend for the platform this binary was built for.
+#[cfg(windows)]
+pub fn default_backend(options: &BackendOptions) -> Result<Box<dyn DisplayBackend>> {
+    gdi_backend(options)
+}
+
+/// Returns the native backend for the platform this binary was built for.
+#[cfg(target_os = "linux")]
+pub fn default_backend(options: &BackendOptions) -> Result<Box<dyn DisplayBackend>> {
+    // Prefer a backend that can change modes, fall back to read-only sysfs
+    if std::env::var("XDG_CURRENT_DESKTOP").is_ok_and(|desktop| desktop.contains("GNOME")) {
+        if let Ok(backend) = MutterBackend::connect(!options.temporary) {
+            return Ok(Box::new(backend));
+        }
+    }
+    if std::env::var_os("WAYLAND_DISPLAY").is_some() {
+        if let Ok(backend) = WaylandBackend::connect() {
+            return Ok(Box::new(backend));
+        }
+    }
+    if std::env::var_os("DISPLAY").is_some() {
+        if let Ok(backend) = X11Backend::connect() {
+            return Ok(Box::new(backend));
+        }
+    }
+    Ok(Box::new(drm_backend(options)))
+}
+
+/// Returns the native backend for the platform this binary was built for.
+#[cfg(not(any(windows, target_os = "linux")))]
+pub fn default_backend(_options: &BackendOptions) -> Result<Box<dyn DisplayBackend>> {
+    Err(anyhow!("No display backend is available for this platform"))
+}
@@ -0,0 +1,468 @@
+use super::drm::sysfs_edid;
+use super::{
+    kms, vrr_capability, DisplayBackend, DisplayMode, MonitorIdentity, Orientation, Output,
+    Placement, VrrCapability,
+};
+use crate::edid::Edid;
+use crate::error::HertzRateError;
+use crate::refresh_rate::RefreshRate;
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+use zbus::blocking::Connection;
+use zbus::zvariant::{OwnedValue, Value};
+
+const DESTINATION: &str = "org.gnome.Mutter.DisplayConfig";
+const PATH: &str = "/org/gnome/Mutter/DisplayConfig";
+const INTERFACE: &str = "org.gnome.Mutter.DisplayConfig";
+
+// ApplyMonitorsConfig methods
+const METHOD_VERIFY: u32 = 0;
+const METHOD_TEMPORARY: u32 = 1;
+const METHOD_PERSISTENT: u32 = 2;
+
+// The `layout-mode` of GetCurrentState: whether logical monitors are sized in
+// scaled or in physical pixels
+const LAYOUT_MODE_LOGICAL: u32 = 1;
+const LAYOUT_MODE_PHYSICAL: u32 = 2;
+
+type Properties = HashMap<String, OwnedValue>;
+/// (connector, vendor, product, serial)
+type MonitorSpec = (String, String, String, String);
+/// (id, width, height, refresh rate, preferred scale, supported scales, properties)
+type MonitorMode = (String, i32, i32, f64, f64, Vec<f64>, Properties);
+type PhysicalMonitor = (MonitorSpec, Vec<MonitorMode>, Properties);
+/// (x, y, scale, transform, primary, monitors, properties)
+type LogicalMonitor = (i32, i32, f64, u32, bool, Vec<MonitorSpec>, Properties);
+type CurrentState = (u32, Vec<PhysicalMonitor>, Vec<LogicalMonitor>, Properties);
+/// (connector, mode id, properties)
+type MonitorAssignment<'a> = (String, String, HashMap<String, Value<'a>>);
+type LogicalMonitorConfig<'a> = (i32, i32, f64, u32, bool, Vec<MonitorAssignment<'a>>);
+
+/// A logical monitor of the new configuration, with the size it takes up on
+/// the desktop before and after the change.
+struct LogicalLayout<'a> {
+    x: i32,
+    y: i32,
+    size: (i32, i32),
+    new_size: (i32, i32),
+    scale: f64,
+    transform: u32,
+    primary: bool,
+    assignments: Vec<MonitorAssignment<'a>>,
+}
+
+/// What a configuration changes on the target monitor.
+#[derive(Debug, Clone, Copy)]
+enum MonitorChange<'a> {
+    Mode(&'a DisplayMode),
+    Placement(&'a Placement),
+}
+
+/// GNOME backend using Mutter's `org.gnome.Mutter.DisplayConfig` D-Bus API,
+/// which works on both Wayland and X11 sessions.
+///
+/// Changes are applied as persistent configurations by default, the same as
+/// `CDS_UPDATEREGISTRY` on Windows; temporary ones are not written to
+/// `monitors.xml`.
+pub struct MutterBackend {
+    conn: Connection,
+    persistent: bool,
+}
+
+impl MutterBackend {
+    /// Connects to Mutter on the session bus.
+    pub fn connect(persistent: bool) -> Result<Self> {
+        let conn = Connection::session().context("Failed to connect to the session bus")?;
+        let backend = Self { conn, persistent };
+        backend
+            .current_state()
+            .context("Mutter DisplayConfig is not available")?;
+        Ok(backend)
+    }
+
+    fn current_state(&self) -> Result<CurrentState> {
+        let reply = self.conn.call_method(
+            Some(DESTINATION),
+            PATH,
+            Some(INTERFACE),
+            "GetCurrentState",
+            &(),
+        )?;
+        Ok(reply.body()?)
+    }
+
+    fn monitor<'a>(state: &'a CurrentState, device_name: &str) -> Result<&'a PhysicalMonitor> {
+        state
+            .1
+            .iter()
+            .find(|(spec, _, _)| spec.0 == device_name)
+            .ok_or_else(|| anyhow!("Monitor {} not found", device_name))
+    }
+
+    /// Sends the current layout with `change` made to `device_name`.
+    fn apply_config(&self, device_name: &str, change: MonitorChange, method: u32) -> Result<()> {
+        self.apply_configs(&[(device_name, change)], method)
+    }
+
+    /// Sends the current layout with all of `changes` made, as one
+    /// configuration that Mutter takes or rejects as a whole.
+    fn apply_configs(&self, changes: &[(&str, MonitorChange)], method: u32) -> Result<()> {
+        let state = self.current_state()?;
+        let mut targets = Vec::new();
+        for &(device_name, change) in changes {
+            let (_, modes, _) = Self::monitor(&state, device_name)?;
+            if let MonitorChange::Mode(mode) = change {
+                let mode_id = modes
+                    .iter()
+                    .find(|candidate| to_display_mode(candidate).same_timing(mode))
+                    .map(|candidate| candidate.0.clone())
+                    .ok_or_else(|| {
+                        HertzRateError::BadMode(format!(
+                            "Monitor {} has no mode {}",
+                            device_name, mode
+                        ))
+                    })?;
+                targets.push((device_name, mode_id));
+            }
+        }
+
+        // The new configuration is the current layout with the changes made
+        let layout_mode = state
+            .3
+            .get("layout-mode")
+            .and_then(|value| value.downcast_ref::<u32>())
+            .copied()
+            .unwrap_or(LAYOUT_MODE_LOGICAL);
+        let mut layouts: Vec<LogicalLayout> = Vec::new();
+        let mut placements = Vec::new();
+        for (x, y, scale, transform, primary, specs, _) in &state.2 {
+            let mut layout = LogicalLayout {
+                x: *x,
+                y: *y,
+                size: (0, 0),
+                new_size: (0, 0),
+                scale: *scale,
+                transform: *transform,
+                primary: *primary,
+                assignments: Vec::new(),
+            };
+            // Mirrored monitors share a logical monitor and a size; the
+            // first one's mode gives it
+            for (index, spec) in specs.iter().enumerate() {
+                let (_, modes, _) = Self::monitor(&state, &spec.0)?;
+                for &(device_name, change) in changes {
+                    if let MonitorChange::Placement(placement) = change {
+                        if device_name == spec.0 {
+                            placements.push((layouts.len(), placement));
+                            layout.transform = placement.orientation.transform();
+                        }
+                    }
+                }
+                let current = modes
+                    .iter()
+                    .find(|mode| is_current(mode))
+                    .ok_or_else(|| anyhow!("Monitor {} is not active", spec.0))?;
+                let target = targets
+                    .iter()
+                    .find(|(device_name, _)| *device_name == spec.0)
+                    .and_then(|(_, mode_id)| modes.iter().find(|mode| mode.0 == *mode_id))
+                    .unwrap_or(current);
+                if index == 0 {
+                    layout.size = logical_size(current, *scale, *transform, layout_mode);
+                    layout.scale = supported_scale(target, *scale);
+                    layout.new_size =
+                        logical_size(target, layout.scale, layout.transform, layout_mode);
+                }
+                layout
+                    .assignments
+                    .push((spec.0.clone(), target.0.clone(), HashMap::new()));
+            }
+            layouts.push(layout);
+        }
+
+        // Mutter refuses logical monitors that overlap or leave gaps, so a
+        // monitor that changes size moves the ones right of and below it
+        // along
+        for index in 0..layouts.len() {
+            let LogicalLayout {
+                x,
+                y,
+                size: (width, height),
+                new_size: (new_width, new_height),
+                ..
+            } = layouts[index];
+            for other in &mut layouts {
+                if other.x >= x + width {
+                    other.x += new_width - width;
+                }
+                if other.y >= y + height {
+                    other.y += new_height - height;
+                }
+            }
+            layouts[index].size = layouts[index].new_size;
+        }
+        for (index, placement) in placements {
+            (layouts[index].x, layouts[index].y) = (placement.x, placement.y);
+        }
+
+        // It also wants the layout to start at the origin
+        let min_x = layouts.iter().map(|layout| layout.x).min().unwrap_or(0);
+        let min_y = layouts.iter().map(|layout| layout.y).min().unwrap_or(0);
+        let logical_monitors: Vec<LogicalMonitorConfig> = layouts
+            .into_iter()
+            .map(|layout| {
+                (
+                    layout.x - min_x,
+                    layout.y - min_y,
+                    layout.scale,
+                    layout.transform,
+                    layout.primary,
+                    layout.assignments,
+                )
+            })
+            .collect();
+
+        let properties: HashMap<String, Value> = HashMap::new();
+
+        self.conn
+            .call_method(
+                Some(DESTINATION),
+                PATH,
+                Some(INTERFACE),
+                "ApplyMonitorsConfig",
+                &(state.0, method, logical_monitors, properties),
+            )
+            .map(|_| ())
+            .map_err(|e| {
+                let message = match changes {
+                    [(device_name, MonitorChange::Mode(mode))] => {
+                        format!("Mutter rejected mode {} for {}: {}", mode, device_name, e)
+                    }
+                    [(device_name, MonitorChange::Placement(placement))] => format!(
+                        "Mutter rejected placement {} for {}: {}",
+                        placement, device_name, e
+                    ),
+                    _ => {
+                        let changes: Vec<String> = changes
+                            .iter()
+                            .map(|(device_name, change)| match change {
+                                MonitorChange::Mode(mode) => {
+                                    format!("{} on {}", mode, device_name)
+                                }
+                                MonitorChange::Placement(placement) => {
+                                    format!("{} at {}", device_name, placement)
+                                }
+                            })
+                            .collect();
+                        format!(
+                            "Mutter rejected the configuration {}: {}",
+                            changes.join(", "),
+                            e
+                        )
+                    }
+                };
+                // Mutter refuses configuration from clients it doesn't trust
+                // with AccessDenied; everything else is a rejected layout
+                match &e {
+                    zbus::Error::MethodError(name, _, _)
+                        if name.as_str() == "org.freedesktop.DBus.Error.AccessDenied" =>
+                    {
+                        HertzRateError::PermissionDenied(message).into()
+                    }
+                    _ => HertzRateError::DriverRejected(message).into(),
+                }
+            })
+    }
+
+    fn persistence_method(&self) -> u32 {
+        if self.persistent {
+            METHOD_PERSISTENT
+        } else {
+            METHOD_TEMPORARY
+        }
+    }
+}
+
+impl DisplayBackend for MutterBackend {
+    fn name(&self) -> &'static str {
+        "mutter"
+    }
+
+    fn enumerate_outputs(&self) -> Result<Vec<Output>> {
+        let state = self.current_state()?;
+
+        Ok(state
+            .1
+            .iter()
+            // Monitors without a current mode are not part of the desktop
+            .filter(|(_, modes, _)| modes.iter().any(is_current))
+            .map(|(spec, _, properties)| Output {
+                device_name: spec.0.clone(),
+                description: string_property(properties, "display-name")
+                    .unwrap_or_else(|| format!("{} {}", spec.1, spec.2)),
+            })
+            .collect())
+    }
+
+    fn current_mode(&self, device_name: &str) -> Result<DisplayMode> {
+        let state = self.current_state()?;
+        let (_, modes, _) = Self::monitor(&state, device_name)?;
+
+        modes
+            .iter()
+            .find(|mode| is_current(mode))
+            .map(to_display_mode)
+            .ok_or_else(|| anyhow!("Monitor {} is not active", device_name))
+    }
+
+    fn query_modes(&self, device_name: &str) -> Result<Vec<DisplayMode>> {
+        let state = self.current_state()?;
+        let (_, modes, _) = Self::monitor(&state, device_name)?;
+
+        Ok(modes.iter().map(to_display_mode).collect())
+    }
+
+    fn test_mode(&self, device_name: &str, mode: &DisplayMode) -> Result<()> {
+        self.apply_config(device_name, MonitorChange::Mode(mode), METHOD_VERIFY)
+    }
+
+    fn apply_mode(&self, device_name: &str, mode: &DisplayMode) -> Result<()> {
+        self.apply_config(
+            device_name,
+            MonitorChange::Mode(mode),
+            self.persistence_method(),
+        )
+    }
+
+    fn test_modes(&self, changes: &[(&str, DisplayMode)]) -> Result<()> {
+        let changes: Vec<(&str, MonitorChange)> = changes
+            .iter()
+            .map(|(device_name, mode)| (*device_name, MonitorChange::Mode(mode)))
+            .collect();
+        self.apply_configs(&changes, METHOD_VERIFY)
+    }
+
+    /// One `ApplyMonitorsConfig` call for all monitors in `changes`.
+    fn apply_modes(&self, changes: &[(&str, DisplayMode)]) -> Result<()> {
+        let changes: Vec<(&str, MonitorChange)> = changes
+            .iter()
+            .map(|(device_name, mode)| (*device_name, MonitorChange::Mode(mode)))
+            .collect();
+        self.apply_configs(&changes, self.persistence_method())
+    }
+
+    fn edid(&self, device_name: &str) -> Result<Option<Vec<u8>>> {
+        Ok(sysfs_edid(device_name))
+    }
+
+    fn vrr(&self, device_name: &str) -> Result<Option<VrrCapability>> {
+        let edid = sysfs_edid(device_name).and_then(|edid| Edid::parse(&edid).ok());
+        Ok(vrr_capability(edid.as_ref(), kms::vrr_capable(device_name)))
+    }
+
+    /// Read from the CRTC Mutter drives; DisplayConfig has no way to change it.
+    fn vrr_enabled(&self, device_name: &str) -> Result<Option<bool>> {
+        Ok(kms::vrr_enabled(device_name))
+    }
+
+    /// The EDID when sysfs has it, else the vendor, product and serial of
+    /// Mutter's monitor spec.
+    fn identity(&self, device_name: &str) -> Result<Option<MonitorIdentity>> {
+        if let Some(edid) = sysfs_edid(device_name).and_then(|edid| Edid::parse(&edid).ok()) {
+            return Ok(Some(edid.identity()));
+        }
+
+        let state = self.current_state()?;
+        let ((_, vendor, product, serial), _, _) = Self::monitor(&state, device_name)?;
+        Ok(Some(MonitorIdentity {
+            manufacturer: vendor.clone(),
+            product: product.clone(),
+            serial: Some(serial.clone()).filter(|serial| !serial.is_empty()),
+        }))
+    }
+
+    fn placement(&self, device_name: &str) -> Result<Option<Placement>> {
+        let state = self.current_state()?;
+        Ok(state
+            .2
+            .iter()
+            .find(|(_, _, _, _, _, specs, _)| specs.iter().any(|spec| spec.0 == device_name))
+            .map(|(x, y, _, transform, _, _, _)| Placement {
+                x: *x,
+                y: *y,
+                orientation: Orientation::from_transform(*transform).unwrap_or_default(),
+            }))
+    }
+
+    fn apply_placement(&self, device_name: &str, placement: &Placement) -> Result<()> {
+        self.apply_config(
+            device_name,
+            MonitorChange::Placement(placement),
+            self.persistence_method(),
+        )
+    }
+}
+
+/// The size `mode` takes up on the desktop: rotated with `transform`, and in
+/// the logical layout mode divided by `scale`.
+fn logical_size(mode: &MonitorMode, scale: f64, transform: u32, layout_mode: u32) -> (i32, i32) {
+    let (mut width, mut height) = (mode.1, mode.2);
+    if Orientation::from_transform(transform)
+        .is_some_and(|orientation| orientation.degrees() % 180 == 90)
+    {
+        (width, height) = (height, width);
+    }
+    if layout_mode == LAYOUT_MODE_PHYSICAL {
+        return (width, height);
+    }
+    let scaled = |size: i32| (f64::from(size) / scale).round() as i32;
+    (scaled(width), scaled(height))
+}
+
+/// `scale` when `mode` supports it, else the closest scale it supports.
+fn supported_scale(mode: &MonitorMode, scale: f64) -> f64 {
+    let scales = &mode.5;
+    if scales.is_empty()
+        || scales
+            .iter()
+            .any(|supported| (supported - scale).abs() < 1e-4)
+    {
+        return scale;
+    }
+    scales
+        .iter()
+        .copied()
+        .min_by(|a, b| (a - scale).abs().total_cmp(&(b - scale).abs()))
+        .unwrap_or(scale)
+}
+
+fn is_current(mode: &MonitorMode) -> bool {
+    bool_property(&mode.6, "is-current")
+}
+
+fn to_display_mode(mode: &MonitorMode) -> DisplayMode {
+    DisplayMode {
+        interlaced: bool_property(&mode.6, "is-interlaced"),
+        preferred: bool_property(&mode.6, "is-preferred"),
+        ..DisplayMode::new(
+            mode.1.max(0) as u32,
+            mode.2.max(0) as u32,
+            RefreshRate::from_approximate(mode.3),
+        )
+    }
+}
+
+fn bool_property(properties: &Properties, key: &str) -> bool {
+    properties
+        .get(key)
+        .and_then(|value| value.downcast_ref::<bool>())
+        .copied()
+        .unwrap_or(false)
+}
+
+fn string_property(properties: &Properties, key: &str) -> Option<String> {
+    properties
+        .get(key)
+        .and_then(|value| value.downcast_ref::<str>())
+        .map(str::to_string)
+}
@@ -0,0 +1,418 @@
+use super::{
+    DisplayBackend, DisplayMode, MonitorIdentity, Orientation, Output, Placement, VrrCapability,
+    VrrSource,
+};
+use crate::error::HertzRateError;
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+
+/// Simulated backend that serves monitors from a TOML scenario file and keeps
+/// every applied change in memory for the lifetime of the process.
+///
+/// A scenario lists `[[monitor]]` tables:
+///
+/// ```toml
+/// [[monitor]]
+/// device_name = "SIM-1"
+/// description = "Simulated 144Hz Monitor"
+/// current = "2560x1440@60"
+/// modes = ["2560x1440@60", "2560x1440@144", "1920x1080@60"]
+/// preferred = "2560x1440@144"       # optional native mode
+/// reject_modes = ["2560x1440@144"]  # driver refuses these modes
+/// disconnect_on_apply = false       # monitor vanishes before the change lands
+/// ignore_apply = false              # driver reports success but changes nothing
+/// position = [0, 0]                 # optional desktop position
+/// orientation = "normal"            # optional: normal, 90, 180, 270, flipped, ...
+/// manufacturer = "SIM"              # optional EDID-style identity
+/// product = "0001"
+/// serial = "A1"
+/// vrr = [48, 144]                   # optional VRR range in Hz
+/// vrr_enabled = false               # whether VRR starts out on
+/// edid = "monitor.bin"              # optional raw EDID, relative to the scenario
+/// ```
+///
+/// Monitors without `position` or `orientation` don't report a placement,
+/// monitors without `manufacturer` report no identity, and monitors without
+/// `vrr` have a fixed refresh rate and refuse to toggle VRR.
+pub struct SimBackend {
+    monitors: RefCell<Vec<SimMonitor>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Scenario {
+    #[serde(default, rename = "monitor")]
+    monitors: Vec<ScenarioMonitor>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ScenarioMonitor {
+    device_name: String,
+    description: String,
+    current: String,
+    modes: Vec<String>,
+    #[serde(default)]
+    preferred: Option<String>,
+    #[serde(default)]
+    reject_modes: Vec<String>,
+    #[serde(default)]
+    disconnect_on_apply: bool,
+    #[serde(default)]
+    ignore_apply: bool,
+    #[serde(default)]
+    position: Option<[i32; 2]>,
+    #[serde(default)]
+    orientation: Option<Orientation>,
+    #[serde(default)]
+    manufacturer: Option<String>,
+    #[serde(default)]
+    product: Option<String>,
+    #[serde(default)]
+    serial: Option<String>,
+    #[serde(default)]
+    vrr: Option<[u32; 2]>,
+    #[serde(default)]
+    vrr_enabled: bool,
+    #[serde(default)]
+    edid: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+struct SimMonitor {
+    device_name: String,
+    description: String,
+    current: DisplayMode,
+    modes: Vec<DisplayMode>,
+    reject_modes: Vec<DisplayMode>,
+    disconnect_on_apply: bool,
+    ignore_apply: bool,
+    connected: bool,
+    placement: Option<Placement>,
+    identity: Option<MonitorIdentity>,
+    vrr: Option<VrrCapability>,
+    vrr_enabled: bool,
+    edid: Option<Vec<u8>>,
+}
+
+impl SimBackend {
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read scenario file {}", path.display()))?;
+        let base = path.parent().unwrap_or_else(|| Path::new(""));
+        Self::parse(&contents, base)
+            .with_context(|| format!("Invalid scenario file {}", path.display()))
+    }
+
+    /// Reads a scenario whose EDID files are relative to the working
+    /// directory.
+    pub fn from_toml(contents: &str) -> Result<Self> {
+        Self::parse(contents, Path::new(""))
+    }
+
+    fn parse(contents: &str, base: &Path) -> Result<Self> {
+        let scenario: Scenario = toml::from_str(contents)?;
+        let monitors = scenario
+            .monitors
+            .into_iter()
+            .map(|monitor| {
+                let edid = match &monitor.edid {
+                    Some(file) => {
+                        let path = base.join(file);
+                        Some(std::fs::read(&path).with_context(|| {
+                            format!("Failed to read EDID file {}", path.display())
+                        })?)
+                    }
+                    None => None,
+                };
+                Ok(SimMonitor {
+                    edid,
+                    ..SimMonitor::try_from(monitor)?
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            monitors: RefCell::new(monitors),
+        })
+    }
+
+    fn with_monitor<T>(
+        &self,
+        device_name: &str,
+        f: impl FnOnce(&mut SimMonitor) -> Result<T>,
+    ) -> Result<T> {
+        let mut monitors = self.monitors.borrow_mut();
+        let monitor = monitors
+            .iter_mut()
+            .find(|m| m.connected && m.device_name == device_name)
+            .ok_or_else(|| anyhow!("Device {} is not connected", device_name))?;
+        f(monitor)
+    }
+}
+
+impl SimMonitor {
+    /// The listed mode matching `mode`, unless the scenario rejects it.
+    fn validate(&self, mode: &DisplayMode) -> Result<DisplayMode> {
+        let listed = self
+            .modes
+            .iter()
+            .find(|candidate| candidate.same_timing(mode))
+            .copied()
+            .ok_or_else(|| {
+                HertzRateError::BadMode(format!("Device {} has no mode {}", self.device_name, mode))
+            })?;
+        if self.reject_modes.iter().any(|r| r.same_timing(mode)) {
+            return Err(
+                HertzRateError::DriverRejected(format!("Driver rejected mode {}", mode)).into(),
+            );
+        }
+        Ok(listed)
+    }
+}
+
+impl TryFrom<ScenarioMonitor> for SimMonitor {
+    type Error = anyhow::Error;
+
+    fn try_from(monitor: ScenarioMonitor) -> Result<Self> {
+        let parse_modes = |modes: &[String]| {
+            modes
+                .iter()
+                .map(|m| m.parse())
+                .collect::<Result<Vec<DisplayMode>>>()
+        };
+
+        let mut modes = parse_modes(&monitor.modes)?;
+        if let Some(preferred) = &monitor.preferred {
+            let preferred: DisplayMode = preferred.parse()?;
+            for mode in modes.iter_mut().filter(|m| m.same_timing(&preferred)) {
+                mode.preferred = true;
+            }
+        }
+
+        let current: DisplayMode = monitor.current.parse()?;
+        let current = modes
+            .iter()
+            .find(|m| m.same_timing(&current))
+            .copied()
+            .ok_or_else(|| {
+                anyhow!(
+                    "Current mode {} of {} is missing from its mode list",
+                    current,
+                    monitor.device_name
+                )
+            })?;
+
+        Ok(SimMonitor {
+            device_name: monitor.device_name,
+            description: monitor.description,
+            current,
+            modes,
+            reject_modes: parse_modes(&monitor.reject_modes)?,
+            disconnect_on_apply: monitor.disconnect_on_apply,
+            ignore_apply: monitor.ignore_apply,
+            connected: true,
+            placement: (monitor.position.is_some() || monitor.orientation.is_some()).then(|| {
+                let [x, y] = monitor.position.unwrap_or_default();
+                Placement {
+                    x,
+                    y,
+                    orientation: monitor.orientation.unwrap_or_default(),
+                }
+            }),
+            identity: monitor.manufacturer.map(|manufacturer| MonitorIdentity {
+                manufacturer,
+                product: monitor.product.unwrap_or_default(),
+                serial: monitor.serial,
+            }),
+            vrr: monitor.vrr.map(|[min, max]| VrrCapability {
+                range: Some((min, max)),
+                source: VrrSource::Driver,
+            }),
+            vrr_enabled: monitor.vrr.is_some() && monitor.vrr_enabled,
+            edid: None,
+        })
+    }
+}
+
+impl DisplayBackend for SimBackend {
+    fn name(&self) -> &'static str {
+        "sim"
+    }
+
+    fn enumerate_outputs(&self) -> Result<Vec<Output>> {
+        Ok(self
+            .monitors
+            .borrow()
+            .iter()
+            .filter(|m| m.connected)
+            .map(|m| Output {
+                device_name: m.device_name.clone(),
+                description: m.description.clone(),
+            })
+            .collect())
+    }
+
+    fn current_mode(&self, device_name: &str) -> Result<DisplayMode> {
+        self.with_monitor(device_name, |m| Ok(m.current))
+    }
+
+    fn query_modes(&self, device_name: &str) -> Result<Vec<DisplayMode>> {
+        self.with_monitor(device_name, |m| Ok(m.modes.clone()))
+    }
+
+    fn test_mode(&self, device_name: &str, mode: &DisplayMode) -> Result<()> {
+        self.with_monitor(device_name, |m| m.validate(mode).map(|_| ()))
+    }
+
+    fn apply_mode(&self, device_name: &str, mode: &DisplayMode) -> Result<()> {
+        self.with_monitor(device_name, |m| {
+            if m.disconnect_on_apply {
+                m.connected = false;
+                return Err(anyhow!("Device {} was disconnected", device_name));
+            }
+            let target = m.validate(mode)?;
+            if !m.ignore_apply {
+                m.current = target;
+            }
+            Ok(())
+        })
+    }
+
+    fn edid(&self, device_name: &str) -> Result<Option<Vec<u8>>> {
+        self.with_monitor(device_name, |m| Ok(m.edid.clone()))
+    }
+
+    fn identity(&self, device_name: &str) -> Result<Option<MonitorIdentity>> {
+        self.with_monitor(device_name, |m| Ok(m.identity.clone()))
+    }
+
+    fn vrr(&self, device_name: &str) -> Result<Option<VrrCapability>> {
+        self.with_monitor(device_name, |m| Ok(m.vrr))
+    }
+
+    fn vrr_enabled(&self, device_name: &str) -> Result<Option<bool>> {
+        self.with_monitor(device_name, |m| Ok(m.vrr.map(|_| m.vrr_enabled)))
+    }
+
+    fn set_vrr(&self, device_name: &str, enabled: bool) -> Result<()> {
+        self.with_monitor(device_name, |m| {
+            if m.vrr.is_none() {
+                return Err(HertzRateError::BackendUnsupported(format!(
+                    "Scenario gives {} no VRR range",
+                    device_name
+                ))
+                .into());
+            }
+            if !m.ignore_apply {
+                m.vrr_enabled = enabled;
+            }
+            Ok(())
+        })
+    }
+
+    fn placement(&self, device_name: &str) -> Result<Option<Placement>> {
+        self.with_monitor(device_name, |m| Ok(m.placement))
+    }
+
+    fn apply_placement(&self, device_name: &str, placement: &Placement) -> Result<()> {
+        self.with_monitor(device_name, |m| {
+            if m.placement.is_none() {
+                return Err(HertzRateError::BackendUnsupported(format!(
+                    "Scenario gives {} no position to change",
+                    device_name
+                ))
+                .into());
+            }
+            if !m.ignore_apply {
+                m.placement = Some(*placement);
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAIR: &str = r#"
+        [[monitor]]
+        device_name = "SIM-1"
+        description = "Main"
+        current = "2560x1440@60"
+        modes = ["2560x1440@60", "2560x1440@144"]
+
+        [[monitor]]
+        device_name = "SIM-2"
+        description = "Side"
+        current = "1920x1080@60"
+        modes = ["1920x1080@60", "1920x1080@75"]
+        reject_modes = ["1920x1080@75"]
+    "#;
+
+    fn mode(text: &str) -> DisplayMode {
+        text.parse().unwrap()
+    }
+
+    fn current(backend: &SimBackend, device_name: &str) -> String {
+        backend.current_mode(device_name).unwrap().to_string()
+    }
+
+    #[test]
+    fn batch_changes_every_monitor() {
+        let backend = SimBackend::from_toml(PAIR).unwrap();
+        backend
+            .apply_modes(&[
+                ("SIM-1", mode("2560x1440@144")),
+                ("SIM-2", mode("1920x1080@60")),
+            ])
+            .unwrap();
+        assert_eq!(current(&backend, "SIM-1"), "2560x1440@144");
+        assert_eq!(current(&backend, "SIM-2"), "1920x1080@60");
+    }
+
+    #[test]
+    fn rejected_second_monitor_rolls_back_the_first() {
+        let backend = SimBackend::from_toml(PAIR).unwrap();
+        backend.test_mode("SIM-1", &mode("2560x1440@144")).unwrap();
+        let error = backend
+            .apply_modes(&[
+                ("SIM-1", mode("2560x1440@144")),
+                ("SIM-2", mode("1920x1080@75")),
+            ])
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Failed to switch SIM-2 to 1920x1080@75; SIM-1 went back to the previous mode"
+        );
+        assert_eq!(current(&backend, "SIM-1"), "2560x1440@60");
+        assert_eq!(current(&backend, "SIM-2"), "1920x1080@60");
+
+        let error = backend
+            .test_modes(&[
+                ("SIM-1", mode("2560x1440@144")),
+                ("SIM-2", mode("1920x1080@75")),
+            ])
+            .unwrap_err();
+        assert_eq!(error.to_string(), "SIM-2 would reject 1920x1080@75");
+    }
+
+    #[test]
+    fn disconnected_monitor_rolls_back_the_others() {
+        let backend = SimBackend::from_toml(&PAIR.replace(
+            "reject_modes = [\"1920x1080@75\"]",
+            "disconnect_on_apply = true",
+        ))
+        .unwrap();
+        let error = backend
+            .apply_modes(&[
+                ("SIM-1", mode("2560x1440@144")),
+                ("SIM-2", mode("1920x1080@75")),
+            ])
+            .unwrap_err();
+        assert!(error.to_string().starts_with("Failed to switch SIM-2"));
+        assert_eq!(current(&backend, "SIM-1"), "2560x1440@60");
+        assert!(backend.current_mode("SIM-2").is_err());
+    }
+}
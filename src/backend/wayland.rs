@@ -0,0 +1,615 @@
+use super::drm::sysfs_edid;
+use super::{
+    kms, vrr_capability, DisplayBackend, DisplayMode, MonitorIdentity, Orientation, Output,
+    Placement, VrrCapability,
+};
+use crate::edid::Edid;
+use crate::error::HertzRateError;
+use crate::refresh_rate::RefreshRate;
+use anyhow::{anyhow, Context, Result};
+use std::cell::RefCell;
+use wayland_client::globals::{registry_queue_init, GlobalListContents};
+use wayland_client::protocol::wl_output::Transform;
+use wayland_client::protocol::wl_registry::WlRegistry;
+use wayland_client::{
+    event_created_child, Connection, Dispatch, EventQueue, Proxy, QueueHandle, WEnum,
+};
+use wayland_protocols_wlr::output_management::v1::client::{
+    zwlr_output_configuration_head_v1::ZwlrOutputConfigurationHeadV1,
+    zwlr_output_configuration_v1::{self, ZwlrOutputConfigurationV1},
+    zwlr_output_head_v1::{self, AdaptiveSyncState, ZwlrOutputHeadV1},
+    zwlr_output_manager_v1::{self, ZwlrOutputManagerV1},
+    zwlr_output_mode_v1::{self, ZwlrOutputModeV1},
+};
+
+/// Wayland backend for wlroots-style compositors using the
+/// `zwlr_output_management_v1` protocol.
+///
+/// Heads map to monitors. A refresh-rate change is sent as a full output
+/// configuration that keeps every other head as it is; the compositor first
+/// tests it and only then applies it. Changes to several heads go into one
+/// configuration, so they succeed or fail together. Adaptive sync needs
+/// version 4 of the protocol.
+pub struct WaylandBackend {
+    inner: RefCell<Inner>,
+}
+
+struct Inner {
+    queue: EventQueue<State>,
+    state: State,
+    manager: ZwlrOutputManagerV1,
+}
+
+#[derive(Default)]
+struct State {
+    heads: Vec<Head>,
+    serial: Option<u32>,
+    outcome: Option<Outcome>,
+}
+
+struct Head {
+    proxy: ZwlrOutputHeadV1,
+    name: String,
+    description: String,
+    make: String,
+    model: String,
+    serial_number: String,
+    enabled: bool,
+    modes: Vec<Mode>,
+    current_mode: Option<ZwlrOutputModeV1>,
+    position: (i32, i32),
+    transform: Option<Transform>,
+    scale: f64,
+    /// Adaptive sync state; only sent from protocol version 4
+    adaptive_sync: Option<bool>,
+}
+
+struct Mode {
+    proxy: ZwlrOutputModeV1,
+    width: i32,
+    height: i32,
+    refresh_mhz: i32,
+    preferred: bool,
+}
+
+/// What a configuration changes on the target head.
+#[derive(Debug, Clone, Copy)]
+enum HeadChange<'a> {
+    Mode(&'a DisplayMode),
+    Placement(&'a Placement),
+    AdaptiveSync(bool),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Outcome {
+    Succeeded,
+    Failed,
+    Cancelled,
+}
+
+impl WaylandBackend {
+    /// Connects to the compositor named by `$WAYLAND_DISPLAY`.
+    pub fn connect() -> Result<Self> {
+        let conn =
+            Connection::connect_to_env().context("Failed to connect to Wayland compositor")?;
+        let (globals, queue) = registry_queue_init::<State>(&conn)?;
+        let manager: ZwlrOutputManagerV1 = globals
+            .bind(&queue.handle(), 1..=4, ())
+            .context("Compositor does not support zwlr_output_management_v1")?;
+
+        let mut inner = Inner {
+            queue,
+            state: State::default(),
+            manager,
+        };
+        inner.sync()?;
+
+        Ok(Self {
+            inner: RefCell::new(inner),
+        })
+    }
+}
+
+impl Inner {
+    /// Processes pending events so heads, modes and the serial are current.
+    fn sync(&mut self) -> Result<()> {
+        self.queue.roundtrip(&mut self.state)?;
+        if self.state.serial.is_none() {
+            self.queue.roundtrip(&mut self.state)?;
+        }
+        Ok(())
+    }
+
+    fn head(&self, device_name: &str) -> Result<&Head> {
+        self.state
+            .heads
+            .iter()
+            .find(|head| head.name == device_name)
+            .ok_or_else(|| anyhow!("Head {} not found", device_name))
+    }
+
+    /// Sends a configuration that applies `change` to `device_name` and keeps
+    /// all other heads unchanged, then waits for the compositor's verdict.
+    fn configure(&mut self, device_name: &str, change: HeadChange, test_only: bool) -> Result<()> {
+        self.configure_all(&[(device_name, change)], test_only)
+    }
+
+    /// Like `configure`, but for several heads in one configuration, so the
+    /// compositor takes or rejects the changes together.
+    fn configure_all(&mut self, changes: &[(&str, HeadChange)], test_only: bool) -> Result<()> {
+        let serial = self
+            .state
+            .serial
+            .ok_or_else(|| anyhow!("Compositor has not sent the output state"))?;
+        let mut targets = Vec::new();
+        for &(device_name, change) in changes {
+            match change {
+                HeadChange::Mode(mode) => targets.push((
+                    device_name,
+                    self.head(device_name)?
+                        .modes
+                        .iter()
+                        .find(|candidate| candidate.to_display_mode().same_timing(mode))
+                        .map(|candidate| candidate.proxy.clone())
+                        .ok_or_else(|| {
+                            HertzRateError::BadMode(format!(
+                                "Head {} has no mode {}",
+                                device_name, mode
+                            ))
+                        })?,
+                )),
+                HeadChange::AdaptiveSync(_) if self.manager.version() < 4 => {
+                    return Err(HertzRateError::BackendUnsupported(
+                        "Toggling VRR is not supported by this compositor: it needs version 4 \
+                         of zwlr_output_management_v1, which has the adaptive_sync head property"
+                            .to_string(),
+                    )
+                    .into());
+                }
+                HeadChange::Placement(_) | HeadChange::AdaptiveSync(_) => {}
+            }
+        }
+        let qh = self.queue.handle();
+        let config = self.manager.create_configuration(serial, &qh, ());
+        for head in &self.state.heads {
+            let head_changes: Vec<HeadChange> = changes
+                .iter()
+                .filter(|(device_name, _)| *device_name == head.name)
+                .map(|&(_, change)| change)
+                .collect();
+            if !head.enabled && head_changes.is_empty() {
+                config.disable_head(&head.proxy);
+                continue;
+            }
+
+            let config_head = config.enable_head(&head.proxy, &qh, ());
+            let target = targets
+                .iter()
+                .find(|(device_name, _)| *device_name == head.name)
+                .map(|(_, mode)| mode);
+            match (target, &head.current_mode) {
+                (Some(target), _) => config_head.set_mode(target),
+                (_, Some(current)) => config_head.set_mode(current),
+                _ => {}
+            }
+            let placement = head_changes.iter().find_map(|change| match *change {
+                HeadChange::Placement(placement) => Some(placement),
+                _ => None,
+            });
+            match placement {
+                Some(placement) => {
+                    config_head.set_position(placement.x, placement.y);
+                    if let Ok(transform) = Transform::try_from(placement.orientation.transform()) {
+                        config_head.set_transform(transform);
+                    }
+                }
+                None => {
+                    config_head.set_position(head.position.0, head.position.1);
+                    if let Some(transform) = head.transform {
+                        config_head.set_transform(transform);
+                    }
+                }
+            }
+            config_head.set_scale(head.scale);
+            let adaptive_sync = head_changes
+                .iter()
+                .find_map(|change| match *change {
+                    HeadChange::AdaptiveSync(enabled) => Some(enabled),
+                    _ => None,
+                })
+                .or(head.adaptive_sync);
+            if let Some(enabled) = adaptive_sync {
+                config_head.set_adaptive_sync(if enabled {
+                    AdaptiveSyncState::Enabled
+                } else {
+                    AdaptiveSyncState::Disabled
+                });
+            }
+        }
+
+        self.state.outcome = None;
+        if test_only {
+            config.test();
+        } else {
+            config.apply();
+        }
+        while self.state.outcome.is_none() {
+            self.queue.blocking_dispatch(&mut self.state)?;
+        }
+        config.destroy();
+
+        match self.state.outcome {
+            Some(Outcome::Succeeded) => Ok(()),
+            Some(Outcome::Cancelled) => Err(anyhow!(
+                "Output configuration was cancelled because the outputs changed; try again"
+            )),
+            _ => Err(HertzRateError::DriverRejected(match changes {
+                [(device_name, HeadChange::Mode(mode))] => {
+                    format!("Compositor rejected mode {} for {}", mode, device_name)
+                }
+                [(device_name, HeadChange::Placement(placement))] => format!(
+                    "Compositor rejected placement {} for {}",
+                    placement, device_name
+                ),
+                [(device_name, HeadChange::AdaptiveSync(enabled))] => format!(
+                    "Compositor refused to turn adaptive sync {} for {}",
+                    if *enabled { "on" } else { "off" },
+                    device_name
+                ),
+                _ => {
+                    let modes: Vec<String> = changes
+                        .iter()
+                        .map(|(device_name, change)| match change {
+                            HeadChange::Mode(mode) => format!("{} on {}", mode, device_name),
+                            _ => device_name.to_string(),
+                        })
+                        .collect();
+                    format!("Compositor rejected the configuration {}", modes.join(", "))
+                }
+            })
+            .into()),
+        }
+    }
+}
+
+impl Mode {
+    fn to_display_mode(&self) -> DisplayMode {
+        DisplayMode {
+            preferred: self.preferred,
+            ..DisplayMode::new(
+                self.width.max(0) as u32,
+                self.height.max(0) as u32,
+                RefreshRate::from_approximate(self.refresh_mhz.max(0) as f64 / 1000.0),
+            )
+        }
+    }
+}
+
+impl DisplayBackend for WaylandBackend {
+    fn name(&self) -> &'static str {
+        "wayland"
+    }
+
+    fn enumerate_outputs(&self) -> Result<Vec<Output>> {
+        let mut inner = self.inner.borrow_mut();
+        inner.sync()?;
+
+        Ok(inner
+            .state
+            .heads
+            .iter()
+            .filter(|head| head.enabled)
+            .map(|head| Output {
+                device_name: head.name.clone(),
+                description: match sysfs_edid(&head.name).and_then(|edid| Edid::parse(&edid).ok()) {
+                    Some(edid) => edid.description(),
+                    None if head.make.is_empty() && head.model.is_empty() => {
+                        head.description.clone()
+                    }
+                    None => format!("{} {}", head.make, head.model).trim().to_string(),
+                },
+            })
+            .collect())
+    }
+
+    fn current_mode(&self, device_name: &str) -> Result<DisplayMode> {
+        let mut inner = self.inner.borrow_mut();
+        inner.sync()?;
+
+        let head = inner.head(device_name)?;
+        let current = head
+            .current_mode
+            .as_ref()
+            .ok_or_else(|| anyhow!("Head {} is not enabled", device_name))?;
+        head.modes
+            .iter()
+            .find(|mode| mode.proxy == *current)
+            .map(Mode::to_display_mode)
+            .ok_or_else(|| anyhow!("Head {} uses an unknown mode", device_name))
+    }
+
+    fn query_modes(&self, device_name: &str) -> Result<Vec<DisplayMode>> {
+        let mut inner = self.inner.borrow_mut();
+        inner.sync()?;
+
+        Ok(inner
+            .head(device_name)?
+            .modes
+            .iter()
+            .map(Mode::to_display_mode)
+            .collect())
+    }
+
+    fn test_mode(&self, device_name: &str, mode: &DisplayMode) -> Result<()> {
+        let mut inner = self.inner.borrow_mut();
+        inner.sync()?;
+        inner.configure(device_name, HeadChange::Mode(mode), true)
+    }
+
+    fn apply_mode(&self, device_name: &str, mode: &DisplayMode) -> Result<()> {
+        let mut inner = self.inner.borrow_mut();
+        inner.sync()?;
+
+        // A configuration object is single-use, so test and apply send one each
+        inner.configure(device_name, HeadChange::Mode(mode), true)?;
+        inner.configure(device_name, HeadChange::Mode(mode), false)
+    }
+
+    /// One configuration covering every head in `changes`.
+    fn test_modes(&self, changes: &[(&str, DisplayMode)]) -> Result<()> {
+        let changes: Vec<(&str, HeadChange)> = changes
+            .iter()
+            .map(|(device_name, mode)| (*device_name, HeadChange::Mode(mode)))
+            .collect();
+        let mut inner = self.inner.borrow_mut();
+        inner.sync()?;
+        inner.configure_all(&changes, true)
+    }
+
+    /// One configuration covering every head in `changes`, which the
+    /// compositor applies as a whole or not at all.
+    fn apply_modes(&self, changes: &[(&str, DisplayMode)]) -> Result<()> {
+        let changes: Vec<(&str, HeadChange)> = changes
+            .iter()
+            .map(|(device_name, mode)| (*device_name, HeadChange::Mode(mode)))
+            .collect();
+        let mut inner = self.inner.borrow_mut();
+        inner.sync()?;
+        inner.configure_all(&changes, true)?;
+        inner.configure_all(&changes, false)
+    }
+
+    fn edid(&self, device_name: &str) -> Result<Option<Vec<u8>>> {
+        Ok(sysfs_edid(device_name))
+    }
+
+    fn vrr(&self, device_name: &str) -> Result<Option<VrrCapability>> {
+        let edid = sysfs_edid(device_name).and_then(|edid| Edid::parse(&edid).ok());
+        Ok(vrr_capability(edid.as_ref(), kms::vrr_capable(device_name)))
+    }
+
+    /// The EDID when sysfs has it, else the make, model and serial number
+    /// the compositor reports.
+    fn identity(&self, device_name: &str) -> Result<Option<MonitorIdentity>> {
+        if let Some(edid) = sysfs_edid(device_name).and_then(|edid| Edid::parse(&edid).ok()) {
+            return Ok(Some(edid.identity()));
+        }
+
+        let mut inner = self.inner.borrow_mut();
+        inner.sync()?;
+        let head = inner.head(device_name)?;
+        Ok(
+            (!head.make.is_empty() || !head.model.is_empty()).then(|| MonitorIdentity {
+                manufacturer: head.make.clone(),
+                product: head.model.clone(),
+                serial: Some(head.serial_number.clone()).filter(|serial| !serial.is_empty()),
+            }),
+        )
+    }
+
+    fn placement(&self, device_name: &str) -> Result<Option<Placement>> {
+        let mut inner = self.inner.borrow_mut();
+        inner.sync()?;
+
+        let head = inner.head(device_name)?;
+        Ok(Some(Placement {
+            x: head.position.0,
+            y: head.position.1,
+            orientation: head
+                .transform
+                .and_then(|transform| Orientation::from_transform(transform.into()))
+                .unwrap_or_default(),
+        }))
+    }
+
+    fn apply_placement(&self, device_name: &str, placement: &Placement) -> Result<()> {
+        let mut inner = self.inner.borrow_mut();
+        inner.sync()?;
+
+        inner.configure(device_name, HeadChange::Placement(placement), true)?;
+        inner.configure(device_name, HeadChange::Placement(placement), false)
+    }
+
+    /// The compositor's `adaptive_sync` head property, else the CRTC state.
+    fn vrr_enabled(&self, device_name: &str) -> Result<Option<bool>> {
+        let mut inner = self.inner.borrow_mut();
+        inner.sync()?;
+
+        let head = inner.head(device_name)?;
+        Ok(head.adaptive_sync.or_else(|| kms::vrr_enabled(device_name)))
+    }
+
+    fn set_vrr(&self, device_name: &str, enabled: bool) -> Result<()> {
+        let mut inner = self.inner.borrow_mut();
+        inner.sync()?;
+
+        inner.configure(device_name, HeadChange::AdaptiveSync(enabled), true)?;
+        inner.configure(device_name, HeadChange::AdaptiveSync(enabled), false)
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        _state: &mut Self,
+        _proxy: &WlRegistry,
+        _event: <WlRegistry as wayland_client::Proxy>::Event,
+        _data: &GlobalListContents,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrOutputManagerV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _proxy: &ZwlrOutputManagerV1,
+        event: zwlr_output_manager_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_output_manager_v1::Event::Head { head } => state.heads.push(Head {
+                proxy: head,
+                name: String::new(),
+                description: String::new(),
+                make: String::new(),
+                model: String::new(),
+                serial_number: String::new(),
+                enabled: false,
+                modes: Vec::new(),
+                current_mode: None,
+                position: (0, 0),
+                transform: None,
+                scale: 1.0,
+                adaptive_sync: None,
+            }),
+            zwlr_output_manager_v1::Event::Done { serial } => state.serial = Some(serial),
+            zwlr_output_manager_v1::Event::Finished => state.serial = None,
+            _ => {}
+        }
+    }
+
+    event_created_child!(State, ZwlrOutputManagerV1, [
+        zwlr_output_manager_v1::EVT_HEAD_OPCODE => (ZwlrOutputHeadV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrOutputHeadV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        proxy: &ZwlrOutputHeadV1,
+        event: zwlr_output_head_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        if let zwlr_output_head_v1::Event::Finished = event {
+            state.heads.retain(|head| head.proxy != *proxy);
+            return;
+        }
+        let Some(head) = state.heads.iter_mut().find(|head| head.proxy == *proxy) else {
+            return;
+        };
+
+        match event {
+            zwlr_output_head_v1::Event::Name { name } => head.name = name,
+            zwlr_output_head_v1::Event::Description { description } => {
+                head.description = description
+            }
+            zwlr_output_head_v1::Event::Make { make } => head.make = make,
+            zwlr_output_head_v1::Event::Model { model } => head.model = model,
+            zwlr_output_head_v1::Event::SerialNumber { serial_number } => {
+                head.serial_number = serial_number
+            }
+            zwlr_output_head_v1::Event::Mode { mode } => head.modes.push(Mode {
+                proxy: mode,
+                width: 0,
+                height: 0,
+                refresh_mhz: 0,
+                preferred: false,
+            }),
+            zwlr_output_head_v1::Event::Enabled { enabled } => {
+                head.enabled = enabled != 0;
+                if !head.enabled {
+                    head.current_mode = None;
+                }
+            }
+            zwlr_output_head_v1::Event::CurrentMode { mode } => head.current_mode = Some(mode),
+            zwlr_output_head_v1::Event::Position { x, y } => head.position = (x, y),
+            zwlr_output_head_v1::Event::Transform {
+                transform: WEnum::Value(transform),
+            } => head.transform = Some(transform),
+            zwlr_output_head_v1::Event::Scale { scale } => head.scale = scale,
+            zwlr_output_head_v1::Event::AdaptiveSync {
+                state: WEnum::Value(state),
+            } => head.adaptive_sync = Some(state == AdaptiveSyncState::Enabled),
+            _ => {}
+        }
+    }
+
+    event_created_child!(State, ZwlrOutputHeadV1, [
+        zwlr_output_head_v1::EVT_MODE_OPCODE => (ZwlrOutputModeV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrOutputModeV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        proxy: &ZwlrOutputModeV1,
+        event: zwlr_output_mode_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        for head in &mut state.heads {
+            if let zwlr_output_mode_v1::Event::Finished = event {
+                head.modes.retain(|mode| mode.proxy != *proxy);
+                continue;
+            }
+            let Some(mode) = head.modes.iter_mut().find(|mode| mode.proxy == *proxy) else {
+                continue;
+            };
+            match event {
+                zwlr_output_mode_v1::Event::Size { width, height } => {
+                    mode.width = width;
+                    mode.height = height;
+                }
+                zwlr_output_mode_v1::Event::Refresh { refresh } => mode.refresh_mhz = refresh,
+                zwlr_output_mode_v1::Event::Preferred => mode.preferred = true,
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Dispatch<ZwlrOutputConfigurationV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _proxy: &ZwlrOutputConfigurationV1,
+        event: zwlr_output_configuration_v1::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+        state.outcome = match event {
+            zwlr_output_configuration_v1::Event::Succeeded => Some(Outcome::Succeeded),
+            zwlr_output_configuration_v1::Event::Failed => Some(Outcome::Failed),
+            zwlr_output_configuration_v1::Event::Cancelled => Some(Outcome::Cancelled),
+            _ => state.outcome,
+        };
+    }
+}
+
+impl Dispatch<ZwlrOutputConfigurationHeadV1, ()> for State {
+    fn event(
+        _state: &mut Self,
+        _proxy: &ZwlrOutputConfigurationHeadV1,
+        _event: <ZwlrOutputConfigurationHeadV1 as wayland_client::Proxy>::Event,
+        _data: &(),
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+    ) {
+    }
+}
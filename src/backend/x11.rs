@@ -0,0 +1,539 @@
+use super::{
+    vrr_capability, DisplayBackend, DisplayMode, Orientation, Output, Placement, VrrCapability,
+};
+use crate::edid::Edid;
+use crate::error::HertzRateError;
+use crate::refresh_rate::RefreshRate;
+use anyhow::{anyhow, Context, Result};
+use x11rb::connection::Connection;
+use x11rb::protocol::randr::{
+    self, ConnectionExt as _, GetCrtcInfoReply, GetOutputInfoReply, GetScreenResourcesCurrentReply,
+    ModeFlag, ModeInfo, Rotation, SetConfig,
+};
+use x11rb::protocol::xproto::{AtomEnum, ConnectionExt as _, PropMode, Window};
+use x11rb::rust_connection::RustConnection;
+
+/// X11 backend that talks the RandR 1.3 protocol directly through x11rb.
+///
+/// Outputs map to monitors, the output's CRTC provides the current mode and
+/// mode changes reprogram that CRTC with its existing position, rotation and
+/// outputs, growing the X screen first when the new mode doesn't fit and
+/// shrinking it to the outputs afterwards. X11 keeps no configuration of its
+/// own, so changes last until the session ends.
+pub struct X11Backend {
+    conn: RustConnection,
+    root: Window,
+}
+
+impl X11Backend {
+    /// Connects to the display named by `$DISPLAY`.
+    pub fn connect() -> Result<Self> {
+        let (conn, screen_num) = x11rb::connect(None).context("Failed to connect to X server")?;
+        let root = conn.setup().roots[screen_num].root;
+
+        let version = conn
+            .randr_query_version(1, 3)?
+            .reply()
+            .context("X server does not support RandR")?;
+        if (version.major_version, version.minor_version) < (1, 3) {
+            return Err(anyhow!(
+                "RandR 1.3 is required, server has {}.{}",
+                version.major_version,
+                version.minor_version
+            ));
+        }
+
+        Ok(Self { conn, root })
+    }
+
+    fn resources(&self) -> Result<GetScreenResourcesCurrentReply> {
+        Ok(self
+            .conn
+            .randr_get_screen_resources_current(self.root)?
+            .reply()?)
+    }
+
+    fn find_output(
+        &self,
+        resources: &GetScreenResourcesCurrentReply,
+        device_name: &str,
+    ) -> Result<(randr::Output, GetOutputInfoReply)> {
+        for &output in &resources.outputs {
+            let info = self
+                .conn
+                .randr_get_output_info(output, resources.config_timestamp)?
+                .reply()?;
+            if String::from_utf8_lossy(&info.name) == device_name {
+                return Ok((output, info));
+            }
+        }
+        Err(anyhow!("Output {} not found", device_name))
+    }
+
+    /// The screen size that holds every active CRTC once `crtc` scans out
+    /// `mode` at its current position and rotation, or an error when the
+    /// server can't make the screen that large.
+    fn screen_size_for(
+        &self,
+        resources: &GetScreenResourcesCurrentReply,
+        crtc: randr::Crtc,
+        info: &GetCrtcInfoReply,
+        mode: &ModeInfo,
+    ) -> Result<(u16, u16)> {
+        let (mut width, mut height) = (0, 0);
+        for &other in &resources.crtcs {
+            let (x, y, w, h) = if other == crtc {
+                let (w, h) = rotated_size(mode, info.rotation);
+                (info.x, info.y, w, h)
+            } else {
+                let other = self
+                    .conn
+                    .randr_get_crtc_info(other, resources.config_timestamp)?
+                    .reply()?;
+                if other.mode == 0 {
+                    continue;
+                }
+                (other.x, other.y, other.width, other.height)
+            };
+            width = width.max(x.max(0) as u32 + u32::from(w));
+            height = height.max(y.max(0) as u32 + u32::from(h));
+        }
+
+        let range = self.conn.randr_get_screen_size_range(self.root)?.reply()?;
+        if width > u32::from(range.max_width) || height > u32::from(range.max_height) {
+            return Err(HertzRateError::DriverRejected(format!(
+                "The outputs would need a {}x{} screen, but the X server allows at most {}x{}",
+                width, height, range.max_width, range.max_height
+            ))
+            .into());
+        }
+        Ok((
+            (width as u16).max(range.min_width),
+            (height as u16).max(range.min_height),
+        ))
+    }
+
+    fn screen_size(&self) -> Result<(u16, u16)> {
+        let geometry = self.conn.get_geometry(self.root)?.reply()?;
+        Ok((geometry.width, geometry.height))
+    }
+
+    /// Resizes the X screen, keeping the DPI the server started with.
+    fn set_screen_size(&self, (width, height): (u16, u16)) -> Result<()> {
+        let screen = self
+            .conn
+            .setup()
+            .roots
+            .iter()
+            .find(|screen| screen.root == self.root);
+        let millimetres = |pixels: u16, initial: Option<(u16, u16)>| match initial {
+            Some((initial_pixels, initial_mm)) if initial_pixels > 0 && initial_mm > 0 => {
+                u32::from(pixels) * u32::from(initial_mm) / u32::from(initial_pixels)
+            }
+            // 96 DPI, like the X server's default
+            _ => (f64::from(pixels) * 25.4 / 96.0).round() as u32,
+        };
+        let mm_width = millimetres(
+            width,
+            screen.map(|screen| (screen.width_in_pixels, screen.width_in_millimeters)),
+        );
+        let mm_height = millimetres(
+            height,
+            screen.map(|screen| (screen.height_in_pixels, screen.height_in_millimeters)),
+        );
+        self.conn
+            .randr_set_screen_size(self.root, width, height, mm_width, mm_height)?
+            .check()
+            .with_context(|| {
+                format!(
+                    "X server refused to resize the screen to {}x{}",
+                    width, height
+                )
+            })
+    }
+
+    fn output_edid(&self, output: randr::Output) -> Result<Vec<u8>> {
+        self.output_property(output, b"EDID")
+    }
+
+    /// The raw data of an output property, empty when the driver doesn't
+    /// set it.
+    fn output_property(&self, output: randr::Output, name: &[u8]) -> Result<Vec<u8>> {
+        let atom = self.conn.intern_atom(true, name)?.reply()?.atom;
+        if atom == 0 {
+            return Ok(Vec::new());
+        }
+        let property = self
+            .conn
+            .randr_get_output_property(output, atom, AtomEnum::ANY, 0, 256, false, false)?
+            .reply()?;
+        Ok(property.data)
+    }
+}
+
+impl DisplayBackend for X11Backend {
+    fn name(&self) -> &'static str {
+        "x11"
+    }
+
+    fn enumerate_outputs(&self) -> Result<Vec<Output>> {
+        let resources = self.resources()?;
+        let mut outputs = Vec::new();
+
+        for &output in &resources.outputs {
+            let info = self
+                .conn
+                .randr_get_output_info(output, resources.config_timestamp)?
+                .reply()?;
+
+            // Skip outputs without a monitor or without an active CRTC
+            if info.connection != randr::Connection::CONNECTED || info.crtc == 0 {
+                continue;
+            }
+
+            let device_name = String::from_utf8_lossy(&info.name).to_string();
+            let description = self
+                .output_edid(output)
+                .ok()
+                .and_then(|edid| Edid::parse(&edid).ok())
+                .map(|edid| edid.description())
+                .unwrap_or_else(|| device_name.clone());
+
+            outputs.push(Output {
+                device_name,
+                description,
+            });
+        }
+
+        Ok(outputs)
+    }
+
+    fn current_mode(&self, device_name: &str) -> Result<DisplayMode> {
+        let resources = self.resources()?;
+        let (_, info) = self.find_output(&resources, device_name)?;
+        if info.crtc == 0 {
+            return Err(anyhow!("Output {} is not active", device_name));
+        }
+
+        let crtc = self
+            .conn
+            .randr_get_crtc_info(info.crtc, resources.config_timestamp)?
+            .reply()?;
+        let mode = find_mode(&resources, crtc.mode)
+            .ok_or_else(|| anyhow!("CRTC of {} uses an unknown mode", device_name))?;
+
+        Ok(to_display_mode(mode))
+    }
+
+    fn query_modes(&self, device_name: &str) -> Result<Vec<DisplayMode>> {
+        let resources = self.resources()?;
+        let (_, info) = self.find_output(&resources, device_name)?;
+
+        // The first num_preferred modes of an output are its preferred ones
+        Ok(info
+            .modes
+            .iter()
+            .filter_map(|&id| find_mode(&resources, id))
+            .enumerate()
+            .map(|(index, mode)| DisplayMode {
+                preferred: index < info.num_preferred as usize,
+                ..to_display_mode(mode)
+            })
+            .collect())
+    }
+
+    /// A heuristic: RandR has no check-only request, so this only verifies
+    /// that the output is driven by a CRTC that can scan out the mode and
+    /// that the X screen can grow to hold it. The driver may still reject
+    /// the mode when it is applied.
+    fn test_mode(&self, device_name: &str, mode: &DisplayMode) -> Result<()> {
+        let resources = self.resources()?;
+        let (output, info) = self.find_output(&resources, device_name)?;
+        if info.crtc == 0 {
+            return Err(anyhow!("Output {} is not active", device_name));
+        }
+        let target = find_target(&resources, &info, device_name, mode)?;
+
+        let crtc = self
+            .conn
+            .randr_get_crtc_info(info.crtc, resources.config_timestamp)?
+            .reply()?;
+        if !crtc.possible.contains(&output) {
+            return Err(anyhow!(
+                "CRTC {} cannot drive output {}",
+                info.crtc,
+                device_name
+            ));
+        }
+        self.screen_size_for(&resources, info.crtc, &crtc, target)?;
+        Ok(())
+    }
+
+    fn tests_with_driver(&self) -> bool {
+        false
+    }
+
+    /// Grows the screen before reprogramming the CRTC when the mode doesn't
+    /// fit, and shrinks it to the outputs once the CRTC runs the mode.
+    fn apply_mode(&self, device_name: &str, mode: &DisplayMode) -> Result<()> {
+        let resources = self.resources()?;
+        let (_, info) = self.find_output(&resources, device_name)?;
+        if info.crtc == 0 {
+            return Err(anyhow!("Output {} is not active", device_name));
+        }
+        let target = find_target(&resources, &info, device_name, mode)?;
+
+        let crtc = self
+            .conn
+            .randr_get_crtc_info(info.crtc, resources.config_timestamp)?
+            .reply()?;
+        let current = self.screen_size()?;
+        let needed = self.screen_size_for(&resources, info.crtc, &crtc, target)?;
+        let grown = (current.0.max(needed.0), current.1.max(needed.1));
+        if grown != current {
+            self.set_screen_size(grown)?;
+        }
+
+        // Resizing the screen may have touched the CRTC's timestamp
+        let result = self
+            .conn
+            .randr_get_crtc_info(info.crtc, resources.config_timestamp)
+            .map_err(anyhow::Error::from)
+            .and_then(|cookie| Ok(cookie.reply()?))
+            .and_then(|crtc| {
+                Ok(self
+                    .conn
+                    .randr_set_crtc_config(
+                        info.crtc,
+                        crtc.timestamp,
+                        resources.config_timestamp,
+                        crtc.x,
+                        crtc.y,
+                        target.id,
+                        crtc.rotation,
+                        &crtc.outputs,
+                    )?
+                    .reply()?)
+            })
+            .and_then(|reply| check_status(reply.status));
+        if let Err(e) = result {
+            if grown != current {
+                let _ = self.set_screen_size(current);
+            }
+            return Err(e);
+        }
+        if needed != grown {
+            self.set_screen_size(needed)?;
+        }
+        Ok(())
+    }
+
+    fn edid(&self, device_name: &str) -> Result<Option<Vec<u8>>> {
+        let resources = self.resources()?;
+        let (output, _) = self.find_output(&resources, device_name)?;
+        let edid = self.output_edid(output)?;
+        Ok((!edid.is_empty()).then_some(edid))
+    }
+
+    /// amdgpu and modesetting mirror the DRM `vrr_capable` connector
+    /// property as an output property of the same name.
+    fn vrr(&self, device_name: &str) -> Result<Option<VrrCapability>> {
+        let resources = self.resources()?;
+        let (output, _) = self.find_output(&resources, device_name)?;
+        let edid = Edid::parse(&self.output_edid(output)?).ok();
+        // A 32-bit integer; checking every byte avoids caring about byte order
+        let property = self.output_property(output, b"vrr_capable")?;
+        let driver_capable = (!property.is_empty()).then(|| property.iter().any(|&b| b != 0));
+        Ok(vrr_capability(edid.as_ref(), driver_capable))
+    }
+
+    /// Drivers that let VRR be switched per output expose it as a
+    /// `VariableRefresh` integer property.
+    fn vrr_enabled(&self, device_name: &str) -> Result<Option<bool>> {
+        let resources = self.resources()?;
+        let (output, _) = self.find_output(&resources, device_name)?;
+        let property = self.output_property(output, b"VariableRefresh")?;
+        Ok((!property.is_empty()).then(|| property.iter().any(|&b| b != 0)))
+    }
+
+    fn set_vrr(&self, device_name: &str, enabled: bool) -> Result<()> {
+        let resources = self.resources()?;
+        let (output, _) = self.find_output(&resources, device_name)?;
+        if self.output_property(output, b"VariableRefresh")?.is_empty() {
+            return Err(HertzRateError::BackendUnsupported(format!(
+                "Toggling VRR is not supported for output {}: the X driver has no \
+                 VariableRefresh output property (set Option \"VariableRefresh\" in xorg.conf instead)",
+                device_name
+            ))
+            .into());
+        }
+
+        let atom = self
+            .conn
+            .intern_atom(true, b"VariableRefresh")?
+            .reply()?
+            .atom;
+        self.conn
+            .randr_change_output_property(
+                output,
+                atom,
+                AtomEnum::INTEGER.into(),
+                32,
+                PropMode::REPLACE,
+                1,
+                &u32::from(enabled).to_ne_bytes(),
+            )?
+            .check()
+            .with_context(|| {
+                format!(
+                    "X server rejected the VariableRefresh change for {}",
+                    device_name
+                )
+            })?;
+        Ok(())
+    }
+
+    fn placement(&self, device_name: &str) -> Result<Option<Placement>> {
+        let resources = self.resources()?;
+        let (_, info) = self.find_output(&resources, device_name)?;
+        if info.crtc == 0 {
+            return Ok(None);
+        }
+
+        let crtc = self
+            .conn
+            .randr_get_crtc_info(info.crtc, resources.config_timestamp)?
+            .reply()?;
+        Ok(Some(Placement {
+            x: crtc.x as i32,
+            y: crtc.y as i32,
+            orientation: to_orientation(crtc.rotation),
+        }))
+    }
+
+    /// Moves the CRTC driving the output; the screen is not resized, so the
+    /// new position must fit inside the current screen.
+    fn apply_placement(&self, device_name: &str, placement: &Placement) -> Result<()> {
+        let resources = self.resources()?;
+        let (_, info) = self.find_output(&resources, device_name)?;
+        if info.crtc == 0 {
+            return Err(anyhow!("Output {} is not active", device_name));
+        }
+        let x = i16::try_from(placement.x)
+            .map_err(|_| anyhow!("Position {} is outside the X screen", placement))?;
+        let y = i16::try_from(placement.y)
+            .map_err(|_| anyhow!("Position {} is outside the X screen", placement))?;
+
+        let crtc = self
+            .conn
+            .randr_get_crtc_info(info.crtc, resources.config_timestamp)?
+            .reply()?;
+        let reply = self
+            .conn
+            .randr_set_crtc_config(
+                info.crtc,
+                crtc.timestamp,
+                resources.config_timestamp,
+                x,
+                y,
+                crtc.mode,
+                to_rotation(placement.orientation),
+                &crtc.outputs,
+            )?
+            .reply()?;
+        check_status(reply.status)
+    }
+}
+
+fn check_status(status: SetConfig) -> Result<()> {
+    match status {
+        SetConfig::SUCCESS => Ok(()),
+        status => Err(HertzRateError::DriverRejected(format!(
+            "RandR SetCrtcConfig failed with status {:?}",
+            status
+        ))
+        .into()),
+    }
+}
+
+/// RandR rotations are counter-clockwise like Wayland transforms; a Wayland
+/// flip mirrors around the vertical axis, which is RandR's `REFLECT_X`.
+fn to_orientation(rotation: Rotation) -> Orientation {
+    let bits = u16::from(rotation);
+    let degrees = [
+        Rotation::ROTATE0,
+        Rotation::ROTATE90,
+        Rotation::ROTATE180,
+        Rotation::ROTATE270,
+    ]
+    .iter()
+    .position(|r| bits & u16::from(*r) != 0)
+    .unwrap_or(0) as u32;
+    let flipped = bits & u16::from(Rotation::REFLECT_X) != 0;
+    Orientation::from_transform(degrees + if flipped { 4 } else { 0 }).unwrap_or_default()
+}
+
+fn to_rotation(orientation: Orientation) -> Rotation {
+    let rotation = match orientation.degrees() {
+        90 => Rotation::ROTATE90,
+        180 => Rotation::ROTATE180,
+        270 => Rotation::ROTATE270,
+        _ => Rotation::ROTATE0,
+    };
+    if orientation.flipped() {
+        rotation | Rotation::REFLECT_X
+    } else {
+        rotation
+    }
+}
+
+fn find_mode(resources: &GetScreenResourcesCurrentReply, id: randr::Mode) -> Option<&ModeInfo> {
+    resources.modes.iter().find(|mode| mode.id == id)
+}
+
+/// The output's RandR mode with the same timing as `mode`.
+fn find_target<'a>(
+    resources: &'a GetScreenResourcesCurrentReply,
+    info: &GetOutputInfoReply,
+    device_name: &str,
+    mode: &DisplayMode,
+) -> Result<&'a ModeInfo> {
+    info.modes
+        .iter()
+        .filter_map(|&id| find_mode(resources, id))
+        .find(|candidate| to_display_mode(candidate).same_timing(mode))
+        .ok_or_else(|| {
+            HertzRateError::BadMode(format!("Output {} has no mode {}", device_name, mode)).into()
+        })
+}
+
+/// The size the mode covers on the screen; quarter turns swap its sides.
+fn rotated_size(mode: &ModeInfo, rotation: Rotation) -> (u16, u16) {
+    if u16::from(rotation) & u16::from(Rotation::ROTATE90 | Rotation::ROTATE270) != 0 {
+        (mode.height, mode.width)
+    } else {
+        (mode.width, mode.height)
+    }
+}
+
+fn to_display_mode(mode: &ModeInfo) -> DisplayMode {
+    DisplayMode {
+        interlaced: u32::from(mode.mode_flags) & u32::from(ModeFlag::INTERLACE) != 0,
+        ..DisplayMode::new(mode.width as u32, mode.height as u32, refresh_rate(mode))
+    }
+}
+
+/// Exact refresh rate from the dot clock and the total (active + blanking)
+/// raster. Interlaced modes scan two fields per frame.
+fn refresh_rate(mode: &ModeInfo) -> RefreshRate {
+    let flags = u32::from(mode.mode_flags);
+    let mut dot_clock = mode.dot_clock as u64;
+    let mut vtotal = mode.vtotal as u64;
+    if flags & u32::from(ModeFlag::DOUBLE_SCAN) != 0 {
+        vtotal *= 2;
+    }
+    if flags & u32::from(ModeFlag::INTERLACE) != 0 {
+        dot_clock *= 2;
+    }
+    RefreshRate::from_timing(dot_clock, mode.htotal as u64, vtotal)
+}
@@ -0,0 +1,445 @@
+use super::DetailedTiming;
+use crate::backend::DisplayMode;
+use crate::refresh_rate::RefreshRate;
+
+pub const TAG: u8 = 0x02;
+
+const HDMI_OUI: u32 = 0x000C03;
+const HDMI_FORUM_OUI: u32 = 0xC45DD8;
+const AMD_OUI: u32 = 0x00001A;
+
+/// Width, height and nominal rate of VICs 1-127, indexed by VIC - 1. VICs
+/// that differ only in aspect ratio or pixel repetition repeat an entry.
+const VICS: [(u32, u32, u32); 127] = [
+    (640, 480, 60),
+    (720, 480, 60),
+    (720, 480, 60),
+    (1280, 720, 60),
+    (1920, 1080, 60),
+    (1440, 480, 60),
+    (1440, 480, 60),
+    (1440, 240, 60),
+    (1440, 240, 60),
+    (2880, 480, 60),
+    (2880, 480, 60),
+    (2880, 240, 60),
+    (2880, 240, 60),
+    (1440, 480, 60),
+    (1440, 480, 60),
+    (1920, 1080, 60),
+    (720, 576, 50),
+    (720, 576, 50),
+    (1280, 720, 50),
+    (1920, 1080, 50),
+    (1440, 576, 50),
+    (1440, 576, 50),
+    (1440, 288, 50),
+    (1440, 288, 50),
+    (2880, 576, 50),
+    (2880, 576, 50),
+    (2880, 288, 50),
+    (2880, 288, 50),
+    (1440, 576, 50),
+    (1440, 576, 50),
+    (1920, 1080, 50),
+    (1920, 1080, 24),
+    (1920, 1080, 25),
+    (1920, 1080, 30),
+    (2880, 480, 60),
+    (2880, 480, 60),
+    (2880, 576, 50),
+    (2880, 576, 50),
+    (1920, 1080, 50),
+    (1920, 1080, 100),
+    (1280, 720, 100),
+    (720, 576, 100),
+    (720, 576, 100),
+    (1440, 576, 100),
+    (1440, 576, 100),
+    (1920, 1080, 120),
+    (1280, 720, 120),
+    (720, 480, 120),
+    (720, 480, 120),
+    (1440, 480, 120),
+    (1440, 480, 120),
+    (720, 576, 200),
+    (720, 576, 200),
+    (1440, 576, 200),
+    (1440, 576, 200),
+    (720, 480, 240),
+    (720, 480, 240),
+    (1440, 480, 240),
+    (1440, 480, 240),
+    (1280, 720, 24),
+    (1280, 720, 25),
+    (1280, 720, 30),
+    (1920, 1080, 120),
+    (1920, 1080, 100),
+    (1280, 720, 24),
+    (1280, 720, 25),
+    (1280, 720, 30),
+    (1280, 720, 50),
+    (1280, 720, 60),
+    (1280, 720, 100),
+    (1280, 720, 120),
+    (1920, 1080, 24),
+    (1920, 1080, 25),
+    (1920, 1080, 30),
+    (1920, 1080, 50),
+    (1920, 1080, 60),
+    (1920, 1080, 100),
+    (1920, 1080, 120),
+    (1680, 720, 24),
+    (1680, 720, 25),
+    (1680, 720, 30),
+    (1680, 720, 50),
+    (1680, 720, 60),
+    (1680, 720, 100),
+    (1680, 720, 120),
+    (2560, 1080, 24),
+    (2560, 1080, 25),
+    (2560, 1080, 30),
+    (2560, 1080, 50),
+    (2560, 1080, 60),
+    (2560, 1080, 100),
+    (2560, 1080, 120),
+    (3840, 2160, 24),
+    (3840, 2160, 25),
+    (3840, 2160, 30),
+    (3840, 2160, 50),
+    (3840, 2160, 60),
+    (4096, 2160, 24),
+    (4096, 2160, 25),
+    (4096, 2160, 30),
+    (4096, 2160, 50),
+    (4096, 2160, 60),
+    (3840, 2160, 24),
+    (3840, 2160, 25),
+    (3840, 2160, 30),
+    (3840, 2160, 50),
+    (3840, 2160, 60),
+    (1280, 720, 48),
+    (1280, 720, 48),
+    (1680, 720, 48),
+    (1920, 1080, 48),
+    (1920, 1080, 48),
+    (2560, 1080, 48),
+    (3840, 2160, 48),
+    (4096, 2160, 48),
+    (3840, 2160, 48),
+    (3840, 2160, 100),
+    (3840, 2160, 120),
+    (3840, 2160, 100),
+    (3840, 2160, 120),
+    (5120, 2160, 24),
+    (5120, 2160, 25),
+    (5120, 2160, 30),
+    (5120, 2160, 48),
+    (5120, 2160, 50),
+    (5120, 2160, 60),
+    (5120, 2160, 100),
+];
+
+/// VICs 193-219, indexed by VIC - 193.
+const HIGH_VICS: [(u32, u32, u32); 27] = [
+    (5120, 2160, 120),
+    (7680, 4320, 24),
+    (7680, 4320, 25),
+    (7680, 4320, 30),
+    (7680, 4320, 48),
+    (7680, 4320, 50),
+    (7680, 4320, 60),
+    (7680, 4320, 100),
+    (7680, 4320, 120),
+    (7680, 4320, 24),
+    (7680, 4320, 25),
+    (7680, 4320, 30),
+    (7680, 4320, 48),
+    (7680, 4320, 50),
+    (7680, 4320, 60),
+    (7680, 4320, 100),
+    (7680, 4320, 120),
+    (10240, 4320, 24),
+    (10240, 4320, 25),
+    (10240, 4320, 30),
+    (10240, 4320, 48),
+    (10240, 4320, 50),
+    (10240, 4320, 60),
+    (10240, 4320, 100),
+    (10240, 4320, 120),
+    (4096, 2160, 100),
+    (4096, 2160, 120),
+];
+
+const INTERLACED_VICS: [u8; 21] = [
+    5, 6, 7, 10, 11, 20, 21, 22, 25, 26, 39, 40, 44, 45, 46, 50, 51, 54, 55, 58, 59,
+];
+
+/// A CTA-861 extension block (tag 0x02).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CtaExtension {
+    pub revision: u8,
+    /// Number of native detailed timings; 0 before revision 2
+    pub native_dtds: u8,
+    pub underscan: bool,
+    pub basic_audio: bool,
+    pub ycbcr444: bool,
+    pub ycbcr422: bool,
+    /// The data block collection, in order
+    pub data_blocks: Vec<DataBlock>,
+    /// Detailed timings after the data block collection
+    pub detailed_timings: Vec<DetailedTiming>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DataBlock {
+    /// Tag 1
+    Audio,
+    /// Tag 2
+    Video(Vec<ShortVideoDescriptor>),
+    /// Tag 4
+    SpeakerAllocation,
+    /// Tag 3 with the HDMI Licensing OUI
+    Hdmi(HdmiVsdb),
+    /// Tag 3 with the HDMI Forum OUI
+    HdmiForum(HdmiForumVsdb),
+    /// Tag 3 with AMD's OUI, which carries the FreeSync range
+    FreeSync(FreeSyncVsdb),
+    /// Any other vendor-specific data block
+    VendorSpecific {
+        oui: u32,
+    },
+    /// Tag 7, identified by the extended tag in its first byte
+    Extended {
+        tag: u8,
+    },
+    Other {
+        tag: u8,
+    },
+}
+
+/// One byte of a video data block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShortVideoDescriptor {
+    pub vic: u8,
+    /// The display's native format; only VICs 1-64 can carry the flag
+    pub native: bool,
+}
+
+/// The HDMI 1.4 vendor-specific data block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HdmiVsdb {
+    /// CEC physical address, e.g. `[1, 0, 0, 0]` for 1.0.0.0
+    pub physical_address: [u8; 4],
+    pub max_tmds_clock_mhz: Option<u32>,
+}
+
+/// The HDMI Forum vendor-specific data block of HDMI 2.x sinks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HdmiForumVsdb {
+    pub version: u8,
+    pub max_tmds_character_rate_mhz: Option<u32>,
+    pub scdc_present: bool,
+    /// Maximum FRL rate in Gbps (lanes times rate per lane), 0 without FRL
+    pub max_frl_gbps: u32,
+    pub allm: bool,
+    /// HDMI VRR range in Hz, when the block is long enough to carry one
+    pub vrr_hz: Option<(u16, u16)>,
+}
+
+/// AMD's FreeSync vendor-specific data block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FreeSyncVsdb {
+    pub version: u8,
+    pub min_hz: u16,
+    pub max_hz: u16,
+}
+
+impl CtaExtension {
+    /// Decodes a 128-byte CTA-861 extension block.
+    pub fn parse(block: &[u8]) -> Self {
+        // Byte 2 is where the detailed timings start; 0 means there are
+        // neither data blocks nor timings
+        let dtd_offset = match block[2] {
+            0 => 127,
+            offset => (offset as usize).clamp(4, 127),
+        };
+        let flags = if block[1] >= 2 { block[3] } else { 0 };
+
+        let mut data_blocks = Vec::new();
+        let mut offset = 4;
+        while block[2] != 0 && offset < dtd_offset {
+            let tag = block[offset] >> 5;
+            let length = (block[offset] & 0x1F) as usize;
+            let end = (offset + 1 + length).min(dtd_offset);
+            data_blocks.push(DataBlock::parse(tag, &block[offset + 1..end]));
+            offset += 1 + length;
+        }
+
+        let detailed_timings = block[dtd_offset..127]
+            .chunks_exact(18)
+            .take_while(|bytes| bytes[0] != 0 || bytes[1] != 0)
+            .map(DetailedTiming::parse)
+            .collect();
+
+        Self {
+            revision: block[1],
+            native_dtds: flags & 0x0F,
+            underscan: flags & 0x80 != 0,
+            basic_audio: flags & 0x40 != 0,
+            ycbcr444: flags & 0x20 != 0,
+            ycbcr422: flags & 0x10 != 0,
+            data_blocks,
+            detailed_timings,
+        }
+    }
+
+    pub fn short_video_descriptors(&self) -> impl Iterator<Item = &ShortVideoDescriptor> {
+        self.data_blocks.iter().flat_map(|block| match block {
+            DataBlock::Video(svds) => svds.as_slice(),
+            _ => &[],
+        })
+    }
+
+    pub fn hdmi(&self) -> Option<&HdmiVsdb> {
+        self.data_blocks.iter().find_map(|block| match block {
+            DataBlock::Hdmi(hdmi) => Some(hdmi),
+            _ => None,
+        })
+    }
+
+    pub fn hdmi_forum(&self) -> Option<&HdmiForumVsdb> {
+        self.data_blocks.iter().find_map(|block| match block {
+            DataBlock::HdmiForum(hdmi_forum) => Some(hdmi_forum),
+            _ => None,
+        })
+    }
+
+    pub fn freesync(&self) -> Option<&FreeSyncVsdb> {
+        self.data_blocks.iter().find_map(|block| match block {
+            DataBlock::FreeSync(freesync) => Some(freesync),
+            _ => None,
+        })
+    }
+
+    /// Detailed timings, then the formats of the short video descriptors.
+    /// Native SVDs are marked preferred.
+    pub fn modes(&self) -> Vec<DisplayMode> {
+        let mut modes: Vec<DisplayMode> = self
+            .detailed_timings
+            .iter()
+            .filter_map(DetailedTiming::to_display_mode)
+            .collect();
+        modes.extend(self.short_video_descriptors().filter_map(|svd| {
+            Some(DisplayMode {
+                preferred: svd.native,
+                ..svd.to_display_mode()?
+            })
+        }));
+        modes
+    }
+}
+
+impl DataBlock {
+    fn parse(tag: u8, payload: &[u8]) -> Self {
+        match tag {
+            1 => DataBlock::Audio,
+            2 => DataBlock::Video(
+                payload
+                    .iter()
+                    .map(|&byte| ShortVideoDescriptor::parse(byte))
+                    .collect(),
+            ),
+            3 if payload.len() >= 3 => {
+                let oui = u32::from_le_bytes([payload[0], payload[1], payload[2], 0]);
+                match oui {
+                    HDMI_OUI => DataBlock::Hdmi(HdmiVsdb::parse(payload)),
+                    HDMI_FORUM_OUI => DataBlock::HdmiForum(HdmiForumVsdb::parse(payload)),
+                    AMD_OUI if payload.len() >= 7 => DataBlock::FreeSync(FreeSyncVsdb {
+                        version: payload[3],
+                        min_hz: payload[5] as u16,
+                        max_hz: payload[6] as u16,
+                    }),
+                    oui => DataBlock::VendorSpecific { oui },
+                }
+            }
+            4 => DataBlock::SpeakerAllocation,
+            7 if !payload.is_empty() => DataBlock::Extended { tag: payload[0] },
+            tag => DataBlock::Other { tag },
+        }
+    }
+}
+
+impl ShortVideoDescriptor {
+    fn parse(byte: u8) -> Self {
+        // Since CTA-861-F, bit 7 is only a native flag for VICs 1-64
+        match byte {
+            129..=192 => Self {
+                vic: byte & 0x7F,
+                native: true,
+            },
+            _ => Self {
+                vic: byte,
+                native: false,
+            },
+        }
+    }
+
+    /// The format of this VIC at its nominal rate, or `None` for reserved VICs.
+    pub fn to_display_mode(&self) -> Option<DisplayMode> {
+        let (width, height, rate) = match self.vic {
+            1..=127 => VICS[self.vic as usize - 1],
+            193..=219 => HIGH_VICS[self.vic as usize - 193],
+            _ => return None,
+        };
+        Some(DisplayMode {
+            interlaced: INTERLACED_VICS.contains(&self.vic),
+            ..DisplayMode::new(width, height, RefreshRate::from_hz(rate))
+        })
+    }
+}
+
+impl HdmiVsdb {
+    fn parse(payload: &[u8]) -> Self {
+        let byte = |index: usize| payload.get(index).copied().unwrap_or(0);
+        Self {
+            physical_address: [byte(3) >> 4, byte(3) & 0x0F, byte(4) >> 4, byte(4) & 0x0F],
+            max_tmds_clock_mhz: (byte(6) != 0).then(|| byte(6) as u32 * 5),
+        }
+    }
+}
+
+impl HdmiForumVsdb {
+    fn parse(payload: &[u8]) -> Self {
+        let byte = |index: usize| payload.get(index).copied().unwrap_or(0);
+        let max_frl_gbps = match byte(6) >> 4 {
+            1 => 9,
+            2 => 18,
+            3 => 24,
+            4 => 32,
+            5 => 40,
+            6 => 48,
+            _ => 0,
+        };
+        let vrr_max = ((byte(8) as u16 & 0xC0) << 2) | byte(9) as u16;
+        Self {
+            version: byte(3),
+            max_tmds_character_rate_mhz: (byte(4) != 0).then(|| byte(4) as u32 * 5),
+            scdc_present: byte(5) & 0x80 != 0,
+            max_frl_gbps,
+            allm: byte(7) & 0x02 != 0,
+            vrr_hz: (payload.len() >= 10 && byte(8) & 0x3F != 0)
+                .then(|| ((byte(8) & 0x3F) as u16, vrr_max)),
+        }
+    }
+}
+
+/// The first CTA-861 format with this size and scan type, in VIC order, e.g.
+/// 720x576@50 for 720x576. The kernel adds such formats for HDMI sinks even
+/// when the EDID doesn't list them.
+pub fn first_format(width: u32, height: u32, interlaced: bool) -> Option<DisplayMode> {
+    (1..=127u8)
+        .chain(193..=219)
+        .filter_map(|vic| ShortVideoDescriptor { vic, native: false }.to_display_mode())
+        .find(|mode| mode.width == width && mode.height == height && mode.interlaced == interlaced)
+}
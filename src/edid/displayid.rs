@@ -0,0 +1,133 @@
+use super::{checksum, DetailedTiming};
+use crate::backend::DisplayMode;
+
+pub const TAG: u8 = 0x70;
+
+/// DisplayID 1.x Type I detailed timing, in 10kHz units.
+const TYPE_I_TIMING: u8 = 0x03;
+/// DisplayID 2.0 Type VII detailed timing, in 1kHz units.
+const TYPE_VII_TIMING: u8 = 0x22;
+
+/// A DisplayID section carried in an EDID extension block (tag 0x70).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisplayIdExtension {
+    /// Structure version, e.g. `0x20` for DisplayID 2.0
+    pub version: u8,
+    /// Product type (1.x) or primary use case (2.0)
+    pub product_type: u8,
+    pub data_blocks: Vec<DisplayIdBlock>,
+    /// Whether the section's own checksum is valid
+    pub checksum_valid: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DisplayIdBlock {
+    /// Type I or Type VII detailed timings
+    DetailedTimings(Vec<DisplayIdTiming>),
+    Other {
+        tag: u8,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayIdTiming {
+    pub timing: DetailedTiming,
+    pub preferred: bool,
+}
+
+impl DisplayIdExtension {
+    /// Decodes the DisplayID section of a 128-byte extension block.
+    pub fn parse(block: &[u8]) -> Self {
+        let end = (5 + block[2] as usize).min(126);
+
+        let mut data_blocks = Vec::new();
+        let mut offset = 5;
+        while offset + 3 <= end {
+            let tag = block[offset];
+            let revision = block[offset + 1];
+            let length = block[offset + 2] as usize;
+            // Padding after the last block is zero-filled
+            if tag == 0 && revision == 0 && length == 0 {
+                break;
+            }
+            let payload = &block[offset + 3..(offset + 3 + length).min(end)];
+            data_blocks.push(DisplayIdBlock::parse(tag, revision, payload));
+            offset += 3 + length;
+        }
+
+        Self {
+            version: block[1],
+            product_type: block[3],
+            data_blocks,
+            checksum_valid: checksum(&block[1..=end]) == 0,
+        }
+    }
+
+    pub fn timings(&self) -> impl Iterator<Item = &DisplayIdTiming> {
+        self.data_blocks.iter().flat_map(|block| match block {
+            DisplayIdBlock::DetailedTimings(timings) => timings.as_slice(),
+            _ => &[],
+        })
+    }
+
+    pub fn modes(&self) -> Vec<DisplayMode> {
+        self.timings()
+            .filter_map(|timing| {
+                Some(DisplayMode {
+                    preferred: timing.preferred,
+                    ..timing.timing.to_display_mode()?
+                })
+            })
+            .collect()
+    }
+}
+
+impl DisplayIdBlock {
+    fn parse(tag: u8, revision: u8, payload: &[u8]) -> Self {
+        match tag {
+            TYPE_I_TIMING | TYPE_VII_TIMING => {
+                let (size, clock_khz) = if tag == TYPE_VII_TIMING {
+                    // Bits 6-4 of the revision give extra bytes per descriptor
+                    (20 + ((revision >> 4) & 0x07) as usize, 1)
+                } else {
+                    (20, 10)
+                };
+                DisplayIdBlock::DetailedTimings(
+                    payload
+                        .chunks_exact(size)
+                        .map(|bytes| DisplayIdTiming::parse(bytes, clock_khz))
+                        .collect(),
+                )
+            }
+            tag => DisplayIdBlock::Other { tag },
+        }
+    }
+}
+
+impl DisplayIdTiming {
+    /// Every field is stored minus one; sync fields carry the polarity in
+    /// their top bit.
+    fn parse(bytes: &[u8], clock_khz: u32) -> Self {
+        let raw = |index: usize| u16::from_le_bytes([bytes[index], bytes[index + 1]]) as u32;
+        let field = |index: usize| raw(index) + 1;
+        let sync = |index: usize| (raw(index) & 0x7FFF) + 1;
+        let pixel_clock = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]) + 1;
+        Self {
+            timing: DetailedTiming {
+                pixel_clock_khz: pixel_clock * clock_khz,
+                h_active: field(4),
+                h_blank: field(6),
+                h_sync_offset: sync(8),
+                h_sync_width: sync(10),
+                v_active: field(12),
+                v_blank: field(14),
+                v_sync_offset: sync(16),
+                v_sync_width: sync(18),
+                h_image_mm: 0,
+                v_image_mm: 0,
+                interlaced: bytes[3] & 0x10 != 0,
+            },
+            preferred: bytes[3] & 0x80 != 0,
+        }
+    }
+}
@@ -0,0 +1,769 @@
+pub mod cta;
+pub mod displayid;
+pub mod report;
+
+use crate::backend::{DisplayMode, MonitorIdentity, VrrCapability, VrrSource};
+use crate::refresh_rate::RefreshRate;
+use anyhow::{anyhow, Result};
+use cta::{CtaExtension, ShortVideoDescriptor};
+use displayid::DisplayIdExtension;
+
+const HEADER: [u8; 8] = [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+pub const BLOCK_SIZE: usize = 128;
+
+/// The established timings of bytes 35-37, most significant bit first. The
+/// last entry is the one manufacturer timing bit defined by VESA.
+const ESTABLISHED_TIMINGS: [(u32, u32, u32); 17] = [
+    (720, 400, 70),
+    (720, 400, 88),
+    (640, 480, 60),
+    (640, 480, 67),
+    (640, 480, 72),
+    (640, 480, 75),
+    (800, 600, 56),
+    (800, 600, 60),
+    (800, 600, 72),
+    (800, 600, 75),
+    (832, 624, 75),
+    (1024, 768, 87),
+    (1024, 768, 60),
+    (1024, 768, 70),
+    (1024, 768, 75),
+    (1280, 1024, 75),
+    (1152, 870, 75),
+];
+
+/// The 128-byte EDID base block and its extension blocks, decoded.
+#[derive(Debug, Clone)]
+pub struct Edid {
+    /// EDID structure version and revision, e.g. `(1, 4)`
+    pub version: (u8, u8),
+    /// Three-letter PNP manufacturer ID, e.g. `DEL`
+    pub manufacturer: String,
+    pub product_code: u16,
+    /// Numeric serial number; 0 when unused
+    pub serial_number: u32,
+    pub manufacture_date: ManufactureDate,
+    /// Digital rather than analog video input
+    pub digital: bool,
+    /// Maximum image size in centimetres, when the block gives one
+    pub screen_size_cm: Option<(u8, u8)>,
+    /// Feature support bit 0: the display accepts any timing within its
+    /// range limits (EDID 1.4) or supports GTF (earlier versions)
+    pub continuous_frequency: bool,
+    pub established_timings: Vec<DisplayMode>,
+    pub standard_timings: Vec<StandardTiming>,
+    /// The four 18-byte descriptors, in order
+    pub descriptors: Vec<Descriptor>,
+    /// Number of extension blocks the base block announces
+    pub extension_count: u8,
+    /// Whether the base block's bytes sum to 0 modulo 256
+    pub checksum_valid: bool,
+    /// The extension blocks present in the data, which may be fewer than
+    /// `extension_count` when the EDID was truncated
+    pub extensions: Vec<ExtensionBlock>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExtensionBlock {
+    pub contents: Extension,
+    pub checksum_valid: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Extension {
+    Cta(CtaExtension),
+    DisplayId(DisplayIdExtension),
+    /// An extension this module does not decode, e.g. a block map (0xF0)
+    Other {
+        tag: u8,
+    },
+}
+
+/// Week and year of manufacture, or the model year when `week` is 0xFF.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ManufactureDate {
+    /// 1-54, or `None` when unspecified
+    pub week: Option<u8>,
+    pub year: u16,
+    pub model_year: bool,
+}
+
+/// A 2-byte standard timing: resolution from the width and aspect ratio.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StandardTiming {
+    pub width: u32,
+    pub height: u32,
+    pub refresh_hz: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Descriptor {
+    DetailedTiming(DetailedTiming),
+    /// Tag 0xFC
+    MonitorName(String),
+    /// Tag 0xFF
+    SerialNumber(String),
+    /// Tag 0xFE, often the panel vendor and model on laptops
+    Text(String),
+    /// Tag 0xFD
+    RangeLimits(RangeLimits),
+    /// Any other display descriptor, e.g. the 0x10 dummy descriptor
+    Other {
+        tag: u8,
+    },
+}
+
+/// An 18-byte detailed timing descriptor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DetailedTiming {
+    pub pixel_clock_khz: u32,
+    pub h_active: u32,
+    pub h_blank: u32,
+    pub h_sync_offset: u32,
+    pub h_sync_width: u32,
+    /// Lines per field for interlaced timings
+    pub v_active: u32,
+    pub v_blank: u32,
+    pub v_sync_offset: u32,
+    pub v_sync_width: u32,
+    /// Image size in millimetres
+    pub h_image_mm: u32,
+    pub v_image_mm: u32,
+    pub interlaced: bool,
+}
+
+/// The display range limits descriptor (tag 0xFD).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RangeLimits {
+    pub min_vertical_hz: u16,
+    pub max_vertical_hz: u16,
+    pub min_horizontal_khz: u16,
+    pub max_horizontal_khz: u16,
+    /// Maximum pixel clock, when given (it is a multiple of 10MHz)
+    pub max_pixel_clock_mhz: Option<u32>,
+}
+
+impl Edid {
+    /// Decodes the base block of `bytes` and the extension blocks after it.
+    /// Bad checksums are reported in `checksum_valid` rather than rejected,
+    /// since many monitors ship one.
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < BLOCK_SIZE {
+            return Err(anyhow!(
+                "EDID is {} bytes, shorter than the {}-byte base block",
+                bytes.len(),
+                BLOCK_SIZE
+            ));
+        }
+        let block = &bytes[..BLOCK_SIZE];
+        if block[..8] != HEADER {
+            return Err(anyhow!("EDID does not start with the fixed header pattern"));
+        }
+
+        let id = u16::from_be_bytes([block[8], block[9]]);
+        let letter = |shift: u16| (((id >> shift) & 0x1F) as u8 + b'@') as char;
+
+        Ok(Self {
+            version: (block[18], block[19]),
+            manufacturer: [letter(10), letter(5), letter(0)].iter().collect(),
+            product_code: u16::from_le_bytes([block[10], block[11]]),
+            serial_number: u32::from_le_bytes([block[12], block[13], block[14], block[15]]),
+            manufacture_date: ManufactureDate {
+                week: (block[16] != 0 && block[16] != 0xFF).then_some(block[16]),
+                year: 1990 + block[17] as u16,
+                model_year: block[16] == 0xFF,
+            },
+            digital: block[20] & 0x80 != 0,
+            screen_size_cm: (block[21] != 0 && block[22] != 0).then_some((block[21], block[22])),
+            continuous_frequency: block[24] & 0x01 != 0,
+            established_timings: parse_established_timings(block),
+            standard_timings: block[38..54]
+                .chunks(2)
+                .filter_map(StandardTiming::parse)
+                .collect(),
+            descriptors: block[54..126].chunks(18).map(Descriptor::parse).collect(),
+            extension_count: block[126],
+            checksum_valid: checksum(block) == 0,
+            extensions: bytes[BLOCK_SIZE..]
+                .chunks_exact(BLOCK_SIZE)
+                .take(block[126] as usize)
+                .map(ExtensionBlock::parse)
+                .collect(),
+        })
+    }
+
+    pub fn cta_extensions(&self) -> impl Iterator<Item = &CtaExtension> {
+        self.extensions
+            .iter()
+            .filter_map(|block| match &block.contents {
+                Extension::Cta(cta) => Some(cta),
+                _ => None,
+            })
+    }
+
+    pub fn displayid_extensions(&self) -> impl Iterator<Item = &DisplayIdExtension> {
+        self.extensions
+            .iter()
+            .filter_map(|block| match &block.contents {
+                Extension::DisplayId(displayid) => Some(displayid),
+                _ => None,
+            })
+    }
+
+    /// The highest TMDS rate any HDMI block allows, in MHz.
+    pub fn max_tmds_mhz(&self) -> Option<u32> {
+        self.cta_extensions()
+            .flat_map(|cta| {
+                [
+                    cta.hdmi().and_then(|hdmi| hdmi.max_tmds_clock_mhz),
+                    cta.hdmi_forum()
+                        .and_then(|hdmi_forum| hdmi_forum.max_tmds_character_rate_mhz),
+                ]
+            })
+            .flatten()
+            .max()
+    }
+
+    /// VRR support the EDID advertises: AMD's FreeSync block first, then the
+    /// HDMI Forum VRR range, then continuous-frequency range limits spanning
+    /// more than 10Hz, which DisplayPort Adaptive-Sync panels report.
+    pub fn vrr(&self) -> Option<VrrCapability> {
+        let freesync = self.cta_extensions().find_map(|cta| cta.freesync());
+        if let Some(freesync) = freesync.filter(|f| f.max_hz > f.min_hz) {
+            return Some(VrrCapability {
+                range: Some((freesync.min_hz as u32, freesync.max_hz as u32)),
+                source: VrrSource::FreeSync,
+            });
+        }
+
+        let hdmi_vrr = self
+            .cta_extensions()
+            .find_map(|cta| cta.hdmi_forum().and_then(|hdmi_forum| hdmi_forum.vrr_hz));
+        if let Some((min, max)) = hdmi_vrr {
+            // A VRRmax of 0 means the maximum is the rate of the current mode
+            let max = if max == 0 {
+                self.range_limits().map_or(0, |l| l.max_vertical_hz)
+            } else {
+                max
+            };
+            return Some(VrrCapability {
+                range: (max > min).then_some((min as u32, max as u32)),
+                source: VrrSource::HdmiVrr,
+            });
+        }
+
+        self.range_limits()
+            .filter(|limits| {
+                self.digital
+                    && self.continuous_frequency
+                    && limits.max_vertical_hz > limits.min_vertical_hz + 10
+            })
+            .map(|limits| VrrCapability {
+                range: Some((limits.min_vertical_hz as u32, limits.max_vertical_hz as u32)),
+                source: VrrSource::AdaptiveSync,
+            })
+    }
+
+    pub fn monitor_name(&self) -> Option<&str> {
+        self.descriptors.iter().find_map(|d| match d {
+            Descriptor::MonitorName(name) if !name.is_empty() => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn serial_string(&self) -> Option<&str> {
+        self.descriptors.iter().find_map(|d| match d {
+            Descriptor::SerialNumber(serial) if !serial.is_empty() => Some(serial.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn range_limits(&self) -> Option<&RangeLimits> {
+        self.descriptors.iter().find_map(|d| match d {
+            Descriptor::RangeLimits(limits) => Some(limits),
+            _ => None,
+        })
+    }
+
+    pub fn detailed_timings(&self) -> impl Iterator<Item = &DetailedTiming> {
+        self.descriptors.iter().filter_map(|d| match d {
+            Descriptor::DetailedTiming(timing) => Some(timing),
+            _ => None,
+        })
+    }
+
+    /// The monitor name descriptor, falling back to the alphanumeric strings
+    /// laptop panels use, then to the PNP ID and product code.
+    pub fn description(&self) -> String {
+        if let Some(name) = self.monitor_name() {
+            return name.to_string();
+        }
+        let strings: Vec<&str> = self
+            .descriptors
+            .iter()
+            .filter_map(|d| match d {
+                Descriptor::Text(text) if !text.is_empty() => Some(text.as_str()),
+                _ => None,
+            })
+            .collect();
+        if !strings.is_empty() {
+            return strings.join(" ");
+        }
+        format!("{} {:04X}", self.manufacturer, self.product_code)
+    }
+
+    /// Manufacturer, product code and serial. The serial comes from the
+    /// serial number descriptor, else the numeric serial when it is set.
+    pub fn identity(&self) -> MonitorIdentity {
+        let serial = self
+            .serial_string()
+            .map(str::to_string)
+            .or_else(|| (self.serial_number != 0).then(|| self.serial_number.to_string()));
+        MonitorIdentity {
+            manufacturer: self.manufacturer.clone(),
+            product: format!("{:04X}", self.product_code),
+            serial,
+        }
+    }
+
+    /// The detailed timings of the base block, then those of the extensions,
+    /// then standard, established and CTA short video descriptor timings.
+    /// Only the first detailed timing of the base block is marked preferred.
+    pub fn modes(&self) -> Vec<DisplayMode> {
+        let mut modes: Vec<DisplayMode> = self
+            .detailed_timings()
+            .filter_map(DetailedTiming::to_display_mode)
+            .enumerate()
+            .map(|(index, mode)| DisplayMode {
+                preferred: index == 0,
+                ..mode
+            })
+            .collect();
+        let extension_timings = self
+            .cta_extensions()
+            .flat_map(|cta| cta.detailed_timings.iter())
+            .chain(
+                self.displayid_extensions()
+                    .flat_map(|d| d.timings().map(|t| &t.timing)),
+            );
+        modes.extend(extension_timings.filter_map(DetailedTiming::to_display_mode));
+        modes.extend(self.standard_timings.iter().map(|timing| {
+            DisplayMode::new(
+                timing.width,
+                timing.height,
+                RefreshRate::from_hz(timing.refresh_hz),
+            )
+        }));
+        modes.extend(self.established_timings.iter().copied());
+        modes.extend(
+            self.cta_extensions()
+                .flat_map(|cta| cta.short_video_descriptors())
+                .filter_map(ShortVideoDescriptor::to_display_mode),
+        );
+        modes
+    }
+
+    /// Spec violations and inconsistencies, one sentence each. Blocks are
+    /// numbered from 0 for the base block.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        if !self.checksum_valid {
+            warnings.push("Block 0 (base) has an invalid checksum".to_string());
+        }
+        if self.version.0 != 1 || self.version.1 > 4 {
+            warnings.push(format!(
+                "Unknown EDID version {}.{}",
+                self.version.0, self.version.1
+            ));
+        }
+        if !self.manufacturer.chars().all(|c| c.is_ascii_uppercase()) {
+            warnings.push(format!(
+                "Manufacturer ID {:?} is not three letters A-Z",
+                self.manufacturer
+            ));
+        }
+        if let Some(week) = self.manufacture_date.week.filter(|week| *week > 54) {
+            warnings.push(format!("Manufacture week {} is out of range (1-54)", week));
+        }
+        if self.version >= (1, 3) {
+            if !matches!(
+                self.descriptors.first(),
+                Some(Descriptor::DetailedTiming(_))
+            ) {
+                warnings.push(
+                    "The first descriptor is not a detailed timing, which EDID 1.3 and later require"
+                        .to_string(),
+                );
+            }
+            if self.version == (1, 3) && self.monitor_name().is_none() {
+                warnings.push("EDID 1.3 requires a monitor name descriptor".to_string());
+            }
+            if self.version == (1, 3) && self.range_limits().is_none() {
+                warnings.push("EDID 1.3 requires a range limits descriptor".to_string());
+            }
+        }
+        if let Some(limits) = self.range_limits() {
+            if limits.min_vertical_hz > limits.max_vertical_hz {
+                warnings.push(format!(
+                    "Range limits give a minimum vertical rate ({}Hz) above the maximum ({}Hz)",
+                    limits.min_vertical_hz, limits.max_vertical_hz
+                ));
+            }
+            if limits.min_horizontal_khz > limits.max_horizontal_khz {
+                warnings.push(format!(
+                    "Range limits give a minimum horizontal rate ({}kHz) above the maximum ({}kHz)",
+                    limits.min_horizontal_khz, limits.max_horizontal_khz
+                ));
+            }
+        }
+        if self.extensions.len() < self.extension_count as usize {
+            warnings.push(format!(
+                "The base block announces {} extension blocks but only {} are present",
+                self.extension_count,
+                self.extensions.len()
+            ));
+        }
+
+        for (index, block) in self.extensions.iter().enumerate() {
+            let number = index + 1;
+            if !block.checksum_valid {
+                warnings.push(format!("Block {} has an invalid checksum", number));
+            }
+            match &block.contents {
+                Extension::Cta(cta) => {
+                    for svd in cta.short_video_descriptors() {
+                        if svd.to_display_mode().is_none() {
+                            warnings
+                                .push(format!("Block {} lists reserved VIC {}", number, svd.vic));
+                        }
+                    }
+                }
+                Extension::DisplayId(displayid) => {
+                    if !displayid.checksum_valid {
+                        warnings.push(format!(
+                            "Block {} has an invalid DisplayID section checksum",
+                            number
+                        ));
+                    }
+                }
+                Extension::Other { .. } => {}
+            }
+        }
+        warnings
+    }
+}
+
+impl ExtensionBlock {
+    fn parse(block: &[u8]) -> Self {
+        let contents = match block[0] {
+            cta::TAG => Extension::Cta(CtaExtension::parse(block)),
+            displayid::TAG => Extension::DisplayId(DisplayIdExtension::parse(block)),
+            tag => Extension::Other { tag },
+        };
+        Self {
+            contents,
+            checksum_valid: checksum(block) == 0,
+        }
+    }
+}
+
+impl StandardTiming {
+    fn parse(bytes: &[u8]) -> Option<Self> {
+        // 0x0101 marks an unused slot
+        if bytes == [0x01, 0x01] || bytes[0] == 0 {
+            return None;
+        }
+        let width = (bytes[0] as u32 + 31) * 8;
+        let height = match bytes[1] >> 6 {
+            0 => width * 10 / 16,
+            1 => width * 3 / 4,
+            2 => width * 4 / 5,
+            _ => width * 9 / 16,
+        };
+        Some(Self {
+            width,
+            height,
+            refresh_hz: (bytes[1] & 0x3F) as u32 + 60,
+        })
+    }
+}
+
+impl Descriptor {
+    fn parse(bytes: &[u8]) -> Self {
+        if bytes[0] != 0 || bytes[1] != 0 {
+            return Descriptor::DetailedTiming(DetailedTiming::parse(bytes));
+        }
+        match bytes[3] {
+            0xFC => Descriptor::MonitorName(descriptor_text(bytes)),
+            0xFF => Descriptor::SerialNumber(descriptor_text(bytes)),
+            0xFE => Descriptor::Text(descriptor_text(bytes)),
+            0xFD => Descriptor::RangeLimits(RangeLimits::parse(bytes)),
+            tag => Descriptor::Other { tag },
+        }
+    }
+}
+
+impl DetailedTiming {
+    /// Parses an 18-byte detailed timing descriptor, as found in the base
+    /// block and in CTA-861 extensions.
+    pub fn parse(bytes: &[u8]) -> Self {
+        let high = |byte: u8, shift: u32| ((byte as u32) >> shift & 0x0F) << 8;
+        Self {
+            pixel_clock_khz: u16::from_le_bytes([bytes[0], bytes[1]]) as u32 * 10,
+            h_active: bytes[2] as u32 | high(bytes[4], 4),
+            h_blank: bytes[3] as u32 | high(bytes[4], 0),
+            v_active: bytes[5] as u32 | high(bytes[7], 4),
+            v_blank: bytes[6] as u32 | high(bytes[7], 0),
+            h_sync_offset: bytes[8] as u32 | ((bytes[11] as u32 >> 6) & 0x03) << 8,
+            h_sync_width: bytes[9] as u32 | ((bytes[11] as u32 >> 4) & 0x03) << 8,
+            v_sync_offset: (bytes[10] as u32 >> 4) | ((bytes[11] as u32 >> 2) & 0x03) << 4,
+            v_sync_width: (bytes[10] as u32 & 0x0F) | (bytes[11] as u32 & 0x03) << 4,
+            h_image_mm: bytes[12] as u32 | high(bytes[14], 4),
+            v_image_mm: bytes[13] as u32 | high(bytes[14], 0),
+            interlaced: bytes[17] & 0x80 != 0,
+        }
+    }
+
+    pub fn refresh_rate(&self) -> Option<RefreshRate> {
+        let htotal = (self.h_active + self.h_blank) as u64;
+        let vtotal = (self.v_active + self.v_blank) as u64;
+        (self.pixel_clock_khz != 0 && htotal * vtotal != 0)
+            .then(|| RefreshRate::from_timing(self.pixel_clock_khz as u64 * 1000, htotal, vtotal))
+    }
+
+    /// The mode this timing drives; interlaced timings count both fields.
+    pub fn to_display_mode(&self) -> Option<DisplayMode> {
+        let height = if self.interlaced {
+            self.v_active * 2
+        } else {
+            self.v_active
+        };
+        Some(DisplayMode {
+            interlaced: self.interlaced,
+            ..DisplayMode::new(self.h_active, height, self.refresh_rate()?)
+        })
+    }
+}
+
+impl RangeLimits {
+    fn parse(bytes: &[u8]) -> Self {
+        // EDID 1.4 adds 255 to a limit when its offset flag is set
+        let offset = |set: bool| if set { 255 } else { 0 };
+        let flags = bytes[4];
+        Self {
+            min_vertical_hz: bytes[5] as u16 + offset(flags & 0x03 == 0x03),
+            max_vertical_hz: bytes[6] as u16 + offset(flags & 0x02 != 0),
+            min_horizontal_khz: bytes[7] as u16 + offset(flags & 0x0C == 0x0C),
+            max_horizontal_khz: bytes[8] as u16 + offset(flags & 0x08 != 0),
+            max_pixel_clock_mhz: (bytes[9] != 0 && bytes[9] != 0xFF)
+                .then_some(bytes[9] as u32 * 10),
+        }
+    }
+}
+
+fn parse_established_timings(block: &[u8]) -> Vec<DisplayMode> {
+    let bits = u32::from_be_bytes([0, block[35], block[36], block[37]]);
+    ESTABLISHED_TIMINGS
+        .iter()
+        .enumerate()
+        .filter(|(index, _)| {
+            let bit = if *index < 16 { 23 - index } else { 7 };
+            bits & (1 << bit) != 0
+        })
+        .map(|(_, &(width, height, rate))| {
+            DisplayMode::new(width, height, RefreshRate::from_hz(rate))
+        })
+        .collect()
+}
+
+/// Text of a display descriptor, up to the first line feed.
+fn descriptor_text(bytes: &[u8]) -> String {
+    let text = String::from_utf8_lossy(&bytes[5..18]);
+    text.split('\n')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_string()
+}
+
+/// Sum of the block's bytes modulo 256; 0 for a valid block.
+pub fn checksum(block: &[u8]) -> u8 {
+    block.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DELL: &[u8] = include_bytes!("../../fixtures/edid/synthetic/dell-s2721dgf.bin");
+    const ASUS: &[u8] = include_bytes!("../../fixtures/edid/synthetic/asus-vg279qm.bin");
+    const SAMSUNG: &[u8] = include_bytes!("../../fixtures/edid/synthetic/samsung-qn90-tv.bin");
+    const BOE: &[u8] = include_bytes!("../../fixtures/edid/synthetic/boe-nv156fhm-n61.bin");
+
+    fn rates(modes: &[DisplayMode], width: u32, height: u32) -> Vec<String> {
+        modes
+            .iter()
+            .filter(|mode| mode.width == width && mode.height == height)
+            .map(|mode| mode.refresh_rate.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn every_fixture_decodes_without_warnings() {
+        for (name, bytes) in [
+            ("dell", DELL),
+            ("asus", ASUS),
+            ("samsung", SAMSUNG),
+            ("boe", BOE),
+        ] {
+            let edid = Edid::parse(bytes).unwrap();
+            assert!(edid.checksum_valid, "{}", name);
+            assert_eq!(edid.extensions.len(), edid.extension_count as usize);
+            assert!(edid.extensions.iter().all(|block| block.checksum_valid));
+            assert_eq!(edid.warnings(), Vec::<String>::new(), "{}", name);
+        }
+    }
+
+    /// Captured EDIDs come from monitors in the wild, whose quirks may
+    /// warn, but they must decode and be listed with their source.
+    #[test]
+    fn every_captured_edid_decodes() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/edid/captured");
+        let sources = std::fs::read_to_string(dir.join("README.md")).unwrap();
+        for entry in std::fs::read_dir(&dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some("bin") {
+                continue;
+            }
+            let name = path.file_name().unwrap().to_string_lossy().into_owned();
+            assert!(
+                sources.contains(&format!("`{}`", name)),
+                "{} has no source in captured/README.md",
+                name
+            );
+            let edid = Edid::parse(&std::fs::read(&path).unwrap()).unwrap();
+            assert!(edid.checksum_valid, "{}", name);
+            assert!(edid.extensions.iter().all(|block| block.checksum_valid));
+            assert!(!edid.description().is_empty(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn dell_identity_range_limits_and_cta_timings() {
+        let edid = Edid::parse(DELL).unwrap();
+        assert_eq!(edid.version, (1, 4));
+        assert_eq!(edid.manufacturer, "DEL");
+        assert_eq!(edid.product_code, 0xA1A5);
+        assert_eq!(edid.monitor_name(), Some("DELL S2721DGF"));
+        assert_eq!(edid.serial_string(), Some("7XKJ123"));
+        assert_eq!(edid.identity().to_string(), "DEL-A1A5-7XKJ123");
+        assert_eq!(edid.manufacture_date.week, Some(34));
+        assert_eq!(edid.manufacture_date.year, 2022);
+        assert_eq!(edid.screen_size_cm, Some((60, 34)));
+
+        let limits = edid.range_limits().unwrap();
+        assert_eq!((limits.min_vertical_hz, limits.max_vertical_hz), (48, 165));
+        assert_eq!(limits.max_pixel_clock_mhz, Some(660));
+
+        let cta = edid.cta_extensions().next().unwrap();
+        assert_eq!(cta.revision, 3);
+        let freesync = cta.freesync().unwrap();
+        assert_eq!((freesync.min_hz, freesync.max_hz), (48, 165));
+        let vics: Vec<(u8, bool)> = cta
+            .short_video_descriptors()
+            .map(|svd| (svd.vic, svd.native))
+            .collect();
+        assert_eq!(vics, [(16, true), (4, false), (2, false)]);
+
+        let modes = edid.modes();
+        assert!(modes[0].preferred);
+        assert_eq!(modes[0].to_string(), "2560x1440@165");
+        assert_eq!(rates(&modes, 2560, 1440), ["165", "59.951", "144"]);
+    }
+
+    #[test]
+    fn asus_displayid_timings() {
+        let edid = Edid::parse(ASUS).unwrap();
+        assert_eq!(edid.monitor_name(), Some("VG279QM"));
+        assert_eq!(edid.serial_string(), Some("L9LMQS012345"));
+        assert_eq!(edid.range_limits().unwrap().max_vertical_hz, 240);
+
+        let displayid = edid.displayid_extensions().next().unwrap();
+        assert!(displayid.checksum_valid);
+        let timings: Vec<String> = displayid.modes().iter().map(ToString::to_string).collect();
+        assert_eq!(timings, ["1920x1080@240", "1920x1080@165"]);
+
+        let mut modes = rates(&edid.modes(), 1920, 1080);
+        modes.dedup();
+        assert_eq!(modes, ["60", "144", "240", "165", "120", "60"]);
+        assert_eq!(edid.vrr().unwrap().range, Some((48, 240)));
+    }
+
+    #[test]
+    fn samsung_hdmi_blocks_and_high_vics() {
+        let edid = Edid::parse(SAMSUNG).unwrap();
+        assert_eq!(edid.monitor_name(), Some("SAMSUNG"));
+        assert_eq!(edid.serial_string(), None);
+        assert_eq!(edid.identity().serial.as_deref(), Some("16780800"));
+        assert_eq!(edid.range_limits().unwrap().min_vertical_hz, 24);
+
+        let cta = edid.cta_extensions().next().unwrap();
+        assert_eq!(cta.hdmi().unwrap().physical_address, [1, 0, 0, 0]);
+        assert_eq!(cta.hdmi_forum().unwrap().vrr_hz, Some((24, 120)));
+        assert_eq!(edid.max_tmds_mhz(), Some(600));
+        let first = cta.short_video_descriptors().next().unwrap();
+        assert_eq!((first.vic, first.native), (97, false));
+        assert_eq!(first.to_display_mode().unwrap().to_string(), "3840x2160@60");
+
+        let modes = edid.modes();
+        assert_eq!(rates(&modes, 3840, 2160), ["60", "60", "50", "30", "25"]);
+        // NTSC timings keep their exact rate next to the whole one
+        assert!(rates(&modes, 1920, 1080).contains(&"59.939".to_string()));
+        assert!(rates(&modes, 1920, 1080).contains(&"119.879".to_string()));
+    }
+
+    #[test]
+    fn laptop_panel_is_named_by_its_strings() {
+        let edid = Edid::parse(BOE).unwrap();
+        assert_eq!(edid.monitor_name(), None);
+        assert_eq!(edid.description(), "BOE HF NV156FHM-N61");
+        assert_eq!(edid.identity().serial, None);
+        assert!(edid.range_limits().is_none());
+        assert_eq!(rates(&edid.modes(), 1920, 1080), ["60", "48"]);
+    }
+
+    #[test]
+    fn bad_checksums_are_flagged() {
+        let mut bytes = DELL.to_vec();
+        bytes[20] ^= 0x01;
+        bytes[BLOCK_SIZE + 10] ^= 0x01;
+        let edid = Edid::parse(&bytes).unwrap();
+        assert!(!edid.checksum_valid);
+        assert!(!edid.extensions[0].checksum_valid);
+        assert_eq!(
+            edid.warnings(),
+            [
+                "Block 0 (base) has an invalid checksum",
+                "Block 1 has an invalid checksum"
+            ]
+        );
+    }
+
+    #[test]
+    fn truncated_input_is_rejected() {
+        assert!(Edid::parse(&DELL[..100]).is_err());
+        assert!(Edid::parse(&[]).is_err());
+
+        let mut bytes = DELL.to_vec();
+        bytes[0] = 0x01;
+        assert!(Edid::parse(&bytes).is_err());
+
+        // A missing extension block is a warning, not a parse failure
+        let edid = Edid::parse(&ASUS[..2 * BLOCK_SIZE + 10]).unwrap();
+        assert_eq!(edid.extensions.len(), 1);
+        assert!(edid.warnings().contains(
+            &"The base block announces 2 extension blocks but only 1 are present".to_string()
+        ));
+    }
+}
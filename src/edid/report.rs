@@ -0,0 +1,316 @@
+use super::cta::{CtaExtension, DataBlock};
+use super::displayid::{DisplayIdBlock, DisplayIdExtension};
+use super::{Descriptor, DetailedTiming, Edid, Extension, RangeLimits, BLOCK_SIZE};
+use anyhow::Result;
+use std::fmt::Write;
+
+/// A human-readable decoding of `bytes`, in the spirit of `edid-decode`:
+/// every block with its checksum, every descriptor and data block, then the
+/// warnings.
+pub fn report(bytes: &[u8]) -> Result<String> {
+    let edid = Edid::parse(bytes)?;
+    let mut out = String::new();
+
+    writeln!(
+        out,
+        "EDID {}.{}, {} bytes ({} extension blocks announced)",
+        edid.version.0,
+        edid.version.1,
+        bytes.len(),
+        edid.extension_count
+    )?;
+    writeln!(out)?;
+    write_base_block(&mut out, &edid, &bytes[..BLOCK_SIZE])?;
+
+    for (index, block) in edid.extensions.iter().enumerate() {
+        let raw = &bytes[(index + 1) * BLOCK_SIZE..(index + 2) * BLOCK_SIZE];
+        writeln!(out)?;
+        match &block.contents {
+            Extension::Cta(cta) => {
+                writeln!(
+                    out,
+                    "Block {}: CTA-861 extension, revision {}",
+                    index + 1,
+                    cta.revision
+                )?;
+                write_checksum(&mut out, raw, block.checksum_valid)?;
+                write_cta(&mut out, cta)?;
+            }
+            Extension::DisplayId(displayid) => {
+                writeln!(
+                    out,
+                    "Block {}: DisplayID {}.{} extension",
+                    index + 1,
+                    displayid.version >> 4,
+                    displayid.version & 0x0F
+                )?;
+                write_checksum(&mut out, raw, block.checksum_valid)?;
+                write_displayid(&mut out, displayid)?;
+            }
+            Extension::Other { tag } => {
+                writeln!(
+                    out,
+                    "Block {}: extension with tag 0x{:02X} (not decoded)",
+                    index + 1,
+                    tag
+                )?;
+                write_checksum(&mut out, raw, block.checksum_valid)?;
+            }
+        }
+    }
+
+    let mut warnings = edid.warnings();
+    let trailing = bytes.len() % BLOCK_SIZE;
+    if trailing != 0 {
+        warnings.push(format!(
+            "{} trailing bytes after the last whole block are ignored",
+            trailing
+        ));
+    }
+    let extra_blocks = (bytes.len() / BLOCK_SIZE).saturating_sub(1 + edid.extensions.len());
+    if extra_blocks != 0 {
+        warnings.push(format!(
+            "{} blocks after the announced extensions are ignored",
+            extra_blocks
+        ));
+    }
+
+    writeln!(out)?;
+    if warnings.is_empty() {
+        writeln!(out, "No warnings")?;
+    } else {
+        writeln!(out, "Warnings:")?;
+        for warning in &warnings {
+            writeln!(out, "  - {}", warning)?;
+        }
+    }
+    Ok(out)
+}
+
+fn write_base_block(out: &mut String, edid: &Edid, raw: &[u8]) -> Result<()> {
+    writeln!(out, "Block 0: base block")?;
+    write_checksum(out, raw, edid.checksum_valid)?;
+    writeln!(out, "  Manufacturer: {}", edid.manufacturer)?;
+    writeln!(out, "  Product code: 0x{:04X}", edid.product_code)?;
+    writeln!(out, "  Serial number: {}", edid.serial_number)?;
+    let date = &edid.manufacture_date;
+    match date.week {
+        _ if date.model_year => writeln!(out, "  Model year: {}", date.year)?,
+        Some(week) => writeln!(out, "  Manufactured: week {} of {}", week, date.year)?,
+        None => writeln!(out, "  Manufactured: {}", date.year)?,
+    }
+    writeln!(
+        out,
+        "  Input: {}",
+        if edid.digital { "digital" } else { "analog" }
+    )?;
+    match edid.screen_size_cm {
+        Some((width, height)) => writeln!(out, "  Screen size: {}x{} cm", width, height)?,
+        None => writeln!(out, "  Screen size: not given")?,
+    }
+
+    if !edid.established_timings.is_empty() {
+        writeln!(out, "  Established timings:")?;
+        for mode in &edid.established_timings {
+            writeln!(out, "    {}", mode)?;
+        }
+    }
+    if !edid.standard_timings.is_empty() {
+        writeln!(out, "  Standard timings:")?;
+        for timing in &edid.standard_timings {
+            writeln!(
+                out,
+                "    {}x{}@{}",
+                timing.width, timing.height, timing.refresh_hz
+            )?;
+        }
+    }
+
+    writeln!(out, "  Descriptors:")?;
+    for (index, descriptor) in edid.descriptors.iter().enumerate() {
+        let text = match descriptor {
+            Descriptor::DetailedTiming(timing) => {
+                let preferred = if index == 0 { " (preferred)" } else { "" };
+                format!("Detailed timing{}: {}", preferred, format_timing(timing))
+            }
+            Descriptor::MonitorName(name) => format!("Monitor name: {}", name),
+            Descriptor::SerialNumber(serial) => format!("Serial number: {}", serial),
+            Descriptor::Text(text) => format!("Text: {}", text),
+            Descriptor::RangeLimits(limits) => format!("Range limits: {}", format_limits(limits)),
+            Descriptor::Other { tag } => format!("Display descriptor with tag 0x{:02X}", tag),
+        };
+        writeln!(out, "    {}. {}", index + 1, text)?;
+    }
+    Ok(())
+}
+
+fn write_cta(out: &mut String, cta: &CtaExtension) -> Result<()> {
+    let flags: Vec<&str> = [
+        (cta.underscan, "underscan"),
+        (cta.basic_audio, "basic audio"),
+        (cta.ycbcr444, "YCbCr 4:4:4"),
+        (cta.ycbcr422, "YCbCr 4:2:2"),
+    ]
+    .iter()
+    .filter(|(set, _)| *set)
+    .map(|(_, name)| *name)
+    .collect();
+    writeln!(
+        out,
+        "  Supports: {}",
+        if flags.is_empty() {
+            "-".to_string()
+        } else {
+            flags.join(", ")
+        }
+    )?;
+    writeln!(out, "  Native detailed timings: {}", cta.native_dtds)?;
+
+    for block in &cta.data_blocks {
+        match block {
+            DataBlock::Audio => writeln!(out, "  Audio data block")?,
+            DataBlock::SpeakerAllocation => writeln!(out, "  Speaker allocation data block")?,
+            DataBlock::Video(svds) => {
+                writeln!(out, "  Video data block:")?;
+                for svd in svds {
+                    let mode = svd
+                        .to_display_mode()
+                        .map_or("reserved".to_string(), |mode| mode.to_string());
+                    let native = if svd.native { " (native)" } else { "" };
+                    writeln!(out, "    VIC {:>3}: {}{}", svd.vic, mode, native)?;
+                }
+            }
+            DataBlock::Hdmi(hdmi) => {
+                let [a, b, c, d] = hdmi.physical_address;
+                write!(
+                    out,
+                    "  HDMI vendor block: physical address {}.{}.{}.{}",
+                    a, b, c, d
+                )?;
+                if let Some(clock) = hdmi.max_tmds_clock_mhz {
+                    write!(out, ", max TMDS clock {} MHz", clock)?;
+                }
+                writeln!(out)?;
+            }
+            DataBlock::HdmiForum(hdmi_forum) => {
+                write!(
+                    out,
+                    "  HDMI Forum vendor block: version {}",
+                    hdmi_forum.version
+                )?;
+                if let Some(rate) = hdmi_forum.max_tmds_character_rate_mhz {
+                    write!(out, ", max TMDS character rate {} MHz", rate)?;
+                }
+                if hdmi_forum.scdc_present {
+                    write!(out, ", SCDC")?;
+                }
+                if hdmi_forum.max_frl_gbps != 0 {
+                    write!(out, ", FRL up to {} Gbps", hdmi_forum.max_frl_gbps)?;
+                }
+                if hdmi_forum.allm {
+                    write!(out, ", ALLM")?;
+                }
+                if let Some((min, max)) = hdmi_forum.vrr_hz {
+                    write!(out, ", VRR {}-{} Hz", min, max)?;
+                }
+                writeln!(out)?;
+            }
+            DataBlock::FreeSync(freesync) => writeln!(
+                out,
+                "  AMD FreeSync vendor block: version {}, {}-{} Hz",
+                freesync.version, freesync.min_hz, freesync.max_hz
+            )?,
+            DataBlock::VendorSpecific { oui } => {
+                writeln!(out, "  Vendor-specific data block, OUI {:06X}", oui)?
+            }
+            DataBlock::Extended { tag } => {
+                writeln!(out, "  Extended data block with tag 0x{:02X}", tag)?
+            }
+            DataBlock::Other { tag } => writeln!(out, "  Data block with tag {}", tag)?,
+        }
+    }
+
+    if !cta.detailed_timings.is_empty() {
+        writeln!(out, "  Detailed timings:")?;
+        for timing in &cta.detailed_timings {
+            writeln!(out, "    {}", format_timing(timing))?;
+        }
+    }
+    Ok(())
+}
+
+fn write_displayid(out: &mut String, displayid: &DisplayIdExtension) -> Result<()> {
+    writeln!(
+        out,
+        "  Section checksum: {}",
+        if displayid.checksum_valid {
+            "valid"
+        } else {
+            "INVALID"
+        }
+    )?;
+    for block in &displayid.data_blocks {
+        match block {
+            DisplayIdBlock::DetailedTimings(timings) => {
+                writeln!(out, "  Detailed timings:")?;
+                for timing in timings {
+                    let preferred = if timing.preferred { " (preferred)" } else { "" };
+                    writeln!(out, "    {}{}", format_timing(&timing.timing), preferred)?;
+                }
+            }
+            DisplayIdBlock::Other { tag } => writeln!(out, "  Data block with tag 0x{:02X}", tag)?,
+        }
+    }
+    Ok(())
+}
+
+fn write_checksum(out: &mut String, raw: &[u8], valid: bool) -> Result<()> {
+    writeln!(
+        out,
+        "  Checksum: 0x{:02X} ({})",
+        raw[BLOCK_SIZE - 1],
+        if valid { "valid" } else { "INVALID" }
+    )?;
+    Ok(())
+}
+
+/// E.g. `2560x1440@165Hz, 653.40 MHz, h 2560+8+32+40, v 1440+3+5+52, 597x336 mm`:
+/// active, front porch, sync and back porch for each direction.
+fn format_timing(timing: &DetailedTiming) -> String {
+    let porch = |blank: u32, offset: u32, width: u32| blank.saturating_sub(offset + width);
+    let mode = match timing.to_display_mode() {
+        Some(mode) => format!("{}Hz", mode),
+        None => "(no pixel clock)".to_string(),
+    };
+    let mut text = format!(
+        "{}, {:.2} MHz, h {}+{}+{}+{}, v {}+{}+{}+{}",
+        mode,
+        timing.pixel_clock_khz as f64 / 1000.0,
+        timing.h_active,
+        timing.h_sync_offset,
+        timing.h_sync_width,
+        porch(timing.h_blank, timing.h_sync_offset, timing.h_sync_width),
+        timing.v_active,
+        timing.v_sync_offset,
+        timing.v_sync_width,
+        porch(timing.v_blank, timing.v_sync_offset, timing.v_sync_width),
+    );
+    if timing.h_image_mm != 0 && timing.v_image_mm != 0 {
+        write!(text, ", {}x{} mm", timing.h_image_mm, timing.v_image_mm).unwrap();
+    }
+    text
+}
+
+fn format_limits(limits: &RangeLimits) -> String {
+    let mut text = format!(
+        "{}-{} Hz vertical, {}-{} kHz horizontal",
+        limits.min_vertical_hz,
+        limits.max_vertical_hz,
+        limits.min_horizontal_khz,
+        limits.max_horizontal_khz
+    );
+    if let Some(clock) = limits.max_pixel_clock_mhz {
+        write!(text, ", max pixel clock {} MHz", clock).unwrap();
+    }
+    text
+}
@@ -0,0 +1,221 @@
+use crate::refresh_rate::{format_rates, RatePolicy, RefreshRate};
+use serde::Serialize;
+use std::fmt;
+use std::io;
+
+/// Exit status for failures that fit none of the classes below.
+pub const EXIT_FAILURE: i32 = 1;
+/// Exit status for command-line mistakes, as clap uses it.
+pub const EXIT_USAGE: i32 = 2;
+
+/// How the CLI reports a failed command on stderr.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum ErrorFormat {
+    /// `Error: ` followed by the message
+    #[default]
+    Text,
+    /// One JSON object, see [`ErrorReport`]
+    Json,
+}
+
+/// The failures automation may want to tell apart. They travel inside
+/// `anyhow::Error` like every other error, possibly wrapped in context, and
+/// [`HertzRateError::find`] digs them out again.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HertzRateError {
+    /// No connected monitor matches the selector, or several do
+    MonitorNotFound(String),
+    /// The monitor has no mode with the requested rate, or none the policy
+    /// accepts
+    RateUnavailable {
+        monitor: String,
+        rate: RatePolicy,
+        /// Rates the monitor offers at its current resolution
+        available: Vec<RefreshRate>,
+    },
+    /// The mode doesn't exist or the platform calls it invalid, e.g.
+    /// `DISP_CHANGE_BADMODE`
+    BadMode(String),
+    /// The change only takes effect after a restart (`DISP_CHANGE_RESTART`)
+    RestartRequired,
+    /// The platform refused access, e.g. the registry or a D-Bus policy
+    PermissionDenied(String),
+    /// The backend can't do this at all
+    BackendUnsupported(String),
+    /// The driver or compositor refused a change it could have made
+    DriverRejected(String),
+}
+
+impl HertzRateError {
+    /// The typed error anywhere in `error`'s chain of causes.
+    pub fn find(error: &anyhow::Error) -> Option<&HertzRateError> {
+        error.chain().find_map(|cause| cause.downcast_ref())
+    }
+
+    /// Short machine-readable name, e.g. `rate_unavailable`.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            HertzRateError::MonitorNotFound(_) => "monitor_not_found",
+            HertzRateError::RateUnavailable { .. } => "rate_unavailable",
+            HertzRateError::BadMode(_) => "bad_mode",
+            HertzRateError::RestartRequired => "restart_required",
+            HertzRateError::PermissionDenied(_) => "permission_denied",
+            HertzRateError::BackendUnsupported(_) => "backend_unsupported",
+            HertzRateError::DriverRejected(_) => "driver_rejected",
+        }
+    }
+
+    /// Process exit status for this class of failure.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            HertzRateError::MonitorNotFound(_) => 3,
+            HertzRateError::RateUnavailable { .. } => 4,
+            HertzRateError::BadMode(_) => 5,
+            HertzRateError::DriverRejected(_) => 6,
+            HertzRateError::PermissionDenied(_) => 7,
+            HertzRateError::BackendUnsupported(_) => 8,
+            HertzRateError::RestartRequired => 9,
+        }
+    }
+}
+
+impl std::error::Error for HertzRateError {}
+
+impl fmt::Display for HertzRateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HertzRateError::MonitorNotFound(message)
+            | HertzRateError::BadMode(message)
+            | HertzRateError::PermissionDenied(message)
+            | HertzRateError::BackendUnsupported(message)
+            | HertzRateError::DriverRejected(message) => f.write_str(message),
+            HertzRateError::RateUnavailable {
+                monitor,
+                rate,
+                available,
+            } => match rate {
+                RatePolicy::Exact(_) => write!(
+                    f,
+                    "Refresh rate {} is not available for monitor {}. Available rates: {}",
+                    rate,
+                    monitor,
+                    format_rates(available)
+                ),
+                _ => write!(
+                    f,
+                    "No refresh rate of monitor {} fits '{}'. Available rates: {}",
+                    monitor,
+                    rate,
+                    format_rates(available)
+                ),
+            },
+            HertzRateError::RestartRequired => {
+                f.write_str("The change only takes effect after the computer is restarted")
+            }
+        }
+    }
+}
+
+/// The exit status for `error`: its class where it has one, a permission
+/// failure for denied file access, and [`EXIT_FAILURE`] otherwise.
+pub fn exit_code(error: &anyhow::Error) -> i32 {
+    if let Some(error) = HertzRateError::find(error) {
+        return error.exit_code();
+    }
+    let denied = error
+        .chain()
+        .filter_map(|cause| cause.downcast_ref::<io::Error>())
+        .any(|cause| cause.kind() == io::ErrorKind::PermissionDenied);
+    if denied {
+        HertzRateError::PermissionDenied(String::new()).exit_code()
+    } else {
+        EXIT_FAILURE
+    }
+}
+
+/// A failure in machine-readable form:
+///
+/// ```json
+/// {
+///   "error": {
+///     "kind": "rate_unavailable",
+///     "message": "Refresh rate 165Hz is not available for monitor DELL S2721DGF. ...",
+///     "exit_code": 4,
+///     "monitor": "DELL S2721DGF",
+///     "rate": "165",
+///     "available": ["60", "120", "144"]
+///   }
+/// }
+/// ```
+///
+/// `kind` is `error` for failures without a class. `monitor`, `rate` and
+/// `available` are only present for `rate_unavailable`.
+#[derive(Debug, Serialize)]
+pub struct ErrorReport {
+    pub error: ErrorDetails,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ErrorDetails {
+    pub kind: &'static str,
+    pub message: String,
+    pub exit_code: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub monitor: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate: Option<RatePolicy>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub available: Option<Vec<RefreshRate>>,
+}
+
+impl ErrorReport {
+    /// Describes `error`, which ends the process with `exit_code`.
+    pub fn new(error: &anyhow::Error, exit_code: i32) -> Self {
+        let typed = HertzRateError::find(error);
+        let (monitor, rate, available) = match typed {
+            Some(HertzRateError::RateUnavailable {
+                monitor,
+                rate,
+                available,
+            }) => (Some(monitor.clone()), Some(*rate), Some(available.clone())),
+            _ => (None, None, None),
+        };
+        ErrorReport {
+            error: ErrorDetails {
+                kind: typed.map_or("error", HertzRateError::kind),
+                message: format!("{:#}", error),
+                exit_code,
+                monitor,
+                rate,
+                available,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn denied_file_access_exits_as_a_permission_failure() {
+        let error = Err::<(), _>(io::Error::from(io::ErrorKind::PermissionDenied))
+            .context("Failed to write /etc/hertzrate/config.toml")
+            .unwrap_err();
+        assert_eq!(exit_code(&error), 7);
+
+        let error = Err::<(), _>(io::Error::from(io::ErrorKind::NotFound))
+            .context("Failed to read /etc/hertzrate/config.toml")
+            .unwrap_err();
+        assert_eq!(exit_code(&error), EXIT_FAILURE);
+    }
+
+    #[test]
+    fn classes_survive_added_context() {
+        let error = anyhow::Error::from(HertzRateError::DriverRejected("1x1@1".to_string()))
+            .context("Failed to set the mode");
+        assert_eq!(exit_code(&error), 6);
+        assert_eq!(ErrorReport::new(&error, 6).error.kind, "driver_rejected");
+    }
+}
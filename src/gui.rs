@@ -1,73 +1,494 @@
-use crate::monitor::Monitor;
+use crate::backend::{DisplayBackend, DisplayMode, Resolution};
+use crate::harmonize::{Harmony, Preference};
+use crate::monitor::{on_off, Monitor, MonitorId, DEFAULT_CONFIRM_TIMEOUT_SECS};
+use crate::refresh_rate::{format_rates, RatePolicy, RefreshRate};
+use crate::snapshot::{self, Snapshot};
 use anyhow::Result;
 use eframe::egui;
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// What a button on a monitor card asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CardAction {
+    Apply,
+    /// Validate the selected mode with the backend without applying it
+    Test,
+    /// Turn variable refresh rate on or off
+    SetVrr(bool),
+    /// Apply the rate a policy picks at the selected resolution, e.g. "Max"
+    SetRate(RatePolicy),
+}
+
+/// How much room a monitor card has; every layout shows the same controls.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CardLayout {
+    Standard,
+    Compact,
+    Grid,
+    Minimal,
+}
+
+impl CardLayout {
+    /// Prefix that keeps the widget ids of the layouts apart.
+    fn id(self) -> &'static str {
+        match self {
+            CardLayout::Standard => "standard",
+            CardLayout::Compact => "compact",
+            CardLayout::Grid => "grid",
+            CardLayout::Minimal => "minimal",
+        }
+    }
+
+    fn combo_width(self) -> f32 {
+        match self {
+            CardLayout::Standard => 120.0,
+            CardLayout::Compact => 80.0,
+            CardLayout::Grid => 70.0,
+            CardLayout::Minimal => 60.0,
+        }
+    }
+}
+
+/// What the monitor cards asked for during one frame, applied after the
+/// cards are drawn.
+#[derive(Default)]
+struct CardEdits {
+    actions: Vec<(MonitorId, CardAction)>,
+    rate_changes: Vec<(MonitorId, RefreshRate)>,
+    resolution_changes: Vec<(MonitorId, Resolution)>,
+}
+
+/// A change waiting to be confirmed; the previous settings are restored at
+/// `deadline` unless the user keeps it.
+struct PendingRevert {
+    /// What the countdown restores, e.g. "DELL S2721DGF will return to 60Hz"
+    summary: String,
+    previous: Snapshot,
+    deadline: Instant,
+}
+
+/// What a button in the snapshot list asked for.
+enum SnapshotAction {
+    Save,
+    Restore(String),
+}
 
 pub struct HertzRateApp {
+    backend: Box<dyn DisplayBackend>,
     monitors: Vec<Monitor>,
-    selected_rates: HashMap<usize, u32>,
+    selected_rates: HashMap<MonitorId, RefreshRate>,
+    selected_resolutions: HashMap<MonitorId, Resolution>,
+    harmonize_preference: Preference,
+    pending_revert: Option<PendingRevert>,
+    snapshot_names: Vec<String>,
+    new_snapshot_name: String,
     status_message: String,
     show_error: bool,
     error_message: String,
     last_refresh: std::time::Instant,
 }
 
-impl Default for HertzRateApp {
-    fn default() -> Self {
-        Self::new()
-    }
-}
-
 impl HertzRateApp {
-    pub fn new() -> Self {
+    pub fn new(backend: Box<dyn DisplayBackend>) -> Self {
         let mut app = Self {
+            backend,
             monitors: Vec::new(),
             selected_rates: HashMap::new(),
+            selected_resolutions: HashMap::new(),
+            harmonize_preference: Preference::default(),
+            pending_revert: None,
+            snapshot_names: Vec::new(),
+            new_snapshot_name: String::new(),
             status_message: String::new(),
             show_error: false,
             error_message: String::new(),
             last_refresh: std::time::Instant::now(),
         };
         app.refresh_monitors();
+        app.refresh_snapshots();
         app
     }
 
     fn refresh_monitors(&mut self) {
-        match Monitor::enumerate_monitors() {
+        self.load_monitors(true);
+    }
+
+    /// Re-enumerates the monitors. Selections are keyed by `MonitorId`, so
+    /// without `reset_selections` a pending choice stays with its monitor
+    /// even when the enumeration order changes.
+    fn load_monitors(&mut self, reset_selections: bool) {
+        match Monitor::enumerate_monitors(self.backend.as_ref()) {
             Ok(monitors) => {
-                // Initialize selected rates with current rates
-                for (index, monitor) in monitors.iter().enumerate() {
+                if reset_selections {
+                    self.selected_rates.clear();
+                    self.selected_resolutions.clear();
+                }
+                let connected = |id: &MonitorId| monitors.iter().any(|m| m.id == *id);
+                self.selected_rates.retain(|id, _| connected(id));
+                self.selected_resolutions.retain(|id, _| connected(id));
+
+                // Monitors without a selection start at their current mode
+                for monitor in &monitors {
                     self.selected_rates
-                        .insert(index, monitor.current_refresh_rate);
+                        .entry(monitor.id.clone())
+                        .or_insert(monitor.current_refresh_rate);
+                    self.selected_resolutions
+                        .entry(monitor.id.clone())
+                        .or_insert(monitor.current_mode.resolution());
                 }
                 self.monitors = monitors;
                 self.status_message = format!("Found {} monitor(s)", self.monitors.len());
                 self.show_error = false;
             }
             Err(e) => {
-                self.error_message = format!("Failed to enumerate monitors: {}", e);
+                self.error_message = format!("Failed to enumerate monitors: {:#}", e);
                 self.show_error = true;
                 self.monitors.clear();
             }
         }
     }
 
-    fn apply_rate_change(&mut self, monitor_index: usize) {
-        if let Some(&rate) = self.selected_rates.get(&monitor_index) {
-            if let Some(monitor) = self.monitors.get(monitor_index) {
-                match monitor.set_refresh_rate(rate) {
-                    Ok(()) => {
-                        self.status_message =
-                            format!("✓ Successfully set {} to {}Hz", monitor.description, rate);
+    fn monitor(&self, id: &MonitorId) -> Option<&Monitor> {
+        self.monitors.iter().find(|monitor| monitor.id == *id)
+    }
+
+    fn apply_rate_change(&mut self, id: &MonitorId) {
+        if let Some(&rate) = self.selected_rates.get(id) {
+            self.apply_rate(id, rate.into());
+        }
+    }
+
+    /// Switches the monitor to the rate `policy` picks at its selected
+    /// resolution.
+    fn apply_rate(&mut self, id: &MonitorId, policy: RatePolicy) {
+        let Some(monitor) = self.monitors.iter().find(|m| m.id == *id) else {
+            return;
+        };
+        let resolution = self.selected_resolution(monitor);
+        match monitor.set_mode(self.backend.as_ref(), resolution, Some(policy)) {
+            Ok(mode) => {
+                self.status_message =
+                    format!("✓ Successfully set {} to {}Hz", monitor.description, mode);
+                self.show_error = false;
+                if !mode.same_timing(&monitor.current_mode) {
+                    self.pending_revert = Some(PendingRevert {
+                        summary: format!(
+                            "{} will return to {}Hz",
+                            monitor.description, monitor.current_mode
+                        ),
+                        previous: Snapshot::from_monitors(
+                            self.backend.as_ref(),
+                            std::slice::from_ref(monitor),
+                        ),
+                        deadline: Instant::now()
+                            + Duration::from_secs(DEFAULT_CONFIRM_TIMEOUT_SECS),
+                    });
+                }
+                // Refresh monitors to get updated current rates
+                self.refresh_monitors();
+            }
+            Err(e) => {
+                self.error_message = format!(
+                    "Failed to set refresh rate for {}: {:#}",
+                    monitor.description, e
+                );
+                self.show_error = true;
+            }
+        }
+    }
+
+    /// Puts every monitor at its own highest rate at its current resolution,
+    /// with one countdown that restores all of them.
+    fn set_all_to_max(&mut self) {
+        let targets = self
+            .monitors
+            .iter()
+            .map(|monitor| monitor.resolve_refresh_rate(RatePolicy::Max))
+            .collect();
+        self.apply_modes(targets, "highest rate");
+    }
+
+    /// Puts the monitors on the common rates `harmonize_preference` picks.
+    fn harmonize(&mut self) {
+        let targets = match Harmony::new(&self.monitors).choose(self.harmonize_preference) {
+            Ok(choice) => choice
+                .targets
+                .into_iter()
+                .map(|(_, mode)| Ok(mode))
+                .collect(),
+            Err(e) => {
+                self.error_message = format!("{:#}", e);
+                self.show_error = true;
+                return;
+            }
+        };
+        self.apply_modes(targets, "common rate");
+    }
+
+    /// Switches every monitor to its entry in `targets`, in monitor order,
+    /// with one countdown that restores all of them. `what` names the
+    /// target in the status message, e.g. "highest rate".
+    fn apply_modes(&mut self, targets: Vec<Result<DisplayMode>>, what: &str) {
+        let backend = self.backend.as_ref();
+        let mut changed = Vec::new();
+        let mut errors = Vec::new();
+        for (monitor, mode) in self.monitors.iter().zip(targets) {
+            match mode {
+                Ok(mode) if mode.same_timing(&monitor.current_mode) => {}
+                Ok(mode) => match monitor.apply_mode(backend, &mode) {
+                    Ok(()) => changed.push(monitor.clone()),
+                    Err(e) => errors.push(format!("{:#}", e)),
+                },
+                Err(e) => errors.push(format!("{:#}", e)),
+            }
+        }
+
+        if errors.is_empty() {
+            self.status_message = if changed.is_empty() {
+                format!("✓ Every monitor already runs at its {}", what)
+            } else {
+                format!("✓ Set {} monitor(s) to their {}", changed.len(), what)
+            };
+            self.show_error = false;
+        } else {
+            self.error_message = errors.join("; ");
+            self.show_error = true;
+        }
+        if !changed.is_empty() {
+            self.pending_revert = Some(PendingRevert {
+                summary: format!(
+                    "{} monitor(s) will return to their previous rate",
+                    changed.len()
+                ),
+                previous: Snapshot::from_monitors(backend, &changed),
+                deadline: Instant::now() + Duration::from_secs(DEFAULT_CONFIRM_TIMEOUT_SECS),
+            });
+        }
+        self.refresh_monitors();
+    }
+
+    fn set_vrr(&mut self, id: &MonitorId, enabled: bool) {
+        let Some(monitor) = self.monitor(id) else {
+            return;
+        };
+        match monitor.set_vrr(self.backend.as_ref(), enabled) {
+            Ok(()) => {
+                self.status_message = format!(
+                    "✓ Turned VRR {} for {}",
+                    on_off(enabled),
+                    monitor.description
+                );
+                self.show_error = false;
+                self.load_monitors(false);
+            }
+            Err(e) => {
+                self.error_message = format!("{:#}", e);
+                self.show_error = true;
+            }
+        }
+    }
+
+    fn revert_pending_change(&mut self) {
+        let Some(pending) = self.pending_revert.take() else {
+            return;
+        };
+        match pending.previous.restore(self.backend.as_ref()) {
+            Ok((_, errors)) if errors.is_empty() => {
+                self.status_message = "Restored the previous settings".to_string();
+                self.show_error = false;
+            }
+            Ok((_, errors)) => {
+                self.error_message = format!(
+                    "Failed to restore the previous settings: {}",
+                    errors
+                        .iter()
+                        .map(|e| format!("{:#}", e))
+                        .collect::<Vec<_>>()
+                        .join("; ")
+                );
+                self.show_error = true;
+            }
+            Err(e) => {
+                self.error_message = format!("Failed to restore the previous settings: {:#}", e);
+                self.show_error = true;
+            }
+        }
+        self.refresh_monitors();
+    }
+
+    fn refresh_snapshots(&mut self) {
+        match snapshot::list() {
+            Ok(names) => self.snapshot_names = names,
+            Err(e) => {
+                self.error_message = format!("Failed to list snapshots: {:#}", e);
+                self.show_error = true;
+            }
+        }
+    }
+
+    fn save_snapshot(&mut self) {
+        let name = self.new_snapshot_name.trim().to_string();
+        let result =
+            Snapshot::from_monitors(self.backend.as_ref(), &self.monitors).save(&name, false);
+        match result {
+            Ok(_) => {
+                self.status_message = format!("✓ Saved snapshot '{}'", name);
+                self.show_error = false;
+                self.new_snapshot_name.clear();
+            }
+            Err(e) => {
+                self.error_message = format!("Failed to save snapshot: {:#}", e);
+                self.show_error = true;
+            }
+        }
+        self.refresh_snapshots();
+    }
+
+    fn restore_snapshot(&mut self, name: &str) {
+        let previous = Snapshot::from_monitors(self.backend.as_ref(), &self.monitors);
+        let result =
+            Snapshot::load(name).and_then(|snapshot| snapshot.restore(self.backend.as_ref()));
+        match result {
+            Ok((plan, errors)) => {
+                let mut skipped: Vec<String> = plan.missing.clone();
+                skipped.extend(plan.unavailable.iter().map(|(monitor, _)| monitor.clone()));
+                if errors.is_empty() {
+                    self.status_message = if plan.steps.is_empty() {
+                        format!("✓ Every monitor already matches '{}'", name)
+                    } else {
+                        format!("✓ Restored snapshot '{}'", name)
+                    };
+                    if !skipped.is_empty() {
+                        self.status_message += &format!(" (skipped {})", skipped.join(", "));
+                    }
+                    self.show_error = false;
+                } else {
+                    self.error_message = format!(
+                        "Failed to restore snapshot '{}': {}",
+                        name,
+                        errors
+                            .iter()
+                            .map(|e| format!("{:#}", e))
+                            .collect::<Vec<_>>()
+                            .join("; ")
+                    );
+                    self.show_error = true;
+                }
+                if plan.steps.len() > errors.len() {
+                    self.pending_revert = Some(PendingRevert {
+                        summary: "The previous settings will be restored".to_string(),
+                        previous,
+                        deadline: Instant::now()
+                            + Duration::from_secs(DEFAULT_CONFIRM_TIMEOUT_SECS),
+                    });
+                }
+            }
+            Err(e) => {
+                self.error_message = format!("Failed to restore snapshot '{}': {:#}", name, e);
+                self.show_error = true;
+            }
+        }
+        self.refresh_monitors();
+    }
+
+    /// Collapsible list of saved snapshots with a field to save a new one.
+    fn render_snapshots(&mut self, ui: &mut egui::Ui) -> Option<SnapshotAction> {
+        let mut action = None;
+        egui::CollapsingHeader::new(format!("📸 Snapshots ({})", self.snapshot_names.len()))
+            .id_source("snapshots")
+            .show(ui, |ui| {
+                ui.horizontal(|ui| {
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.new_snapshot_name)
+                            .hint_text("Snapshot name")
+                            .desired_width(160.0),
+                    );
+                    let can_save = !self.new_snapshot_name.trim().is_empty();
+                    if ui
+                        .add_enabled(can_save, egui::Button::new("💾 Save current"))
+                        .clicked()
+                    {
+                        action = Some(SnapshotAction::Save);
+                    }
+                });
+                if self.snapshot_names.is_empty() {
+                    ui.small("No snapshots saved yet");
+                }
+                for name in &self.snapshot_names {
+                    ui.horizontal(|ui| {
+                        ui.label(name);
+                        if ui.small_button("Restore").clicked() {
+                            action = Some(SnapshotAction::Restore(name.clone()));
+                        }
+                    });
+                }
+            });
+        action
+    }
+
+    /// The "Keep these settings?" countdown shown after a mode change.
+    fn render_confirm_dialog(&mut self, ctx: &egui::Context) {
+        let Some(pending) = &self.pending_revert else {
+            return;
+        };
+        let remaining = pending.deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            self.revert_pending_change();
+            return;
+        }
+
+        let mut keep = false;
+        let mut revert = false;
+        egui::Window::new("Keep these settings?")
+            .collapsible(false)
+            .resizable(false)
+            .anchor(egui::Align2::CENTER_CENTER, [0.0, 0.0])
+            .show(ctx, |ui| {
+                ui.label(format!(
+                    "{} in {}s.",
+                    pending.summary,
+                    remaining.as_secs() + 1
+                ));
+                ui.horizontal(|ui| {
+                    keep = ui.button("Keep").clicked();
+                    revert = ui.button("Revert now").clicked();
+                });
+            });
+
+        if keep {
+            self.pending_revert = None;
+            self.status_message = "✓ Keeping the new settings".to_string();
+        } else if revert {
+            self.revert_pending_change();
+        } else {
+            // Keep the countdown ticking without user input
+            ctx.request_repaint_after(Duration::from_millis(250));
+        }
+    }
+
+    fn test_rate_change(&mut self, id: &MonitorId) {
+        if let Some(&rate) = self.selected_rates.get(id) {
+            if let Some(monitor) = self.monitors.iter().find(|m| m.id == *id) {
+                let resolution = self.selected_resolution(monitor);
+                let result = monitor
+                    .resolve_mode(resolution, Some(rate.into()))
+                    .and_then(|mode| {
+                        monitor
+                            .test_mode(self.backend.as_ref(), &mode)
+                            .map(|_| mode)
+                    });
+                match result {
+                    Ok(mode) => {
+                        self.status_message = format!(
+                            "✓ {} would accept {}Hz (nothing was changed)",
+                            monitor.description, mode
+                        );
                         self.show_error = false;
-                        // Refresh monitors to get updated current rates
-                        self.refresh_monitors();
                     }
                     Err(e) => {
-                        self.error_message = format!(
-                            "Failed to set refresh rate for {}: {}",
-                            monitor.description, e
-                        );
+                        self.error_message =
+                            format!("Test failed for {}: {:#}", monitor.description, e);
                         self.show_error = true;
                     }
                 }
@@ -75,13 +496,19 @@ impl HertzRateApp {
         }
     }
 
+    fn selected_resolution(&self, monitor: &Monitor) -> Resolution {
+        self.selected_resolutions
+            .get(&monitor.id)
+            .copied()
+            .unwrap_or(monitor.current_mode.resolution())
+    }
+
     fn render_monitor_card_grid(
         &self,
         ui: &mut egui::Ui,
         index: usize,
         monitor: &Monitor,
-        actions: &mut Vec<usize>,
-        rate_changes: &mut Vec<(usize, u32)>,
+        edits: &mut CardEdits,
     ) {
         ui.group(|ui| {
             ui.set_min_width(200.0);
@@ -98,45 +525,12 @@ impl HertzRateApp {
                     ));
                     ui.separator();
                     ui.small(format!("{}Hz", monitor.current_refresh_rate));
+                    self.render_vrr_badge(ui, monitor);
                 });
 
                 ui.add_space(4.0);
 
-                // Compact controls
-                let mut selected_rate = self
-                    .selected_rates
-                    .get(&index)
-                    .copied()
-                    .unwrap_or(monitor.current_refresh_rate);
-
-                let mut rate_changed = false;
-                egui::ComboBox::from_id_source(format!("grid_combo_{}", index))
-                    .selected_text(format!("{}Hz", selected_rate))
-                    .width(70.0)
-                    .show_ui(ui, |ui| {
-                        for &rate in &monitor.available_refresh_rates {
-                            let text = if rate == monitor.current_refresh_rate {
-                                format!("{}Hz ✓", rate)
-                            } else {
-                                format!("{}Hz", rate)
-                            };
-
-                            if ui
-                                .selectable_value(&mut selected_rate, rate, text)
-                                .changed()
-                            {
-                                rate_changed = true;
-                            }
-                        }
-                    });
-
-                if rate_changed {
-                    rate_changes.push((index, selected_rate));
-                }
-
-                if ui.small_button("Apply").clicked() {
-                    actions.push(index);
-                }
+                self.render_mode_controls(ui, monitor, CardLayout::Grid, edits);
             });
         });
     }
@@ -146,41 +540,12 @@ impl HertzRateApp {
         ui: &mut egui::Ui,
         index: usize,
         monitor: &Monitor,
-        actions: &mut Vec<usize>,
-        rate_changes: &mut Vec<(usize, u32)>,
+        edits: &mut CardEdits,
     ) {
         ui.horizontal(|ui| {
             ui.label(format!("Monitor {}", index));
             ui.separator();
-
-            let mut selected_rate = self
-                .selected_rates
-                .get(&index)
-                .copied()
-                .unwrap_or(monitor.current_refresh_rate);
-
-            let mut rate_changed = false;
-            egui::ComboBox::from_id_source(format!("minimal_combo_{}", index))
-                .selected_text(format!("{}Hz", selected_rate))
-                .width(60.0)
-                .show_ui(ui, |ui| {
-                    for &rate in &monitor.available_refresh_rates {
-                        if ui
-                            .selectable_value(&mut selected_rate, rate, format!("{}Hz", rate))
-                            .changed()
-                        {
-                            rate_changed = true;
-                        }
-                    }
-                });
-
-            if rate_changed {
-                rate_changes.push((index, selected_rate));
-            }
-
-            if ui.small_button("Apply").clicked() {
-                actions.push(index);
-            }
+            self.render_mode_controls(ui, monitor, CardLayout::Minimal, edits);
         });
     }
 
@@ -189,8 +554,7 @@ impl HertzRateApp {
         ui: &mut egui::Ui,
         index: usize,
         monitor: &Monitor,
-        actions: &mut Vec<usize>,
-        rate_changes: &mut Vec<(usize, u32)>,
+        edits: &mut CardEdits,
     ) {
         ui.group(|ui| {
             ui.vertical(|ui| {
@@ -204,50 +568,12 @@ impl HertzRateApp {
                     ));
                     ui.separator();
                     ui.label(format!("{}Hz", monitor.current_refresh_rate));
+                    self.render_vrr_badge(ui, monitor);
                 });
 
                 ui.add_space(4.0);
 
-                ui.vertical(|ui| {
-                    ui.label("Refresh rate:");
-
-                    ui.horizontal(|ui| {
-                        let mut selected_rate = self
-                            .selected_rates
-                            .get(&index)
-                            .copied()
-                            .unwrap_or(monitor.current_refresh_rate);
-
-                        let mut rate_changed = false;
-                        egui::ComboBox::from_id_source(format!("compact_combo_{}", index))
-                            .selected_text(format!("{}Hz", selected_rate))
-                            .width(80.0)
-                            .show_ui(ui, |ui| {
-                                for &rate in &monitor.available_refresh_rates {
-                                    let text = if rate == monitor.current_refresh_rate {
-                                        format!("{}Hz (current)", rate)
-                                    } else {
-                                        format!("{}Hz", rate)
-                                    };
-
-                                    if ui
-                                        .selectable_value(&mut selected_rate, rate, text)
-                                        .changed()
-                                    {
-                                        rate_changed = true;
-                                    }
-                                }
-                            });
-
-                        if rate_changed {
-                            rate_changes.push((index, selected_rate));
-                        }
-
-                        if ui.button("Apply").clicked() {
-                            actions.push(index);
-                        }
-                    });
-                });
+                self.render_mode_controls(ui, monitor, CardLayout::Compact, edits);
             });
         });
     }
@@ -257,13 +583,14 @@ impl HertzRateApp {
         ui: &mut egui::Ui,
         index: usize,
         monitor: &Monitor,
-        actions: &mut Vec<usize>,
-        rate_changes: &mut Vec<(usize, u32)>,
+        edits: &mut CardEdits,
     ) {
         ui.group(|ui| {
             ui.vertical(|ui| {
                 ui.horizontal(|ui| {
-                    ui.strong(format!("📺 Monitor {}: {}", index, monitor.description));
+                    ui.strong(format!("📺 Monitor {}: {}", index, monitor.description))
+                        .on_hover_text(format!("ID: {}", monitor.id));
+                    self.render_vrr_badge(ui, monitor);
                 });
 
                 ui.horizontal_wrapped(|ui| {
@@ -275,53 +602,263 @@ impl HertzRateApp {
                     ui.label(format!("Current: {}Hz", monitor.current_refresh_rate));
                     ui.separator();
                     ui.label(format!(
-                        "Available: {:?}Hz",
-                        monitor.available_refresh_rates
+                        "Available: {}Hz",
+                        format_rates(&monitor.available_refresh_rates)
                     ));
                 });
 
                 ui.add_space(4.0);
 
-                ui.horizontal_wrapped(|ui| {
-                    ui.label("Set refresh rate:");
-
-                    let mut selected_rate = self
-                        .selected_rates
-                        .get(&index)
-                        .copied()
-                        .unwrap_or(monitor.current_refresh_rate);
-
-                    let mut rate_changed = false;
-                    egui::ComboBox::from_id_source(format!("standard_combo_{}", index))
-                        .selected_text(format!("{}Hz", selected_rate))
-                        .width(120.0)
-                        .show_ui(ui, |ui| {
-                            for &rate in &monitor.available_refresh_rates {
-                                let text = if rate == monitor.current_refresh_rate {
-                                    format!("{}Hz (current)", rate)
-                                } else {
-                                    format!("{}Hz", rate)
-                                };
-
-                                if ui
-                                    .selectable_value(&mut selected_rate, rate, text)
-                                    .changed()
-                                {
-                                    rate_changed = true;
+                self.render_mode_controls(ui, monitor, CardLayout::Standard, edits);
+
+                egui::CollapsingHeader::new(format!("All modes ({})", monitor.modes.len()))
+                    .id_source(format!("standard_modes_{}", monitor.id))
+                    .show(ui, |ui| {
+                        egui::Grid::new(format!("standard_mode_grid_{}", monitor.id))
+                            .striped(true)
+                            .show(ui, |ui| {
+                                for mode in &monitor.modes {
+                                    let mut details = mode.details();
+                                    if mode.same_timing(&monitor.current_mode) {
+                                        details.push("current".to_string());
+                                    }
+                                    ui.label(format!(
+                                        "{}×{}{}",
+                                        mode.width,
+                                        mode.height,
+                                        if mode.interlaced { "i" } else { "" }
+                                    ));
+                                    ui.label(format!("{}Hz", mode.refresh_rate));
+                                    ui.small(details.join(", "));
+                                    ui.end_row();
                                 }
-                            }
-                        });
+                            });
+                    });
+            });
+        });
+    }
 
-                    if rate_changed {
-                        rate_changes.push((index, selected_rate));
+    /// The controls every card layout has: resolution and refresh rate
+    /// pickers, "Test", "Apply", "Set to max" and the VRR toggle. Only their
+    /// size and labels depend on the layout.
+    fn render_mode_controls(
+        &self,
+        ui: &mut egui::Ui,
+        monitor: &Monitor,
+        layout: CardLayout,
+        edits: &mut CardEdits,
+    ) {
+        let id = layout.id();
+        let roomy = matches!(layout, CardLayout::Standard | CardLayout::Compact);
+        let current_marker = if roomy { " (current)" } else { " ✓" };
+
+        ui.horizontal_wrapped(|ui| {
+            if layout == CardLayout::Standard {
+                ui.label("Resolution:");
+            }
+
+            let current_resolution = monitor.current_mode.resolution();
+            let mut selected_resolution = self.selected_resolution(monitor);
+
+            let mut resolution_changed = false;
+            egui::ComboBox::from_id_source(format!("{}_resolution_{}", id, monitor.id))
+                .selected_text(selected_resolution.to_string())
+                .width(layout.combo_width() + 30.0)
+                .show_ui(ui, |ui| {
+                    for resolution in monitor.resolutions() {
+                        let text = if resolution == current_resolution {
+                            format!("{}{}", resolution, current_marker)
+                        } else {
+                            resolution.to_string()
+                        };
+
+                        if ui
+                            .selectable_value(&mut selected_resolution, resolution, text)
+                            .changed()
+                        {
+                            resolution_changed = true;
+                        }
                     }
+                });
+
+            if resolution_changed {
+                edits
+                    .resolution_changes
+                    .push((monitor.id.clone(), selected_resolution));
+            }
+
+            if layout == CardLayout::Standard {
+                ui.label("Refresh rate:");
+            }
+
+            let mut selected_rate = self
+                .selected_rates
+                .get(&monitor.id)
+                .copied()
+                .unwrap_or(monitor.current_refresh_rate);
 
-                    if ui.button("Apply").clicked() {
-                        actions.push(index);
+            let mut rate_changed = false;
+            egui::ComboBox::from_id_source(format!("{}_combo_{}", id, monitor.id))
+                .selected_text(format!("{}Hz", selected_rate))
+                .width(layout.combo_width())
+                .show_ui(ui, |ui| {
+                    for rate in monitor.refresh_rates_at(self.selected_resolution(monitor)) {
+                        let text = if rate == monitor.current_refresh_rate {
+                            format!("{}Hz{}", rate, current_marker)
+                        } else {
+                            format!("{}Hz", rate)
+                        };
+
+                        let mut response = ui.selectable_value(&mut selected_rate, rate, text);
+                        // Fractional rates show their exact value, e.g. 60000/1001
+                        if rate.denominator() != 1 {
+                            response = response.on_hover_text(format!(
+                                "{}/{} Hz",
+                                rate.numerator(),
+                                rate.denominator()
+                            ));
+                        }
+                        if response.changed() {
+                            rate_changed = true;
+                        }
                     }
                 });
-            });
+
+            if rate_changed {
+                edits.rate_changes.push((monitor.id.clone(), selected_rate));
+            }
+
+            let button = |ui: &mut egui::Ui, text: &str| {
+                if roomy {
+                    ui.button(text)
+                } else {
+                    ui.small_button(text)
+                }
+            };
+
+            if button(ui, "Test")
+                .on_hover_text("Ask the driver whether this mode would work, without applying it")
+                .clicked()
+            {
+                edits.actions.push((monitor.id.clone(), CardAction::Test));
+            }
+
+            if button(ui, "Apply").clicked() {
+                edits.actions.push((monitor.id.clone(), CardAction::Apply));
+            }
+
+            self.render_max_button(ui, monitor, roomy, &mut edits.actions);
         });
+
+        self.render_vrr_toggle(ui, monitor, &mut edits.actions);
+    }
+
+    /// A "VRR 48-144Hz" badge for monitors with variable refresh rate.
+    fn render_vrr_badge(&self, ui: &mut egui::Ui, monitor: &Monitor) {
+        let Some(vrr) = &monitor.vrr else {
+            return;
+        };
+        let text = match vrr.range {
+            Some((min, max)) => format!(" VRR {}-{}Hz ", min, max),
+            None => " VRR ".to_string(),
+        };
+        ui.label(
+            egui::RichText::new(text)
+                .small()
+                .color(egui::Color32::WHITE)
+                .background_color(egui::Color32::from_rgb(46, 125, 50)),
+        )
+        .on_hover_text(format!("Variable refresh rate: {}", vrr));
+    }
+
+    /// Preference picker and "Harmonize" button for mixed refresh rates. The
+    /// button's tooltip shows what it would pick and which monitor limits it.
+    fn render_harmonize(&mut self, ui: &mut egui::Ui) {
+        egui::ComboBox::from_id_source("harmonize_preference")
+            .selected_text(self.harmonize_preference.to_string())
+            .width(140.0)
+            .show_ui(ui, |ui| {
+                for preference in [
+                    Preference::Highest,
+                    Preference::Lowest,
+                    Preference::MultipleOfFastest,
+                ] {
+                    ui.selectable_value(
+                        &mut self.harmonize_preference,
+                        preference,
+                        preference.to_string(),
+                    );
+                }
+            });
+
+        let hint = match Harmony::new(&self.monitors).choose(self.harmonize_preference) {
+            Ok(choice) => {
+                let mut lines: Vec<String> = choice
+                    .targets
+                    .iter()
+                    .map(|(monitor, mode)| format!("{}: {}Hz", monitor.description, mode))
+                    .collect();
+                if let Some((monitor, reason)) = &choice.limiting {
+                    lines.push(format!("Limited by {}: {}", monitor.description, reason));
+                }
+                Some(lines.join("\n"))
+            }
+            Err(e) => {
+                ui.small(format!("{:#}", e));
+                None
+            }
+        };
+        if let Some(hint) = hint {
+            if ui.button("🔗 Harmonize").on_hover_text(hint).clicked() {
+                self.harmonize();
+            }
+        }
+    }
+
+    /// A "Set to max" quick button that applies the highest rate of the
+    /// selected resolution.
+    fn render_max_button(
+        &self,
+        ui: &mut egui::Ui,
+        monitor: &Monitor,
+        full_size: bool,
+        actions: &mut Vec<(MonitorId, CardAction)>,
+    ) {
+        let resolution = self.selected_resolution(monitor);
+        let Some(max) = monitor.choose_rate(resolution, RatePolicy::Max) else {
+            return;
+        };
+        let button = if full_size {
+            ui.button("Set to max")
+        } else {
+            ui.small_button("Set to max")
+        };
+        if button
+            .on_hover_text(format!("Switch to {}@{}Hz", resolution, max))
+            .clicked()
+        {
+            actions.push((monitor.id.clone(), CardAction::SetRate(RatePolicy::Max)));
+        }
+    }
+
+    /// A "Variable refresh rate" checkbox for monitors with VRR. Where the
+    /// backend doesn't report the current state it shows as off.
+    fn render_vrr_toggle(
+        &self,
+        ui: &mut egui::Ui,
+        monitor: &Monitor,
+        actions: &mut Vec<(MonitorId, CardAction)>,
+    ) {
+        if monitor.vrr.is_none() {
+            return;
+        }
+        let mut enabled = monitor.vrr_enabled.unwrap_or(false);
+        let response = ui
+            .checkbox(&mut enabled, "Variable refresh rate")
+            .on_hover_text("Let the monitor follow the frame rate (adaptive sync)");
+        if response.changed() {
+            actions.push((monitor.id.clone(), CardAction::SetVrr(enabled)));
+        }
     }
 
     fn truncate_text(&self, text: &str, max_len: usize) -> String {
@@ -337,11 +874,18 @@ impl eframe::App for HertzRateApp {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // Auto-refresh every 5 seconds
         if self.last_refresh.elapsed().as_secs() >= 5 {
-            self.refresh_monitors();
+            self.load_monitors(false);
             self.last_refresh = std::time::Instant::now();
         }
 
+        self.render_confirm_dialog(ctx);
+
         egui::CentralPanel::default().show(ctx, |ui| {
+            // Other changes wait until the pending one is kept or reverted
+            if self.pending_revert.is_some() {
+                ui.disable();
+            }
+
             // Responsive header
             ui.vertical_centered(|ui| {
                 ui.heading("🖥️ HertzRate");
@@ -367,6 +911,14 @@ impl eframe::App for HertzRateApp {
                 if ui.button("🔄 Refresh Monitors").clicked() {
                     self.refresh_monitors();
                 }
+                if !self.monitors.is_empty()
+                    && ui
+                        .button("⏫ All to max")
+                        .on_hover_text("Set every monitor to its highest rate")
+                        .clicked()
+                {
+                    self.set_all_to_max();
+                }
 
                 ui.separator();
 
@@ -380,7 +932,7 @@ impl eframe::App for HertzRateApp {
                     ui.label(format!("{} monitors", monitor_count));
 
                     // Show refresh rate diversity
-                    let unique_rates: std::collections::HashSet<u32> = self
+                    let unique_rates: std::collections::HashSet<RefreshRate> = self
                         .monitors
                         .iter()
                         .map(|m| m.current_refresh_rate)
@@ -389,20 +941,28 @@ impl eframe::App for HertzRateApp {
                     if unique_rates.len() > 1 {
                         ui.separator();
                         ui.colored_label(egui::Color32::YELLOW, "Mixed refresh rates");
+                        self.render_harmonize(ui);
                     }
                 }
             });
 
             ui.separator();
 
+            match self.render_snapshots(ui) {
+                Some(SnapshotAction::Save) => self.save_snapshot(),
+                Some(SnapshotAction::Restore(name)) => self.restore_snapshot(&name),
+                None => {}
+            }
+
+            ui.separator();
+
             if self.monitors.is_empty() {
                 ui.label("No monitors found. Click 'Refresh Monitors' to try again.");
                 return;
             }
 
             // Monitor list - data-adaptive layout
-            let mut actions_to_perform: Vec<usize> = Vec::new();
-            let mut rate_changes: Vec<(usize, u32)> = Vec::new();
+            let mut edits = CardEdits::default();
 
             egui::ScrollArea::vertical().show(ui, |ui| {
                 let available_width = ui.available_width();
@@ -423,13 +983,7 @@ impl eframe::App for HertzRateApp {
                         .spacing([10.0, 10.0])
                         .show(ui, |ui| {
                             for (index, monitor) in self.monitors.iter().enumerate() {
-                                self.render_monitor_card_grid(
-                                    ui,
-                                    index,
-                                    monitor,
-                                    &mut actions_to_perform,
-                                    &mut rate_changes,
-                                );
+                                self.render_monitor_card_grid(ui, index, monitor, &mut edits);
 
                                 if (index + 1) % columns == 0 {
                                     ui.end_row();
@@ -440,29 +994,11 @@ impl eframe::App for HertzRateApp {
                     // Vertical list layout
                     for (index, monitor) in self.monitors.iter().enumerate() {
                         if use_minimal_layout {
-                            self.render_monitor_card_minimal(
-                                ui,
-                                index,
-                                monitor,
-                                &mut actions_to_perform,
-                                &mut rate_changes,
-                            );
+                            self.render_monitor_card_minimal(ui, index, monitor, &mut edits);
                         } else if use_compact_layout {
-                            self.render_monitor_card_compact(
-                                ui,
-                                index,
-                                monitor,
-                                &mut actions_to_perform,
-                                &mut rate_changes,
-                            );
+                            self.render_monitor_card_compact(ui, index, monitor, &mut edits);
                         } else {
-                            self.render_monitor_card_standard(
-                                ui,
-                                index,
-                                monitor,
-                                &mut actions_to_perform,
-                                &mut rate_changes,
-                            );
+                            self.render_monitor_card_standard(ui, index, monitor, &mut edits);
                         }
                         ui.add_space(8.0);
                     }
@@ -470,12 +1006,28 @@ impl eframe::App for HertzRateApp {
             });
 
             // Process collected actions
-            for (index, rate) in rate_changes {
-                self.selected_rates.insert(index, rate);
+            for (id, rate) in edits.rate_changes {
+                self.selected_rates.insert(id, rate);
             }
 
-            for index in actions_to_perform {
-                self.apply_rate_change(index);
+            // A new resolution starts out at its fastest rate
+            for (id, resolution) in edits.resolution_changes {
+                if let Some(mode) = self
+                    .monitor(&id)
+                    .and_then(|monitor| monitor.find_mode(resolution, None))
+                {
+                    self.selected_rates.insert(id.clone(), mode.refresh_rate);
+                }
+                self.selected_resolutions.insert(id, resolution);
+            }
+
+            for (id, action) in edits.actions {
+                match action {
+                    CardAction::Apply => self.apply_rate_change(&id),
+                    CardAction::Test => self.test_rate_change(&id),
+                    CardAction::SetVrr(enabled) => self.set_vrr(&id, enabled),
+                    CardAction::SetRate(policy) => self.apply_rate(&id, policy),
+                }
             }
 
             ui.separator();
@@ -484,9 +1036,11 @@ impl eframe::App for HertzRateApp {
     }
 }
 
-pub fn run_gui() -> Result<()> {
+pub fn run_gui(backend: Box<dyn DisplayBackend>) -> Result<()> {
     // Get initial monitor count to size window appropriately
-    let initial_monitor_count = Monitor::enumerate_monitors().map(|m| m.len()).unwrap_or(1);
+    let initial_monitor_count = Monitor::enumerate_monitors(backend.as_ref())
+        .map(|m| m.len())
+        .unwrap_or(1);
 
     // Adaptive initial window size based on expected content
     let (width, height) = match initial_monitor_count {
@@ -509,7 +1063,139 @@ pub fn run_gui() -> Result<()> {
     eframe::run_native(
         "HertzRate - Monitor Refresh Rate Manager",
         options,
-        Box::new(|_cc| Ok(Box::new(HertzRateApp::new()))),
+        Box::new(|_cc| Ok(Box::new(HertzRateApp::new(backend)))),
     )
     .map_err(|e| anyhow::anyhow!("Failed to run GUI: {}", e))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::SimBackend;
+
+    fn app(scenario: &str) -> HertzRateApp {
+        let path = format!("{}/scenarios/{}", env!("CARGO_MANIFEST_DIR"), scenario);
+        HertzRateApp::new(Box::new(SimBackend::from_file(path.as_ref()).unwrap()))
+    }
+
+    /// Selects `rate` on the monitor with `device_name` and presses Apply.
+    fn apply(app: &mut HertzRateApp, device_name: &str, rate: &str) -> MonitorId {
+        let id = app
+            .monitors
+            .iter()
+            .find(|monitor| monitor.device_name == device_name)
+            .unwrap()
+            .id
+            .clone();
+        app.selected_rates.insert(id.clone(), rate.parse().unwrap());
+        app.apply_rate_change(&id);
+        id
+    }
+
+    #[test]
+    fn apply_switches_the_rate_and_starts_the_countdown() {
+        let mut app = app("desk.toml");
+        let id = apply(&mut app, "SIM-1", "144");
+        assert!(!app.show_error, "{}", app.error_message);
+        assert_eq!(
+            app.monitor(&id).unwrap().current_mode.to_string(),
+            "2560x1440@144"
+        );
+        assert!(app.pending_revert.is_some());
+    }
+
+    #[test]
+    fn apply_reports_a_rejected_mode() {
+        let mut app = app("failures.toml");
+        apply(&mut app, "SIM-REJECT", "165");
+        assert!(app.show_error);
+        assert!(app
+            .error_message
+            .contains("Driver rejected mode 2560x1440@165"));
+        assert!(app.pending_revert.is_none());
+    }
+
+    #[test]
+    fn apply_reports_a_monitor_that_disconnects() {
+        let mut app = app("failures.toml");
+        apply(&mut app, "SIM-UNPLUG", "75");
+        assert!(app.show_error);
+        assert!(app
+            .error_message
+            .contains("Device SIM-UNPLUG was disconnected"));
+        assert!(app.pending_revert.is_none());
+    }
+
+    #[test]
+    fn apply_notices_a_driver_that_ignores_the_change() {
+        let mut app = app("failures.toml");
+        apply(&mut app, "SIM-STUCK", "120");
+        assert!(app.show_error);
+        assert!(
+            app.error_message.contains("still runs 1920x1080@60Hz"),
+            "{}",
+            app.error_message
+        );
+        assert!(app.pending_revert.is_none());
+    }
+
+    /// The texts a monitor card draws in `layout`.
+    fn card_texts(app: &HertzRateApp, layout: CardLayout) -> Vec<String> {
+        let ctx = egui::Context::default();
+        let output = ctx.run(egui::RawInput::default(), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                let monitor = &app.monitors[0];
+                let mut edits = CardEdits::default();
+                match layout {
+                    CardLayout::Standard => {
+                        app.render_monitor_card_standard(ui, 0, monitor, &mut edits)
+                    }
+                    CardLayout::Compact => {
+                        app.render_monitor_card_compact(ui, 0, monitor, &mut edits)
+                    }
+                    CardLayout::Grid => app.render_monitor_card_grid(ui, 0, monitor, &mut edits),
+                    CardLayout::Minimal => {
+                        app.render_monitor_card_minimal(ui, 0, monitor, &mut edits)
+                    }
+                }
+            });
+        });
+        output
+            .shapes
+            .into_iter()
+            .filter_map(|clipped| match clipped.shape {
+                egui::Shape::Text(text) => Some(text.galley.text().to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn every_layout_has_the_same_controls() {
+        let app = app("desk.toml");
+        for layout in [
+            CardLayout::Standard,
+            CardLayout::Compact,
+            CardLayout::Grid,
+            CardLayout::Minimal,
+        ] {
+            let texts = card_texts(&app, layout);
+            for control in [
+                "2560x1440",
+                "60Hz",
+                "Test",
+                "Apply",
+                "Set to max",
+                "Variable refresh rate",
+            ] {
+                assert!(
+                    texts.iter().any(|text| text == control),
+                    "{:?} has no {}: {:?}",
+                    layout,
+                    control,
+                    texts
+                );
+            }
+        }
+    }
+}
@@ -1,10 +1,9 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-mod gui;
-mod monitor;
-
 use anyhow::Result;
+use hertzrate::backend::{self, BackendOptions};
+use hertzrate::gui;
 
 fn main() -> Result<()> {
-    gui::run_gui()
+    gui::run_gui(backend::create_backend(&BackendOptions::default())?)
 }
@@ -0,0 +1,398 @@
+use crate::backend::DisplayMode;
+use crate::monitor::Monitor;
+use crate::refresh_rate::{format_rates, RefreshRate};
+use anyhow::{anyhow, Result};
+use std::fmt;
+
+/// Which common rate `harmonize` picks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Preference {
+    /// The highest rate every monitor supports
+    #[default]
+    Highest,
+    /// The lowest rate every monitor supports
+    Lowest,
+    /// The fastest monitor as fast as possible, the others at rates it is an
+    /// integer multiple of, e.g. 144Hz with 72Hz
+    MultipleOfFastest,
+}
+
+impl fmt::Display for Preference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Preference::Highest => "highest",
+            Preference::Lowest => "lowest",
+            Preference::MultipleOfFastest => "multiple-of-fastest",
+        })
+    }
+}
+
+/// The refresh rates a set of monitors can share at their current
+/// resolutions. Rates are compared to the millihertz, so 59.94Hz and
+/// 60000/1001 count as the same rate but 59.94Hz and 60Hz don't.
+pub struct Harmony<'a> {
+    monitors: &'a [Monitor],
+    /// Each monitor's rates at its current resolution, ascending
+    rates: Vec<Vec<RefreshRate>>,
+}
+
+/// A rate for every monitor, as `Harmony::choose` picked them.
+pub struct Choice<'a> {
+    pub targets: Vec<(&'a Monitor, DisplayMode)>,
+    /// The monitor that keeps the choice from being faster, and why
+    pub limiting: Option<(&'a Monitor, String)>,
+}
+
+impl<'a> Harmony<'a> {
+    pub fn new(monitors: &'a [Monitor]) -> Self {
+        let rates = monitors
+            .iter()
+            .map(|monitor| monitor.refresh_rates_at(monitor.current_mode.resolution()))
+            .collect();
+        Self { monitors, rates }
+    }
+
+    /// Rates every monitor supports, ascending, as the first monitor lists
+    /// them.
+    pub fn common(&self) -> Vec<RefreshRate> {
+        let Some((first, others)) = self.rates.split_first() else {
+            return Vec::new();
+        };
+        first
+            .iter()
+            .copied()
+            .filter(|&rate| {
+                others
+                    .iter()
+                    .all(|rates| rates.iter().any(|&other| same_rate(rate, other)))
+            })
+            .collect()
+    }
+
+    /// Combinations in which the fastest monitor's rate is an integer
+    /// multiple of every other monitor's rate and at least one monitor runs
+    /// slower, fastest combination first. Each lists one rate per monitor.
+    pub fn multiples(&self) -> Vec<Vec<RefreshRate>> {
+        let Some(fastest) = self.fastest() else {
+            return Vec::new();
+        };
+        self.rates[fastest]
+            .iter()
+            .rev()
+            .filter_map(|&rate| self.divisors_of(rate).ok())
+            .filter(|rates| !rates.iter().all(|&r| same_rate(r, rates[fastest])))
+            .collect()
+    }
+
+    /// The monitor whose highest rate is lowest; no common rate can be
+    /// faster than it.
+    pub fn slowest(&self) -> Option<(&'a Monitor, RefreshRate)> {
+        self.monitors
+            .iter()
+            .zip(&self.rates)
+            .filter_map(|(monitor, rates)| Some((monitor, *rates.last()?)))
+            .min_by_key(|&(_, max)| max)
+    }
+
+    /// Picks a rate for every monitor following `preference`.
+    pub fn choose(&self, preference: Preference) -> Result<Choice<'a>> {
+        match preference {
+            Preference::Highest | Preference::Lowest => {
+                let common = self.common();
+                let rate = match preference {
+                    Preference::Highest => common.last(),
+                    _ => common.first(),
+                };
+                let Some(&rate) = rate else {
+                    return Err(self.no_common_rate());
+                };
+                let rates = self
+                    .rates
+                    .iter()
+                    .map(|rates| {
+                        rates
+                            .iter()
+                            .copied()
+                            .find(|&other| same_rate(rate, other))
+                            .expect("common rates are listed by every monitor")
+                    })
+                    .collect();
+                let limiting = self
+                    .slowest()
+                    .filter(|_| preference == Preference::Highest)
+                    .map(|(monitor, max)| {
+                        (
+                            monitor,
+                            format!(
+                                "its highest rate at {} is {}Hz",
+                                monitor.current_mode.resolution(),
+                                max
+                            ),
+                        )
+                    });
+                Ok(self.choice(rates, limiting))
+            }
+            Preference::MultipleOfFastest => {
+                let fastest = self.fastest().ok_or_else(|| anyhow!("No monitors found"))?;
+                // Of the combinations, the one that keeps the monitor furthest
+                // below its own highest rate closest to it, so 120/60 beats
+                // 144/24 on a 60Hz TV
+                let combinations: Vec<(RefreshRate, Result<Vec<RefreshRate>, usize>)> = self.rates
+                    [fastest]
+                    .iter()
+                    .rev()
+                    .map(|&rate| (rate, self.divisors_of(rate)))
+                    .collect();
+                let mut best: Option<(usize, f64)> = None;
+                for (index, (_, rates)) in combinations.iter().enumerate() {
+                    let Ok(rates) = rates else { continue };
+                    let (_, fraction) = self.worst_fraction(rates);
+                    if best.is_none_or(|(_, best_fraction)| fraction > best_fraction) {
+                        best = Some((index, fraction));
+                    }
+                }
+                let Some((best, _)) = best else {
+                    return Err(anyhow!(
+                        "No rate of {} is an integer multiple of a rate of every other monitor",
+                        self.monitors[fastest].description
+                    ));
+                };
+
+                // Explain why the fastest monitor isn't at the faster rates
+                let limiting = combinations[..best]
+                    .first()
+                    .map(|(rate, rates)| match rates {
+                        Err(index) => (
+                            &self.monitors[*index],
+                            format!(
+                                "{}Hz is no multiple of any of its rates {}",
+                                rate,
+                                format_rates(&self.rates[*index])
+                            ),
+                        ),
+                        Ok(rates) => {
+                            let (index, _) = self.worst_fraction(rates);
+                            (
+                                &self.monitors[index],
+                                format!("{}Hz would slow it down to {}Hz", rate, rates[index]),
+                            )
+                        }
+                    });
+                let (_, rates) = &combinations[best];
+                Ok(self.choice(
+                    rates
+                        .clone()
+                        .expect("only combinations that work are picked"),
+                    limiting,
+                ))
+            }
+        }
+    }
+
+    /// The monitor running furthest below its highest rate with `rates`, and
+    /// the fraction of that rate it gets.
+    fn worst_fraction(&self, rates: &[RefreshRate]) -> (usize, f64) {
+        rates
+            .iter()
+            .zip(&self.rates)
+            .map(|(rate, available)| {
+                let max = available.last().map_or(1.0, RefreshRate::as_f64);
+                rate.as_f64() / max
+            })
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .unwrap_or((0, 1.0))
+    }
+
+    /// Index of the monitor with the highest rate.
+    fn fastest(&self) -> Option<usize> {
+        (0..self.rates.len()).max_by_key(|&index| self.rates[index].last().copied())
+    }
+
+    /// For each monitor the highest rate `rate` is an integer multiple of,
+    /// or the index of the first monitor without one.
+    fn divisors_of(&self, rate: RefreshRate) -> std::result::Result<Vec<RefreshRate>, usize> {
+        self.rates
+            .iter()
+            .enumerate()
+            .map(|(index, rates)| {
+                rates
+                    .iter()
+                    .rev()
+                    .copied()
+                    .find(|&divisor| is_multiple(rate, divisor))
+                    .ok_or(index)
+            })
+            .collect()
+    }
+
+    fn choice(
+        &self,
+        rates: Vec<RefreshRate>,
+        limiting: Option<(&'a Monitor, String)>,
+    ) -> Choice<'a> {
+        let targets = self
+            .monitors
+            .iter()
+            .zip(rates)
+            .map(|(monitor, rate)| {
+                let mode = monitor
+                    .find_mode(monitor.current_mode.resolution(), Some(rate))
+                    .expect("rates come from the monitor's modes");
+                (monitor, mode)
+            })
+            .collect();
+        Choice { targets, limiting }
+    }
+
+    fn no_common_rate(&self) -> anyhow::Error {
+        let rates: Vec<String> = self
+            .monitors
+            .iter()
+            .zip(&self.rates)
+            .map(|(monitor, rates)| format!("{} {}", monitor.description, format_rates(rates)))
+            .collect();
+        anyhow!(
+            "No refresh rate is supported by every monitor ({}); try --prefer multiple-of-fastest",
+            rates.join(", ")
+        )
+    }
+}
+
+/// Whether two rates agree to the millihertz.
+fn same_rate(a: RefreshRate, b: RefreshRate) -> bool {
+    a.millihertz() == b.millihertz()
+}
+
+/// Whether `rate` is an integer multiple of `divisor` (including 1×), to the
+/// millihertz per multiple.
+fn is_multiple(rate: RefreshRate, divisor: RefreshRate) -> bool {
+    let (rate, divisor) = (
+        i64::from(rate.millihertz()),
+        i64::from(divisor.millihertz()),
+    );
+    if divisor == 0 || rate < divisor - 1 {
+        return false;
+    }
+    let factor = (rate + divisor / 2) / divisor;
+    (rate - factor * divisor).abs() <= factor
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::SimBackend;
+
+    /// Monitors at 1920x1080 with the given rates each, named `SIM-1`, ...
+    fn monitors(rates: &[&[&str]]) -> Vec<Monitor> {
+        let scenario: String = rates
+            .iter()
+            .enumerate()
+            .map(|(index, rates)| {
+                let modes: Vec<String> = rates
+                    .iter()
+                    .map(|rate| format!("\"1920x1080@{}\"", rate))
+                    .collect();
+                format!(
+                    "[[monitor]]\ndevice_name = \"SIM-{0}\"\ndescription = \"Monitor {0}\"\n\
+                     current = \"1920x1080@{1}\"\nmodes = [{2}]\n",
+                    index + 1,
+                    rates[0],
+                    modes.join(", ")
+                )
+            })
+            .collect();
+        let backend = SimBackend::from_toml(&scenario).unwrap();
+        Monitor::enumerate_monitors(&backend).unwrap()
+    }
+
+    /// The chosen rates as text, and the limiting monitor's device name.
+    fn choose(monitors: &[Monitor], preference: Preference) -> (Vec<String>, Option<String>) {
+        let choice = Harmony::new(monitors).choose(preference).unwrap();
+        let rates = choice
+            .targets
+            .iter()
+            .map(|(_, mode)| mode.refresh_rate.to_string())
+            .collect();
+        let limiting = choice
+            .limiting
+            .map(|(monitor, _)| monitor.device_name.clone());
+        (rates, limiting)
+    }
+
+    #[test]
+    fn highest_is_limited_by_the_slowest_monitor() {
+        let monitors = monitors(&[&["60", "120", "144"], &["50", "60", "72", "120"]]);
+        assert_eq!(
+            Harmony::new(&monitors).common(),
+            ["60".parse().unwrap(), "120".parse().unwrap()]
+        );
+        let (rates, limiting) = choose(&monitors, Preference::Highest);
+        assert_eq!(rates, ["120", "120"]);
+        assert_eq!(limiting.as_deref(), Some("SIM-2"));
+    }
+
+    #[test]
+    fn lowest_has_no_limiting_monitor() {
+        let monitors = monitors(&[&["60", "120", "144"], &["50", "60", "72", "120"]]);
+        let (rates, limiting) = choose(&monitors, Preference::Lowest);
+        assert_eq!(rates, ["60", "60"]);
+        assert_eq!(limiting, None);
+    }
+
+    #[test]
+    fn multiple_of_fastest_keeps_every_monitor_closest_to_its_maximum() {
+        let monitors = monitors(&[&["60", "120", "144"], &["50", "60", "72", "120"]]);
+        assert_eq!(
+            Harmony::new(&monitors).multiples(),
+            [vec!["144".parse().unwrap(), "72".parse().unwrap()]]
+        );
+        // 144/72 would leave the second monitor at 60% of its maximum
+        let (rates, limiting) = choose(&monitors, Preference::MultipleOfFastest);
+        assert_eq!(rates, ["120", "120"]);
+        assert_eq!(limiting.as_deref(), Some("SIM-2"));
+    }
+
+    #[test]
+    fn ntsc_rates_count_as_the_same_rate() {
+        let monitors = monitors(&[&["59.94", "144"], &["60000/1001", "75"]]);
+        let (rates, _) = choose(&monitors, Preference::Highest);
+        assert_eq!(rates, ["59.94", "59.94"]);
+    }
+
+    #[test]
+    fn without_a_common_rate_only_multiples_remain() {
+        let monitors = monitors(&[&["144", "165"], &["60", "72", "75"]]);
+        assert!(Harmony::new(&monitors).common().is_empty());
+        for preference in [Preference::Highest, Preference::Lowest] {
+            let error = Harmony::new(&monitors).choose(preference).err().unwrap();
+            assert!(error
+                .to_string()
+                .starts_with("No refresh rate is supported by every monitor"));
+        }
+
+        let harmony = Harmony::new(&monitors);
+        let choice = harmony.choose(Preference::MultipleOfFastest).unwrap();
+        let (monitor, reason) = choice.limiting.unwrap();
+        assert_eq!(monitor.device_name, "SIM-2");
+        assert_eq!(
+            reason,
+            "165Hz is no multiple of any of its rates [60, 72, 75]"
+        );
+        let (rates, _) = choose(&monitors, Preference::MultipleOfFastest);
+        assert_eq!(rates, ["144", "72"]);
+    }
+
+    #[test]
+    fn no_multiple_at_all_is_an_error() {
+        let monitors = monitors(&[&["144", "165"], &["50", "75"]]);
+        let error = Harmony::new(&monitors)
+            .choose(Preference::MultipleOfFastest)
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.to_string(),
+            "No rate of Monitor 1 is an integer multiple of a rate of every other monitor"
+        );
+    }
+}
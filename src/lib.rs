@@ -0,0 +1,12 @@
+pub mod backend;
+pub mod config;
+pub mod edid;
+pub mod error;
+pub mod gui;
+pub mod harmonize;
+pub mod listing;
+pub mod monitor;
+pub mod paths;
+pub mod profile;
+pub mod refresh_rate;
+pub mod snapshot;
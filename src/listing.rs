@@ -0,0 +1,523 @@
+use crate::backend::{DisplayBackend, DisplayMode, Orientation, Resolution, VrrSource};
+use crate::config::Config;
+use crate::harmonize::Preference;
+use crate::monitor::Monitor;
+use crate::profile::Profile;
+use crate::refresh_rate::{RatePolicy, RefreshRate};
+use crate::snapshot::{self, Snapshot};
+use anyhow::Result;
+use serde::Serialize;
+
+/// Version of the JSON and CSV layouts of every command. Fields may be added without a new
+/// version; renaming, removing or changing the meaning of one bumps it.
+pub const SCHEMA_VERSION: u32 = 1;
+
+/// How commands that report monitors, snapshots or profiles print them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum OutputFormat {
+    /// Human-readable text
+    #[default]
+    Table,
+    /// One JSON document
+    Json,
+    /// A header line and one row per item, e.g. per monitor
+    Csv,
+}
+
+/// The connected monitors in the machine-readable schema:
+///
+/// ```json
+/// {
+///   "schema_version": 1,
+///   "backend": "wayland",
+///   "monitors": [
+///     {
+///       "id": "DEL-41A8-5QF8K93",
+///       "identity": { "manufacturer": "DEL", "product": "41A8", "serial": "5QF8K93" },
+///       "device_name": "DP-1",
+///       "description": "Dell Inc. DELL S2721DGF",
+///       "resolution": "2560x1440",
+///       "refresh_rate": "144",
+///       "current_mode": { "width": 2560, "height": 1440, "refresh_rate": "144", ... },
+///       "available_refresh_rates": ["60", "120", "144"],
+///       "modes": [ ... ],
+///       "placement": { "x": 0, "y": 0, "orientation": "normal" },
+///       "vrr": { "range": [48, 144], "source": "freesync" },
+///       "vrr_enabled": true
+///     }
+///   ]
+/// }
+/// ```
+#[derive(Debug, Serialize)]
+pub struct MonitorList<'a> {
+    pub schema_version: u32,
+    /// Backend the monitors were read with
+    pub backend: &'a str,
+    pub monitors: &'a [Monitor],
+}
+
+/// A CSV row. Lists are joined with spaces, rates are exact as in the JSON
+/// and missing values are empty.
+#[derive(Serialize)]
+struct CsvRow<'a> {
+    schema_version: u32,
+    index: usize,
+    id: String,
+    manufacturer: Option<&'a str>,
+    product: Option<&'a str>,
+    serial: Option<&'a str>,
+    device_name: &'a str,
+    description: &'a str,
+    width: u32,
+    height: u32,
+    interlaced: bool,
+    refresh_rate: RefreshRate,
+    refresh_hz: f64,
+    available_refresh_rates: String,
+    modes: String,
+    x: Option<i32>,
+    y: Option<i32>,
+    orientation: Option<Orientation>,
+    vrr_min: Option<u32>,
+    vrr_max: Option<u32>,
+    vrr_source: Option<VrrSource>,
+    vrr_enabled: Option<bool>,
+}
+
+impl<'a> MonitorList<'a> {
+    pub fn new(backend: &'a dyn DisplayBackend, monitors: &'a [Monitor]) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            backend: backend.name(),
+            monitors,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// One row per monitor, with the columns of [`CsvRow`] as the header.
+    pub fn to_csv(&self) -> Result<String> {
+        to_csv(
+            self.monitors
+                .iter()
+                .enumerate()
+                .map(|(index, monitor)| CsvRow::new(index, monitor)),
+        )
+    }
+}
+
+impl<'a> CsvRow<'a> {
+    fn new(index: usize, monitor: &'a Monitor) -> Self {
+        let identity = monitor.id.identity();
+        let mode = monitor.current_mode;
+        let join = |items: Vec<String>| items.join(" ");
+        Self {
+            schema_version: SCHEMA_VERSION,
+            index,
+            id: monitor.id.to_string(),
+            manufacturer: identity.map(|identity| identity.manufacturer.as_str()),
+            product: identity.map(|identity| identity.product.as_str()),
+            serial: identity.and_then(|identity| identity.serial.as_deref()),
+            device_name: &monitor.device_name,
+            description: &monitor.description,
+            width: mode.width,
+            height: mode.height,
+            interlaced: mode.interlaced,
+            refresh_rate: mode.refresh_rate,
+            refresh_hz: mode.refresh_rate.millihertz() as f64 / 1000.0,
+            available_refresh_rates: join(
+                monitor
+                    .available_refresh_rates
+                    .iter()
+                    .map(RefreshRate::to_exact_string)
+                    .collect(),
+            ),
+            modes: join(monitor.modes.iter().map(exact_mode).collect()),
+            x: monitor.placement.map(|placement| placement.x),
+            y: monitor.placement.map(|placement| placement.y),
+            orientation: monitor.placement.map(|placement| placement.orientation),
+            vrr_min: monitor.vrr.and_then(|vrr| vrr.range).map(|(min, _)| min),
+            vrr_max: monitor.vrr.and_then(|vrr| vrr.range).map(|(_, max)| max),
+            vrr_source: monitor.vrr.map(|vrr| vrr.source),
+            vrr_enabled: monitor.vrr_enabled,
+        }
+    }
+}
+
+/// One value from `get`, e.g. for `--field rate`:
+///
+/// ```json
+/// {
+///   "schema_version": 1,
+///   "backend": "wayland",
+///   "id": "DEL-41A8-5QF8K93",
+///   "device_name": "DP-1",
+///   "field": "rate",
+///   "value": "60000/1001"
+/// }
+/// ```
+///
+/// Rates are exact, and the CSV has the same columns in one row.
+#[derive(Debug, Serialize)]
+pub struct FieldValue<'a> {
+    pub schema_version: u32,
+    pub backend: &'a str,
+    pub id: String,
+    pub device_name: &'a str,
+    pub field: &'a str,
+    pub value: String,
+}
+
+impl<'a> FieldValue<'a> {
+    pub fn new(
+        backend: &'a dyn DisplayBackend,
+        monitor: &'a Monitor,
+        field: &'a str,
+        value: String,
+    ) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            backend: backend.name(),
+            id: monitor.id.to_string(),
+            device_name: &monitor.device_name,
+            field,
+            value,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn to_csv(&self) -> Result<String> {
+        to_csv([self])
+    }
+}
+
+/// The saved snapshots, as `snapshot list` prints them. `backend` and
+/// `monitors` are `null` and `error` says why for a snapshot that can't be
+/// read.
+#[derive(Debug, Serialize)]
+pub struct SnapshotList {
+    pub schema_version: u32,
+    pub snapshots: Vec<SnapshotEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SnapshotEntry {
+    pub name: String,
+    pub backend: Option<String>,
+    /// How many monitors the snapshot has settings for
+    pub monitors: Option<usize>,
+    pub error: Option<String>,
+}
+
+/// A `snapshot list` CSV row; the columns are the fields of
+/// [`SnapshotEntry`].
+#[derive(Serialize)]
+struct SnapshotRow<'a> {
+    schema_version: u32,
+    name: &'a str,
+    backend: Option<&'a str>,
+    monitors: Option<usize>,
+    error: Option<&'a str>,
+}
+
+impl SnapshotList {
+    /// Reads every snapshot in the configuration directory.
+    pub fn load() -> Result<Self> {
+        let snapshots = snapshot::list()?
+            .into_iter()
+            .map(|name| match Snapshot::load(&name) {
+                Ok(snapshot) => SnapshotEntry {
+                    name,
+                    backend: Some(snapshot.backend),
+                    monitors: Some(snapshot.monitors.len()),
+                    error: None,
+                },
+                Err(e) => SnapshotEntry {
+                    name,
+                    backend: None,
+                    monitors: None,
+                    error: Some(format!("{:#}", e)),
+                },
+            })
+            .collect();
+        Ok(Self {
+            schema_version: SCHEMA_VERSION,
+            snapshots,
+        })
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn to_csv(&self) -> Result<String> {
+        to_csv(self.snapshots.iter().map(|entry| SnapshotRow {
+            schema_version: SCHEMA_VERSION,
+            name: &entry.name,
+            backend: entry.backend.as_deref(),
+            monitors: entry.monitors,
+            error: entry.error.as_deref(),
+        }))
+    }
+}
+
+/// The profiles in the config file, as `profile list` prints them:
+///
+/// ```json
+/// {
+///   "schema_version": 1,
+///   "profiles": [
+///     {
+///       "name": "gaming",
+///       "rate": null, "resolution": null, "vrr": null,
+///       "monitors": [
+///         { "id": "DEL-41A8", "device_name": null, "description": null,
+///           "resolution": "2560x1440", "rate": "max", "vrr": true }
+///       ]
+///     }
+///   ]
+/// }
+/// ```
+///
+/// The profile-wide `rate`, `resolution` and `vrr` cover the monitors no
+/// entry matches. Rates use the syntax `--rate` takes.
+#[derive(Debug, Serialize)]
+pub struct ProfileList<'a> {
+    pub schema_version: u32,
+    pub profiles: Vec<ProfileEntry<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProfileEntry<'a> {
+    pub name: &'a str,
+    pub rate: Option<RatePolicy>,
+    pub resolution: Option<Resolution>,
+    pub vrr: Option<bool>,
+    pub monitors: Vec<ProfileMonitorEntry<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ProfileMonitorEntry<'a> {
+    pub id: Option<&'a str>,
+    pub device_name: Option<&'a str>,
+    pub description: Option<&'a str>,
+    pub resolution: Option<Resolution>,
+    pub rate: Option<RatePolicy>,
+    pub vrr: Option<bool>,
+}
+
+/// A `profile list` CSV row. Each profile has a `default` row with its
+/// profile-wide settings, followed by a `monitor` row per entry.
+#[derive(Serialize)]
+struct ProfileRow<'a> {
+    schema_version: u32,
+    profile: &'a str,
+    scope: &'static str,
+    id: Option<&'a str>,
+    device_name: Option<&'a str>,
+    description: Option<&'a str>,
+    resolution: Option<Resolution>,
+    rate: Option<RatePolicy>,
+    vrr: Option<bool>,
+}
+
+impl<'a> ProfileList<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            profiles: config
+                .profiles
+                .iter()
+                .map(|(name, profile)| ProfileEntry::new(name, profile))
+                .collect(),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn to_csv(&self) -> Result<String> {
+        to_csv(self.profiles.iter().flat_map(|profile| {
+            let defaults = ProfileRow {
+                schema_version: SCHEMA_VERSION,
+                profile: profile.name,
+                scope: "default",
+                id: None,
+                device_name: None,
+                description: None,
+                resolution: profile.resolution,
+                rate: profile.rate,
+                vrr: profile.vrr,
+            };
+            let monitors = profile.monitors.iter().map(|monitor| ProfileRow {
+                schema_version: SCHEMA_VERSION,
+                profile: profile.name,
+                scope: "monitor",
+                id: monitor.id,
+                device_name: monitor.device_name,
+                description: monitor.description,
+                resolution: monitor.resolution,
+                rate: monitor.rate,
+                vrr: monitor.vrr,
+            });
+            std::iter::once(defaults).chain(monitors)
+        }))
+    }
+}
+
+impl<'a> ProfileEntry<'a> {
+    fn new(name: &'a str, profile: &'a Profile) -> Self {
+        Self {
+            name,
+            rate: profile.rate,
+            resolution: profile.resolution,
+            vrr: profile.vrr,
+            monitors: profile
+                .monitors
+                .iter()
+                .map(|monitor| ProfileMonitorEntry {
+                    id: monitor.id.as_deref(),
+                    device_name: monitor.device_name.as_deref(),
+                    description: monitor.description.as_deref(),
+                    resolution: monitor.resolution,
+                    rate: monitor.rate,
+                    vrr: monitor.vrr,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// What `harmonize` found and did:
+///
+/// ```json
+/// {
+///   "schema_version": 1,
+///   "backend": "wayland",
+///   "preference": "highest",
+///   "dry_run": false,
+///   "common_rates": ["60", "120"],
+///   "multiples": [["120", "60"]],
+///   "limiting": { "id": "GSM-5B7F", "device_name": "HDMI-A-1", "reason": "..." },
+///   "monitors": [
+///     { "id": "DEL-41A8-5QF8K93", "device_name": "DP-1", "description": "DELL S2721DGF",
+///       "from": { "width": 2560, ... }, "to": { "width": 2560, ... },
+///       "status": "changed", "error": null }
+///   ]
+/// }
+/// ```
+///
+/// `multiples` lists one rate per monitor, in the order of `monitors`.
+/// `status` is `unchanged`, `accepted` (on a dry run), `changed` or `failed`.
+/// The CSV has one row per monitor with the columns `schema_version`, `id`,
+/// `device_name`, `description`, `from`, `to`, `status` and `error`, and
+/// modes written as `WIDTHxHEIGHT@RATE`.
+#[derive(Debug, Serialize)]
+pub struct HarmonizeReport<'a> {
+    pub schema_version: u32,
+    pub backend: &'a str,
+    pub preference: String,
+    pub dry_run: bool,
+    pub common_rates: Vec<RefreshRate>,
+    pub multiples: Vec<Vec<RefreshRate>>,
+    pub limiting: Option<LimitingMonitor<'a>>,
+    pub monitors: Vec<HarmonizeResult<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LimitingMonitor<'a> {
+    pub id: String,
+    pub device_name: &'a str,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct HarmonizeResult<'a> {
+    pub id: String,
+    pub device_name: &'a str,
+    pub description: &'a str,
+    pub from: DisplayMode,
+    pub to: DisplayMode,
+    pub status: HarmonizeStatus,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HarmonizeStatus {
+    /// The monitor already ran the chosen mode
+    Unchanged,
+    /// The driver would take the mode; only on dry runs
+    Accepted,
+    Changed,
+    Failed,
+}
+
+#[derive(Serialize)]
+struct HarmonizeRow<'a> {
+    schema_version: u32,
+    id: &'a str,
+    device_name: &'a str,
+    description: &'a str,
+    from: String,
+    to: String,
+    status: HarmonizeStatus,
+    error: Option<&'a str>,
+}
+
+impl<'a> HarmonizeReport<'a> {
+    pub fn new(backend: &'a dyn DisplayBackend, preference: Preference, dry_run: bool) -> Self {
+        Self {
+            schema_version: SCHEMA_VERSION,
+            backend: backend.name(),
+            preference: preference.to_string(),
+            dry_run,
+            common_rates: Vec::new(),
+            multiples: Vec::new(),
+            limiting: None,
+            monitors: Vec::new(),
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn to_csv(&self) -> Result<String> {
+        to_csv(self.monitors.iter().map(|result| HarmonizeRow {
+            schema_version: SCHEMA_VERSION,
+            id: &result.id,
+            device_name: result.device_name,
+            description: result.description,
+            from: exact_mode(&result.from),
+            to: exact_mode(&result.to),
+            status: result.status,
+            error: result.error.as_deref(),
+        }))
+    }
+}
+
+/// A mode as `WIDTHxHEIGHT@RATE` with the exact rate, as the machine-readable
+/// formats write it.
+pub fn exact_mode(mode: &DisplayMode) -> String {
+    format!(
+        "{}@{}",
+        mode.resolution(),
+        mode.refresh_rate.to_exact_string()
+    )
+}
+
+/// Writes `rows` with a header line made of their field names.
+fn to_csv<T: Serialize>(rows: impl IntoIterator<Item = T>) -> Result<String> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    for row in rows {
+        writer.serialize(row)?;
+    }
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
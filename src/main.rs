@@ -1,15 +1,53 @@
-mod gui;
-mod monitor;
-
-use anyhow::Result;
-use clap::{Parser, Subcommand};
-use monitor::Monitor;
+use anyhow::{anyhow, Context, Result};
+use clap::{ArgGroup, Args, CommandFactory, Parser, Subcommand, ValueEnum};
+use hertzrate::backend::{
+    self, BackendKind, BackendOptions, DisplayBackend, DisplayMode, Resolution,
+};
+use hertzrate::config::Config;
+use hertzrate::edid;
+use hertzrate::error::{self, ErrorFormat, ErrorReport, EXIT_USAGE};
+use hertzrate::gui;
+use hertzrate::harmonize::{Harmony, Preference};
+use hertzrate::listing::{
+    exact_mode, FieldValue, HarmonizeReport, HarmonizeResult, HarmonizeStatus, LimitingMonitor,
+    MonitorList, OutputFormat, ProfileList, SnapshotList,
+};
+use hertzrate::monitor::{on_off, Monitor, DEFAULT_CONFIRM_TIMEOUT_SECS};
+use hertzrate::paths;
+use hertzrate::profile::Profile;
+use hertzrate::refresh_rate::{format_rates, RatePolicy};
+use hertzrate::snapshot::Snapshot;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "hertzrate")]
 #[command(about = "A tool to manage monitor refresh rates")]
 #[command(version = "0.1.0")]
 struct Cli {
+    /// Display backend to use
+    #[arg(long, global = true, value_enum, default_value_t = BackendKind::Auto)]
+    backend: BackendKind,
+    /// Scenario file for the simulated backend
+    #[arg(long, global = true, value_name = "FILE")]
+    scenario: Option<PathBuf>,
+    /// sysfs DRM class directory for the drm backend (default: /sys/class/drm)
+    #[arg(long, global = true, value_name = "DIR")]
+    sysfs_root: Option<PathBuf>,
+    /// Apply changes without persisting them across restarts (gdi, mutter)
+    #[arg(long, global = true)]
+    temporary: bool,
+    /// How to report a failure on stderr; the exit status tells its class
+    #[arg(long, global = true, value_enum, default_value_t = ErrorFormat::Text)]
+    error_format: ErrorFormat,
+    /// Keep the change another hertzrate is counting down on, e.g. from a
+    /// second terminal or a script
+    #[arg(long)]
+    confirm: bool,
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -19,32 +57,381 @@ enum Commands {
     /// Launch the graphical user interface
     Gui,
     /// List all connected monitors and their available refresh rates
-    List,
-    /// Set refresh rate for a specific monitor
-    Set {
-        /// Monitor index (use 'list' command to see available monitors)
+    List {
+        /// Also print every mode each monitor reports (json and csv always include them)
+        #[arg(long)]
+        modes: bool,
+        /// Output format; json and csv follow a versioned schema
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    /// Print one value of a monitor's current state, for scripts and status bars
+    Get {
+        /// Monitor index, ID, device name or part of its description (see 'list')
         #[arg(short, long)]
-        monitor: usize,
-        /// Refresh rate in Hz
+        monitor: String,
+        /// Value to print
+        #[arg(long, value_enum, default_value_t = Field::Rate)]
+        field: Field,
+        /// Output format; json and csv follow a versioned schema
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    /// Check a monitor's current state: exits with 0 if it matches, 1 if it
+    /// doesn't and 2 or an error class status if it can't be checked
+    Is {
+        /// Monitor index, ID, device name or part of its description (see 'list')
         #[arg(short, long)]
-        rate: u32,
+        monitor: String,
+        /// Refresh rate in Hz, e.g. 144, 59.94 or 60000/1001, a range such as
+        /// 120..=165, or max, min, native or nearest:RATE
+        #[arg(short, long)]
+        rate: RatePolicy,
+    },
+    /// Set refresh rate, resolution or both for a specific monitor
+    #[command(group(
+        ArgGroup::new("target")
+            .required(true)
+            .multiple(true)
+            .args(["rate", "mode", "resolution"])
+    ))]
+    Set {
+        /// Monitor index, ID, device name or part of its description (see 'list')
+        #[arg(short, long)]
+        monitor: String,
+        /// Refresh rate in Hz, e.g. 144, 59.94 or 60000/1001; or max, min,
+        /// native (the preferred timing), nearest:RATE or a range such as
+        /// 120..=165 (its highest supported rate)
+        #[arg(short, long, conflicts_with = "mode")]
+        rate: Option<RatePolicy>,
+        /// Full mode to switch to, e.g. 1920x1080@240
+        #[arg(long, conflicts_with = "resolution")]
+        mode: Option<DisplayMode>,
+        /// Resolution to switch to, e.g. 1920x1080; picks the highest rate
+        /// unless --rate is also given
+        #[arg(long)]
+        resolution: Option<Resolution>,
+        #[command(flatten)]
+        change: ChangeOptions,
     },
     /// Set refresh rate for all monitors
     SetAll {
-        /// Refresh rate in Hz
+        /// Refresh rate in Hz, e.g. 144, 59.94 or 60000/1001; keywords and
+        /// ranges are resolved per monitor, so max puts each at its highest
+        #[arg(short, long)]
+        rate: RatePolicy,
+        /// Change every monitor or none: check all modes first, apply them
+        /// as one batch and roll back if anything fails
+        #[arg(long)]
+        atomic: bool,
+        #[command(flatten)]
+        change: ChangeOptions,
+    },
+    /// Put all monitors on refresh rates they share, for smooth movement
+    /// across screens
+    Harmonize {
+        /// Which common rate to pick
+        #[arg(long, value_enum, default_value_t = Preference::Highest)]
+        prefer: Preference,
+        /// Output format; json and csv follow a versioned schema and need
+        /// --dry-run or --no-confirm
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+        #[command(flatten)]
+        change: ChangeOptions,
+    },
+    /// Turn variable refresh rate (adaptive sync) on or off for a monitor
+    Vrr {
+        #[arg(value_enum)]
+        state: VrrState,
+        /// Monitor index, ID, device name or part of its description (see 'list')
+        #[arg(short, long)]
+        monitor: String,
+    },
+    /// Save and restore every monitor's mode and position
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotAction,
+    },
+    /// Manage the named profiles in the config file
+    Profile {
+        #[command(subcommand)]
+        action: ProfileAction,
+    },
+    /// Save or decode monitor EDIDs
+    Edid {
+        #[command(subcommand)]
+        action: EdidAction,
+    },
+}
+
+/// Values `get` can print.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Field {
+    /// Current refresh rate, e.g. 144 or 59.94
+    Rate,
+    /// Current resolution, e.g. 2560x1440
+    Resolution,
+    /// Current mode, e.g. 2560x1440@144
+    Mode,
+    /// on, off, unknown or unsupported
+    Vrr,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum VrrState {
+    On,
+    Off,
+}
+
+#[derive(Subcommand)]
+enum EdidAction {
+    /// Save a monitor's raw EDID
+    Dump {
+        /// Monitor index, ID, device name or part of its description (see 'list')
+        #[arg(short, long)]
+        monitor: String,
+        /// File to write; prints a hex dump when omitted
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+    /// Decode the EDID of a monitor or of a saved file
+    #[command(group(
+        ArgGroup::new("source")
+            .required(true)
+            .args(["monitor", "file"])
+    ))]
+    Decode {
+        /// Monitor index, ID, device name or part of its description (see 'list')
         #[arg(short, long)]
-        rate: u32,
+        monitor: Option<String>,
+        /// Raw EDID file, e.g. from 'edid dump' or /sys/class/drm/*/edid
+        file: Option<PathBuf>,
     },
 }
 
-fn main() -> Result<()> {
+#[derive(Subcommand)]
+enum ProfileAction {
+    /// List the profiles in the config file
+    List {
+        /// Output format; json and csv follow a versioned schema
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+    /// Show a profile's settings and what it would change
+    Show { name: String },
+    /// Apply a profile to the connected monitors
+    Apply {
+        name: String,
+        /// Change every monitor or none: check all modes first, apply them
+        /// as one batch and roll back if anything fails
+        #[arg(long)]
+        atomic: bool,
+        #[command(flatten)]
+        change: ChangeOptions,
+    },
+    /// Save every monitor's current resolution and rate as a profile
+    CreateFromCurrent {
+        name: String,
+        /// Replace an existing profile with the same name
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum SnapshotAction {
+    /// Save the current settings of every monitor
+    Save {
+        /// Snapshot name (letters, digits, '-', '_' and '.')
+        name: String,
+        /// Replace an existing snapshot with the same name
+        #[arg(long)]
+        force: bool,
+    },
+    /// Restore a saved snapshot, changing only what differs
+    Restore {
+        name: String,
+        #[command(flatten)]
+        change: ChangeOptions,
+    },
+    /// List saved snapshots
+    List {
+        /// Output format; json and csv follow a versioned schema
+        #[arg(long, value_enum, default_value_t = OutputFormat::Table)]
+        format: OutputFormat,
+    },
+}
+
+/// Options shared by every command that changes modes.
+#[derive(Args, Debug, Clone, Copy)]
+struct ChangeOptions {
+    /// Only check whether the driver would accept the change
+    #[arg(long)]
+    dry_run: bool,
+    /// Keep the new mode without asking for confirmation (for scripts)
+    #[arg(long)]
+    no_confirm: bool,
+    /// Seconds to wait for confirmation before the previous mode is restored
+    #[arg(
+        long,
+        value_name = "SECS",
+        default_value_t = DEFAULT_CONFIRM_TIMEOUT_SECS,
+        value_parser = clap::value_parser!(u64).range(1..)
+    )]
+    confirm_timeout: u64,
+}
+
+fn main() {
     let cli = Cli::parse();
+    let error_format = cli.error_format;
+    // `is` answers "no" with status 1, so its unclassified failures use 2
+    let general_failure = match cli.command {
+        Some(Commands::Is { .. }) => EXIT_USAGE,
+        _ => error::EXIT_FAILURE,
+    };
+
+    if let Err(e) = run(cli) {
+        let code = match error::exit_code(&e) {
+            error::EXIT_FAILURE => general_failure,
+            code => code,
+        };
+        match error_format {
+            ErrorFormat::Text => eprintln!("Error: {:#}", e),
+            ErrorFormat::Json => eprintln!(
+                "{}",
+                serde_json::to_string(&ErrorReport::new(&e, code))
+                    .expect("error reports always serialize")
+            ),
+        }
+        std::process::exit(code);
+    }
+}
+
+fn run(cli: Cli) -> Result<()> {
+    if cli.confirm {
+        if cli.command.is_some() {
+            Cli::command()
+                .error(
+                    clap::error::ErrorKind::ArgumentConflict,
+                    "--confirm can't be combined with a command",
+                )
+                .exit();
+        }
+        return confirm_pending_change();
+    }
+    if let Some(Commands::Harmonize { format, change, .. }) = &cli.command {
+        if *format != OutputFormat::Table && !change.dry_run && !change.no_confirm {
+            Cli::command()
+                .error(
+                    clap::error::ErrorKind::ArgumentConflict,
+                    "--format json and csv need --dry-run or --no-confirm, \
+                     as the confirmation prompt would mix with the output",
+                )
+                .exit();
+        }
+    }
+
+    // Saved EDIDs can be decoded on machines without a usable display backend
+    if let Some(Commands::Edid {
+        action: EdidAction::Decode {
+            file: Some(path), ..
+        },
+    }) = &cli.command
+    {
+        return decode_edid_file(path);
+    }
+
+    let options = BackendOptions {
+        kind: cli.backend,
+        scenario: cli.scenario,
+        sysfs_root: cli.sysfs_root,
+        temporary: cli.temporary,
+    };
+
+    // `is` answers with its exit status rather than with output
+    if let Some(Commands::Is { monitor, rate }) = &cli.command {
+        let backend = backend::create_backend(&options)?;
+        let matches = monitor_is_at_rate(backend.as_ref(), monitor, *rate)?;
+        std::process::exit(if matches { 0 } else { 1 });
+    }
+
+    let backend = backend::create_backend(&options)?;
 
     match cli.command {
-        Some(Commands::Gui) => gui::run_gui()?,
-        Some(Commands::List) => list_monitors()?,
-        Some(Commands::Set { monitor, rate }) => set_monitor_refresh_rate(monitor, rate)?,
-        Some(Commands::SetAll { rate }) => set_all_monitors_refresh_rate(rate)?,
+        Some(Commands::Gui) => gui::run_gui(backend)?,
+        Some(Commands::List { modes, format }) => list_monitors(backend.as_ref(), modes, format)?,
+        Some(Commands::Get {
+            monitor,
+            field,
+            format,
+        }) => get_field(backend.as_ref(), &monitor, field, format)?,
+        Some(Commands::Is { .. }) => unreachable!("handled before the backend is created"),
+        Some(Commands::Set {
+            monitor,
+            rate,
+            mode,
+            resolution,
+            change,
+        }) => match (mode, resolution) {
+            (Some(mode), _) => set_monitor_mode(
+                backend.as_ref(),
+                &monitor,
+                mode.resolution(),
+                Some(mode.refresh_rate.into()),
+                &change,
+            )?,
+            (None, Some(resolution)) => {
+                set_monitor_mode(backend.as_ref(), &monitor, resolution, rate, &change)?
+            }
+            (None, None) => set_monitor_refresh_rate(
+                backend.as_ref(),
+                &monitor,
+                rate.expect("clap requires --rate, --mode or --resolution"),
+                &change,
+            )?,
+        },
+        Some(Commands::SetAll {
+            rate,
+            atomic,
+            change,
+        }) => set_all_monitors_refresh_rate(backend.as_ref(), rate, atomic, &change)?,
+        Some(Commands::Harmonize {
+            prefer,
+            format,
+            change,
+        }) => harmonize(backend.as_ref(), prefer, format, &change)?,
+        Some(Commands::Vrr { state, monitor }) => {
+            set_monitor_vrr(backend.as_ref(), &monitor, state == VrrState::On)?
+        }
+        Some(Commands::Snapshot { action }) => match action {
+            SnapshotAction::Save { name, force } => save_snapshot(backend.as_ref(), &name, force)?,
+            SnapshotAction::Restore { name, change } => {
+                restore_snapshot(backend.as_ref(), &name, &change)?
+            }
+            SnapshotAction::List { format } => list_snapshots(format)?,
+        },
+        Some(Commands::Profile { action }) => match action {
+            ProfileAction::List { format } => list_profiles(format)?,
+            ProfileAction::Show { name } => show_profile(backend.as_ref(), &name)?,
+            ProfileAction::Apply {
+                name,
+                atomic,
+                change,
+            } => apply_profile(backend.as_ref(), &name, atomic, &change)?,
+            ProfileAction::CreateFromCurrent { name, force } => {
+                create_profile_from_current(backend.as_ref(), &name, force)?
+            }
+        },
+        Some(Commands::Edid { action }) => match action {
+            EdidAction::Dump { monitor, output } => {
+                dump_edid(backend.as_ref(), &monitor, output.as_deref())?
+            }
+            EdidAction::Decode { monitor, .. } => decode_monitor_edid(
+                backend.as_ref(),
+                &monitor.expect("clap requires a monitor or a file"),
+            )?,
+        },
         None => {
             // If no command specified, show help and suggest using GUI
             println!("HertzRate - Monitor Refresh Rate Manager");
@@ -52,15 +439,30 @@ fn main() -> Result<()> {
             println!("For GUI mode, run: hertzrate-gui.exe");
             println!("For CLI help, run: hertzrate.exe --help");
             println!();
-            list_monitors()?;
+            list_monitors(backend.as_ref(), false, OutputFormat::Table)?;
         }
     }
 
     Ok(())
 }
 
-fn list_monitors() -> Result<()> {
-    let monitors = Monitor::enumerate_monitors()?;
+fn list_monitors(
+    backend: &dyn DisplayBackend,
+    show_modes: bool,
+    format: OutputFormat,
+) -> Result<()> {
+    let monitors = Monitor::enumerate_monitors(backend)?;
+    match format {
+        OutputFormat::Json => {
+            println!("{}", MonitorList::new(backend, &monitors).to_json()?);
+            return Ok(());
+        }
+        OutputFormat::Csv => {
+            print!("{}", MonitorList::new(backend, &monitors).to_csv()?);
+            return Ok(());
+        }
+        OutputFormat::Table => {}
+    }
 
     if monitors.is_empty() {
         println!("No monitors found.");
@@ -72,6 +474,7 @@ fn list_monitors() -> Result<()> {
 
     for (index, monitor) in monitors.iter().enumerate() {
         println!("Monitor {}: {}", index, monitor.description);
+        println!("  ID: {}", monitor.id);
         println!("  Device: {}", monitor.device_name);
         println!(
             "  Resolution: {}x{}",
@@ -79,63 +482,253 @@ fn list_monitors() -> Result<()> {
         );
         println!("  Current Refresh Rate: {}Hz", monitor.current_refresh_rate);
         println!(
-            "  Available Refresh Rates: {:?}Hz",
-            monitor.available_refresh_rates
+            "  Available Refresh Rates: {}Hz",
+            format_rates(&monitor.available_refresh_rates)
         );
+        match (&monitor.vrr, monitor.vrr_enabled) {
+            (Some(vrr), Some(enabled)) => println!("  VRR: {}, {}", vrr, on_off(enabled)),
+            (Some(vrr), None) => println!("  VRR: {}", vrr),
+            (None, _) => println!("  VRR: not supported"),
+        }
+        if show_modes {
+            println!("  All Modes:");
+            for mode in &monitor.modes {
+                let mut details = mode.details();
+                if mode.same_timing(&monitor.current_mode) {
+                    details.push("current".to_string());
+                }
+                let line = format!(
+                    "    {:<12} {:>7}Hz  {}",
+                    format!(
+                        "{}x{}{}",
+                        mode.width,
+                        mode.height,
+                        if mode.interlaced { "i" } else { "" }
+                    ),
+                    mode.refresh_rate.to_string(),
+                    details.join(", ")
+                );
+                println!("{}", line.trim_end());
+            }
+        }
         println!();
     }
 
     Ok(())
 }
 
-fn set_monitor_refresh_rate(monitor_index: usize, refresh_rate: u32) -> Result<()> {
-    let monitors = Monitor::enumerate_monitors()?;
+fn get_field(
+    backend: &dyn DisplayBackend,
+    selector: &str,
+    field: Field,
+    format: OutputFormat,
+) -> Result<()> {
+    let monitors = Monitor::enumerate_monitors(backend)?;
+    let monitor = Monitor::select(&monitors, selector)?;
+    let vrr = match (&monitor.vrr, monitor.vrr_enabled) {
+        (None, _) => "unsupported",
+        (Some(_), Some(enabled)) => on_off(enabled),
+        (Some(_), None) => "unknown",
+    };
+    // Machine-readable rates are exact, like in 'list --format json'
+    let value = match (field, format) {
+        (Field::Rate, OutputFormat::Table) => monitor.current_refresh_rate.to_string(),
+        (Field::Rate, _) => monitor.current_refresh_rate.to_exact_string(),
+        (Field::Resolution, _) => monitor.current_mode.resolution().to_string(),
+        (Field::Mode, OutputFormat::Table) => monitor.current_mode.to_string(),
+        (Field::Mode, _) => exact_mode(&monitor.current_mode),
+        (Field::Vrr, _) => vrr.to_string(),
+    };
+    let name = field
+        .to_possible_value()
+        .expect("no field is skipped")
+        .get_name()
+        .to_string();
+    match format {
+        OutputFormat::Table => println!("{}", value),
+        OutputFormat::Json => println!(
+            "{}",
+            FieldValue::new(backend, monitor, &name, value).to_json()?
+        ),
+        OutputFormat::Csv => print!(
+            "{}",
+            FieldValue::new(backend, monitor, &name, value).to_csv()?
+        ),
+    }
+    Ok(())
+}
+
+/// Whether the monitor runs at `rate`, matched like `set --rate` matches
+/// modes, so `60` also accepts 59.94Hz and `max` its highest rate.
+fn monitor_is_at_rate(
+    backend: &dyn DisplayBackend,
+    selector: &str,
+    rate: RatePolicy,
+) -> Result<bool> {
+    let monitors = Monitor::enumerate_monitors(backend)?;
+    let monitor = Monitor::select(&monitors, selector)?;
+    Ok(monitor.runs_at(rate))
+}
 
-    if monitor_index >= monitors.len() {
-        return Err(anyhow::anyhow!(
-            "Monitor index {} is out of range. Available monitors: 0-{}",
-            monitor_index,
-            monitors.len() - 1
-        ));
+fn set_monitor_refresh_rate(
+    backend: &dyn DisplayBackend,
+    selector: &str,
+    refresh_rate: RatePolicy,
+    change: &ChangeOptions,
+) -> Result<()> {
+    let monitors = Monitor::enumerate_monitors(backend)?;
+    let monitor = Monitor::select(&monitors, selector)?;
+    let mode = monitor.resolve_refresh_rate(refresh_rate)?;
+    if change.dry_run {
+        return test_monitor_mode(backend, monitor, &mode);
     }
 
-    let monitor = &monitors[monitor_index];
     println!(
-        "Setting refresh rate to {}Hz for monitor: {}",
+        "Setting refresh rate to {} for monitor: {}",
         refresh_rate, monitor.description
     );
 
-    monitor.set_refresh_rate(refresh_rate)?;
+    monitor.apply_mode(backend, &mode)?;
+
+    println!(
+        "✓ Successfully changed refresh rate to {}Hz",
+        mode.refresh_rate
+    );
+    confirm_or_revert(backend, &[(monitor, mode)], &[], change)
+}
+
+fn set_monitor_mode(
+    backend: &dyn DisplayBackend,
+    selector: &str,
+    resolution: Resolution,
+    refresh_rate: Option<RatePolicy>,
+    change: &ChangeOptions,
+) -> Result<()> {
+    let monitors = Monitor::enumerate_monitors(backend)?;
+    let monitor = Monitor::select(&monitors, selector)?;
+    let mode = monitor.resolve_mode(resolution, refresh_rate)?;
+    if change.dry_run {
+        return test_monitor_mode(backend, monitor, &mode);
+    }
+
+    match refresh_rate {
+        Some(rate) => println!(
+            "Setting mode to {}@{} for monitor: {}",
+            resolution, rate, monitor.description
+        ),
+        None => println!(
+            "Setting resolution to {} for monitor: {}",
+            resolution, monitor.description
+        ),
+    }
+
+    monitor.apply_mode(backend, &mode)?;
+
+    println!("✓ Successfully changed mode to {}Hz", mode);
+    confirm_or_revert(backend, &[(monitor, mode)], &[], change)
+}
+
+/// Warns that a dry run can only guess when the backend can't ask the driver.
+fn print_dry_run_caveat(backend: &dyn DisplayBackend) {
+    if !backend.tests_with_driver() {
+        println!(
+            "Note: the {} backend can't ask the driver without applying a mode, so this check \
+             is a heuristic and the driver may still reject the change",
+            backend.name()
+        );
+    }
+}
+
+/// Prints what switching `monitor` to `mode` would change and whether the
+/// driver accepts it, without applying anything.
+fn test_monitor_mode(
+    backend: &dyn DisplayBackend,
+    monitor: &Monitor,
+    mode: &DisplayMode,
+) -> Result<()> {
+    if mode.same_timing(&monitor.current_mode) {
+        println!(
+            "Dry run: monitor {} already runs {}Hz",
+            monitor.description, mode
+        );
+    } else {
+        println!(
+            "Dry run: monitor {} would change from {}Hz to {}Hz",
+            monitor.description, monitor.current_mode, mode
+        );
+    }
+    print_dry_run_caveat(backend);
+
+    monitor.test_mode(backend, mode)?;
 
-    println!("✓ Successfully changed refresh rate to {}Hz", refresh_rate);
+    println!("✓ The driver accepts {}Hz; nothing was changed", mode);
     Ok(())
 }
 
-fn set_all_monitors_refresh_rate(refresh_rate: u32) -> Result<()> {
-    let monitors = Monitor::enumerate_monitors()?;
+fn set_all_monitors_refresh_rate(
+    backend: &dyn DisplayBackend,
+    refresh_rate: RatePolicy,
+    atomic: bool,
+    change: &ChangeOptions,
+) -> Result<()> {
+    let monitors = Monitor::enumerate_monitors(backend)?;
 
     if monitors.is_empty() {
         println!("No monitors found.");
         return Ok(());
     }
 
-    println!(
-        "Setting refresh rate to {}Hz for all monitors...",
-        refresh_rate
-    );
+    if change.dry_run {
+        println!(
+            "Dry run: checking {} for all monitors, nothing will be changed...",
+            refresh_rate
+        );
+        print_dry_run_caveat(backend);
+    } else {
+        println!(
+            "Setting refresh rate to {} for all monitors...",
+            refresh_rate
+        );
+    }
+
+    if atomic {
+        let targets = monitors
+            .iter()
+            .map(|monitor| (monitor, monitor.resolve_refresh_rate(refresh_rate)))
+            .collect();
+        return apply_atomically(backend, targets, Vec::new(), change);
+    }
 
     let mut success_count = 0;
+    let mut unchanged = 0;
     let mut errors = Vec::new();
+    let mut changed = Vec::new();
 
     for (index, monitor) in monitors.iter().enumerate() {
-        match monitor.set_refresh_rate(refresh_rate) {
-            Ok(()) => {
-                println!("✓ Monitor {}: {} - Success", index, monitor.description);
+        let mode = monitor.resolve_refresh_rate(refresh_rate);
+        match switch_mode(backend, monitor, mode, change) {
+            Ok(mode) if mode.same_timing(&monitor.current_mode) => {
+                println!(
+                    "✓ Monitor {}: {} - already {}Hz",
+                    index, monitor.description, mode
+                );
+                unchanged += 1;
+            }
+            Ok(mode) if change.dry_run => {
+                println!(
+                    "✓ Monitor {}: {} - {}Hz -> {}Hz accepted",
+                    index, monitor.description, monitor.current_mode, mode
+                );
                 success_count += 1;
             }
+            Ok(mode) => {
+                println!("✓ Monitor {}: {} - {}Hz", index, monitor.description, mode);
+                success_count += 1;
+                changed.push((monitor, mode));
+            }
             Err(e) => {
                 println!(
-                    "✗ Monitor {}: {} - Failed: {}",
+                    "✗ Monitor {}: {} - Failed: {:#}",
                     index, monitor.description, e
                 );
                 errors.push((index, e));
@@ -144,18 +737,979 @@ fn set_all_monitors_refresh_rate(refresh_rate: u32) -> Result<()> {
     }
 
     println!();
+    if unchanged == monitors.len() {
+        println!(
+            "Summary: all {} monitors already run {}",
+            unchanged, refresh_rate
+        );
+    } else {
+        println!(
+            "Summary: {}/{} monitors {}{}",
+            success_count,
+            monitors.len() - unchanged,
+            if change.dry_run {
+                "would be updated"
+            } else {
+                "updated successfully"
+            },
+            if unchanged > 0 {
+                format!(", {} unchanged", unchanged)
+            } else {
+                String::new()
+            }
+        );
+    }
+
+    if !errors.is_empty() {
+        println!("Errors occurred for {} monitor(s):", errors.len());
+        for (index, error) in &errors {
+            println!("  Monitor {}: {:#}", index, error);
+        }
+    }
+
+    if !change.dry_run {
+        confirm_or_revert(backend, &changed, &[], change)?;
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else if change.dry_run {
+        Err(anyhow!(
+            "{} monitor(s) would not accept the new refresh rate",
+            errors.len()
+        ))
+    } else {
+        Err(anyhow!(
+            "{} monitor(s) could not be switched to the new refresh rate",
+            errors.len()
+        ))
+    }
+}
+
+fn harmonize(
+    backend: &dyn DisplayBackend,
+    preference: Preference,
+    format: OutputFormat,
+    change: &ChangeOptions,
+) -> Result<()> {
+    if format != OutputFormat::Table {
+        return harmonize_report(backend, preference, format, change);
+    }
+
+    let monitors = Monitor::enumerate_monitors(backend)?;
+    if monitors.len() < 2 {
+        println!("Harmonizing needs at least two monitors");
+        return Ok(());
+    }
+
+    let harmony = Harmony::new(&monitors);
+    let common = harmony.common();
+    if common.is_empty() {
+        println!("Rates every monitor supports: none");
+    } else {
+        println!("Rates every monitor supports: {}", format_rates(&common));
+    }
+    let multiples = harmony.multiples();
+    if !multiples.is_empty() {
+        println!("Integer-multiple combinations:");
+        for rates in multiples {
+            let rates: Vec<String> = monitors
+                .iter()
+                .zip(rates)
+                .map(|(monitor, rate)| format!("{}: {}Hz", monitor.description, rate))
+                .collect();
+            println!("  {}", rates.join("; "));
+        }
+    }
+
+    let choice = harmony.choose(preference)?;
+    if let Some((monitor, reason)) = &choice.limiting {
+        println!("Limiting monitor: {} ({})", monitor.description, reason);
+    }
+    println!();
+    if change.dry_run {
+        println!(
+            "Dry run: checking the best common rates ({}), nothing will be changed...",
+            preference
+        );
+        print_dry_run_caveat(backend);
+    } else {
+        println!("Switching to the best common rates ({})...", preference);
+    }
+
+    let targets = choice
+        .targets
+        .into_iter()
+        .map(|(monitor, mode)| (monitor, Ok(mode)))
+        .collect();
+    let (changed, errors) = apply_targets(backend, targets, change);
+    if !change.dry_run {
+        confirm_or_revert(backend, &changed, &[], change)?;
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "{} monitor(s) could not be switched to the common rate",
+            errors.len()
+        ))
+    }
+}
+
+/// `harmonize` for `--format json` and `csv`: switches without printing
+/// progress, then prints one report. Runs without a confirmation prompt, so
+/// `change` is a dry run or has `no_confirm` set.
+fn harmonize_report(
+    backend: &dyn DisplayBackend,
+    preference: Preference,
+    format: OutputFormat,
+    change: &ChangeOptions,
+) -> Result<()> {
+    let monitors = Monitor::enumerate_monitors(backend)?;
+    let mut report = HarmonizeReport::new(backend, preference, change.dry_run);
+    let mut failed = 0;
+    // With fewer than two monitors there is nothing to harmonize and the
+    // report stays empty, like the table's one-line notice
+    if monitors.len() >= 2 {
+        let harmony = Harmony::new(&monitors);
+        report.common_rates = harmony.common();
+        report.multiples = harmony.multiples();
+        let choice = harmony.choose(preference)?;
+        report.limiting = choice.limiting.map(|(monitor, reason)| LimitingMonitor {
+            id: monitor.id.to_string(),
+            device_name: &monitor.device_name,
+            reason,
+        });
+        for (monitor, mode) in choice.targets {
+            let (status, error) = match switch_mode(backend, monitor, Ok(mode), change) {
+                Ok(_) if mode.same_timing(&monitor.current_mode) => {
+                    (HarmonizeStatus::Unchanged, None)
+                }
+                Ok(_) if change.dry_run => (HarmonizeStatus::Accepted, None),
+                Ok(_) => (HarmonizeStatus::Changed, None),
+                Err(e) => {
+                    failed += 1;
+                    (HarmonizeStatus::Failed, Some(format!("{:#}", e)))
+                }
+            };
+            report.monitors.push(HarmonizeResult {
+                id: monitor.id.to_string(),
+                device_name: &monitor.device_name,
+                description: &monitor.description,
+                from: monitor.current_mode,
+                to: mode,
+                status,
+                error,
+            });
+        }
+    }
+
+    if format == OutputFormat::Json {
+        println!("{}", report.to_json()?);
+    } else {
+        print!("{}", report.to_csv()?);
+    }
+    if failed == 0 {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "{} monitor(s) could not be switched to the common rate",
+            failed
+        ))
+    }
+}
+
+fn set_monitor_vrr(backend: &dyn DisplayBackend, selector: &str, enabled: bool) -> Result<()> {
+    let monitors = Monitor::enumerate_monitors(backend)?;
+    let monitor = Monitor::select(&monitors, selector)?;
+    if monitor.vrr_enabled == Some(enabled) {
+        println!(
+            "✓ VRR is already {} for monitor {}",
+            on_off(enabled),
+            monitor.description
+        );
+        return Ok(());
+    }
+
     println!(
-        "Summary: {}/{} monitors updated successfully",
-        success_count,
-        monitors.len()
+        "Turning VRR {} for monitor: {}",
+        on_off(enabled),
+        monitor.description
     );
 
+    monitor.set_vrr(backend, enabled)?;
+
+    // Some drivers accept the request but keep their own setting
+    if let Ok(Some(actual)) = backend.vrr_enabled(&monitor.device_name) {
+        if actual != enabled {
+            return Err(anyhow!(
+                "The driver accepted the change but VRR is still {} for monitor {}",
+                on_off(actual),
+                monitor.description
+            ));
+        }
+    }
+    println!("✓ Successfully turned VRR {}", on_off(enabled));
+    Ok(())
+}
+
+fn save_snapshot(backend: &dyn DisplayBackend, name: &str, force: bool) -> Result<()> {
+    let snapshot = Snapshot::capture(backend)?;
+    let path = snapshot.save(name, force)?;
+
+    println!(
+        "✓ Saved {} monitor(s) to snapshot '{}'",
+        snapshot.monitors.len(),
+        name
+    );
+    for monitor in &snapshot.monitors {
+        print!(
+            "  {}: {}x{}@{}Hz",
+            monitor, monitor.width, monitor.height, monitor.refresh_rate
+        );
+        if let Some([x, y]) = monitor.position {
+            print!(" at {:+}{:+}", x, y);
+        }
+        if let Some(orientation) = monitor.orientation {
+            print!(", {}", orientation);
+        }
+        if let Some(enabled) = monitor.vrr {
+            print!(", VRR {}", on_off(enabled));
+        }
+        println!();
+    }
+    println!("  File: {}", path.display());
+    Ok(())
+}
+
+fn restore_snapshot(
+    backend: &dyn DisplayBackend,
+    name: &str,
+    change: &ChangeOptions,
+) -> Result<()> {
+    let snapshot = Snapshot::load(name)?;
+    if snapshot.backend != backend.name() {
+        println!(
+            "Note: snapshot '{}' was taken with the {} backend, not {}",
+            name,
+            snapshot.backend,
+            backend.name()
+        );
+    }
+
+    let monitors = Monitor::enumerate_monitors(backend)?;
+    let plan = snapshot.plan(&monitors);
+    for missing in &plan.missing {
+        println!("! {} is not connected, skipping it", missing);
+    }
+    for (monitor, e) in &plan.unavailable {
+        println!("! Skipping {}: {:#}", monitor, e);
+    }
+    if plan.steps.is_empty() {
+        println!(
+            "✓ Every connected monitor already matches snapshot '{}'",
+            name
+        );
+        return Ok(());
+    }
+
+    if change.dry_run {
+        println!("Dry run: restoring snapshot '{}' would change:", name);
+        print_dry_run_caveat(backend);
+        let mut rejected = 0;
+        for step in &plan.steps {
+            match step.test(backend) {
+                Ok(()) => println!("✓ {}", step),
+                Err(e) => {
+                    println!("✗ {} - {:#}", step, e);
+                    rejected += 1;
+                }
+            }
+        }
+        if rejected > 0 {
+            return Err(anyhow!("{} change(s) would be rejected", rejected));
+        }
+        println!("Nothing was changed");
+        return Ok(());
+    }
+
+    let previous = Snapshot::from_monitors(backend, &monitors);
+    println!("Restoring snapshot '{}'...", name);
+    let mut errors = Vec::new();
+    for step in &plan.steps {
+        match step.apply(backend) {
+            Ok(()) => println!("✓ {}", step),
+            Err(e) => {
+                println!("✗ {:#}", e);
+                errors.push(e);
+            }
+        }
+    }
+
+    let applied = plan.steps.len() - errors.len();
+    if applied > 0 && !change.no_confirm {
+        if wait_for_confirmation(change.confirm_timeout) {
+            println!("✓ Keeping the new settings");
+        } else {
+            println!("Restoring the previous settings...");
+            let (_, revert_errors) = previous.restore(backend)?;
+            for e in &revert_errors {
+                println!("✗ {:#}", e);
+            }
+            return Err(if revert_errors.is_empty() {
+                anyhow!("The new settings were not confirmed and have been reverted")
+            } else {
+                anyhow!(
+                    "The new settings were not confirmed and {} monitor(s) could not be restored",
+                    revert_errors.len()
+                )
+            });
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "{} of {} change(s) could not be applied",
+            errors.len(),
+            plan.steps.len()
+        ))
+    }
+}
+
+fn list_snapshots(format: OutputFormat) -> Result<()> {
+    let list = SnapshotList::load()?;
+    match format {
+        OutputFormat::Json => {
+            println!("{}", list.to_json()?);
+            return Ok(());
+        }
+        OutputFormat::Csv => {
+            print!("{}", list.to_csv()?);
+            return Ok(());
+        }
+        OutputFormat::Table => {}
+    }
+
+    if list.snapshots.is_empty() {
+        println!("No snapshots saved. Create one with 'hertzrate snapshot save <name>'.");
+        return Ok(());
+    }
+
+    println!("Saved Snapshots:");
+    for entry in list.snapshots {
+        match (entry.backend, entry.monitors, entry.error) {
+            (Some(backend), Some(monitors), _) => println!(
+                "  {} ({} monitor(s), {} backend)",
+                entry.name, monitors, backend
+            ),
+            (_, _, error) => println!(
+                "  {} (unreadable: {})",
+                entry.name,
+                error.unwrap_or_default()
+            ),
+        }
+    }
+    Ok(())
+}
+
+fn load_profile(name: &str) -> Result<Profile> {
+    Config::load()?.profiles.remove(name).ok_or_else(|| {
+        anyhow!(
+            "Profile '{}' not found (use 'profile list' to see configured profiles)",
+            name
+        )
+    })
+}
+
+fn list_profiles(format: OutputFormat) -> Result<()> {
+    let config = Config::load()?;
+    match format {
+        OutputFormat::Json => {
+            println!("{}", ProfileList::new(&config).to_json()?);
+            return Ok(());
+        }
+        OutputFormat::Csv => {
+            print!("{}", ProfileList::new(&config).to_csv()?);
+            return Ok(());
+        }
+        OutputFormat::Table => {}
+    }
+
+    if config.profiles.is_empty() {
+        println!(
+            "No profiles configured in {}. Create one with 'hertzrate profile create-from-current <name>'.",
+            Config::path()?.display()
+        );
+        return Ok(());
+    }
+
+    println!("Profiles:");
+    for (name, profile) in &config.profiles {
+        println!("  {}", name);
+        for line in profile.describe() {
+            println!("    {}", line);
+        }
+    }
+    Ok(())
+}
+
+fn show_profile(backend: &dyn DisplayBackend, name: &str) -> Result<()> {
+    let profile = load_profile(name)?;
+    println!("Profile '{}':", name);
+    for line in profile.describe() {
+        println!("  {}", line);
+    }
+
+    let monitors = Monitor::enumerate_monitors(backend)?;
+    let plan = profile.plan(&monitors);
+    println!();
+    println!("On the connected monitors:");
+    if plan.targets.is_empty() && plan.vrr.is_empty() {
+        println!("  No connected monitor is affected");
+    }
+    for (monitor, mode) in &plan.targets {
+        match mode {
+            Ok(mode) if mode.same_timing(&monitor.current_mode) => {
+                println!("  {}: already {}Hz", monitor.description, mode)
+            }
+            Ok(mode) => println!(
+                "  {}: {}Hz -> {}Hz",
+                monitor.description, monitor.current_mode, mode
+            ),
+            Err(e) => println!("  {}: ✗ {:#}", monitor.description, e),
+        }
+    }
+    for &(monitor, enabled) in &plan.vrr {
+        match monitor.vrr_enabled {
+            Some(current) if current == enabled => {
+                println!("  {}: VRR already {}", monitor.description, on_off(enabled))
+            }
+            Some(current) => println!(
+                "  {}: VRR {} -> {}",
+                monitor.description,
+                on_off(current),
+                on_off(enabled)
+            ),
+            None => println!("  {}: VRR {}", monitor.description, on_off(enabled)),
+        }
+    }
+    for entry in &plan.unmatched {
+        println!("  {}: not connected", entry);
+    }
+    Ok(())
+}
+
+fn apply_profile(
+    backend: &dyn DisplayBackend,
+    name: &str,
+    atomic: bool,
+    change: &ChangeOptions,
+) -> Result<()> {
+    let profile = load_profile(name)?;
+    let monitors = Monitor::enumerate_monitors(backend)?;
+    let plan = profile.plan(&monitors);
+
+    for entry in &plan.unmatched {
+        println!("! {} is not connected, skipping it", entry);
+    }
+    if plan.targets.is_empty() && plan.vrr.is_empty() {
+        println!("Profile '{}' does not affect any connected monitor", name);
+        return Ok(());
+    }
+
+    if change.dry_run {
+        println!(
+            "Dry run: checking profile '{}', nothing will be changed...",
+            name
+        );
+        print_dry_run_caveat(backend);
+    } else {
+        println!("Applying profile '{}'...", name);
+    }
+
+    if atomic {
+        return apply_atomically(backend, plan.targets, plan.vrr, change)
+            .with_context(|| format!("Profile '{}' was not applied", name));
+    }
+
+    let (changed, mut errors) = apply_targets(backend, plan.targets, change);
+
+    // VRR can't be tested in advance, so a dry run only reports it
+    let mut toggles = Vec::new();
+    for (monitor, enabled) in plan.vrr {
+        if monitor.vrr_enabled == Some(enabled) {
+            println!(
+                "✓ {} - VRR already {}",
+                monitor.description,
+                on_off(enabled)
+            );
+        } else if change.dry_run {
+            println!(
+                "✓ {} - VRR would be turned {}",
+                monitor.description,
+                on_off(enabled)
+            );
+        } else {
+            match monitor.set_vrr(backend, enabled) {
+                Ok(()) => {
+                    println!("✓ {} - VRR {}", monitor.description, on_off(enabled));
+                    toggles.push((monitor, enabled));
+                }
+                Err(e) => {
+                    println!("✗ {} - Failed: {:#}", monitor.description, e);
+                    errors.push(e);
+                }
+            }
+        }
+    }
+
+    if !change.dry_run {
+        confirm_or_revert(backend, &changed, &toggles, change)?;
+    }
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "Profile '{}' could not be applied to {} monitor(s)",
+            name,
+            errors.len()
+        ))
+    }
+}
+
+/// Switches each monitor to its mode, or only tests the modes on a dry run,
+/// printing a line per monitor. Returns the monitors that changed, with their
+/// new modes, and the failures.
+fn apply_targets<'a>(
+    backend: &dyn DisplayBackend,
+    targets: Vec<(&'a Monitor, Result<DisplayMode>)>,
+    change: &ChangeOptions,
+) -> (Vec<(&'a Monitor, DisplayMode)>, Vec<anyhow::Error>) {
+    let mut errors = Vec::new();
+    let mut changed = Vec::new();
+    for (monitor, mode) in targets {
+        match switch_mode(backend, monitor, mode, change) {
+            Ok(mode) if mode.same_timing(&monitor.current_mode) => {
+                println!("✓ {} - already {}Hz", monitor.description, mode)
+            }
+            Ok(mode) if change.dry_run => println!(
+                "✓ {} - {}Hz -> {}Hz accepted",
+                monitor.description, monitor.current_mode, mode
+            ),
+            Ok(mode) => {
+                println!("✓ {} - {}Hz", monitor.description, mode);
+                changed.push((monitor, mode));
+            }
+            Err(e) => {
+                println!("✗ {} - Failed: {:#}", monitor.description, e);
+                errors.push(e);
+            }
+        }
+    }
+    (changed, errors)
+}
+
+/// Switches one monitor to `mode` unless it already runs it, or only tests
+/// the mode on a dry run.
+fn switch_mode(
+    backend: &dyn DisplayBackend,
+    monitor: &Monitor,
+    mode: Result<DisplayMode>,
+    change: &ChangeOptions,
+) -> Result<DisplayMode> {
+    mode.and_then(|mode| {
+        if mode.same_timing(&monitor.current_mode) {
+            Ok(mode)
+        } else if change.dry_run {
+            monitor.test_mode(backend, &mode).map(|_| mode)
+        } else {
+            monitor.apply_mode(backend, &mode).map(|_| mode)
+        }
+    })
+}
+
+/// Changes every monitor or none: all modes are resolved and tested before
+/// anything changes, then applied as one batch followed by the VRR changes.
+/// When one of them fails, the monitors already changed get their previous
+/// settings back.
+fn apply_atomically(
+    backend: &dyn DisplayBackend,
+    targets: Vec<(&Monitor, Result<DisplayMode>)>,
+    vrr: Vec<(&Monitor, bool)>,
+    change: &ChangeOptions,
+) -> Result<()> {
+    let mut batch = Vec::new();
+    let mut errors = Vec::new();
+    for (monitor, mode) in targets {
+        match mode {
+            Ok(mode) if mode.same_timing(&monitor.current_mode) => {
+                println!("✓ {} - already {}Hz", monitor.description, mode)
+            }
+            Ok(mode) => batch.push((monitor, mode)),
+            Err(e) => {
+                println!("✗ {} - {:#}", monitor.description, e);
+                errors.push(e);
+            }
+        }
+    }
+    let mut toggles = Vec::new();
+    for (monitor, enabled) in vrr {
+        if monitor.vrr_enabled == Some(enabled) {
+            println!(
+                "✓ {} - VRR already {}",
+                monitor.description,
+                on_off(enabled)
+            );
+            continue;
+        }
+        match monitor.check_vrr(enabled) {
+            Ok(()) => toggles.push((monitor, enabled)),
+            Err(e) => {
+                println!("✗ {} - {:#}", monitor.description, e);
+                errors.push(e);
+            }
+        }
+    }
     if !errors.is_empty() {
-        println!("Errors occurred for {} monitor(s):", errors.len());
-        for (index, error) in errors {
-            println!("  Monitor {}: {}", index, error);
+        let count = errors.len();
+        return Err(errors.swap_remove(0).context(format!(
+            "Nothing was changed because {} monitor(s) can't take the new settings",
+            count
+        )));
+    }
+    if batch.is_empty() && toggles.is_empty() {
+        return Ok(());
+    }
+
+    if !batch.is_empty() {
+        Monitor::test_modes(backend, &batch).context("Nothing was changed")?;
+    }
+    if change.dry_run {
+        for (monitor, mode) in &batch {
+            println!(
+                "✓ {} - {}Hz -> {}Hz accepted",
+                monitor.description, monitor.current_mode, mode
+            );
+        }
+        // VRR can't be tested in advance, so a dry run only reports it
+        for (monitor, enabled) in &toggles {
+            println!(
+                "✓ {} - VRR would be turned {}",
+                monitor.description,
+                on_off(*enabled)
+            );
+        }
+        println!("✓ The driver accepts the modes together; nothing was changed");
+        return Ok(());
+    }
+
+    if !batch.is_empty() {
+        if let Err(e) = Monitor::apply_modes(backend, &batch) {
+            // Not every backend can undo a failed batch, and none notices a
+            // driver that accepted it without changing every monitor
+            println!("✗ {:#}", e);
+            println!("Rolling back...");
+            roll_back(backend, &batch, &[]);
+            return Err(e.context("The changes were rolled back"));
+        }
+        for (monitor, mode) in &batch {
+            println!("✓ {} - {}Hz", monitor.description, mode);
+        }
+    }
+    for (index, &(monitor, enabled)) in toggles.iter().enumerate() {
+        if let Err(e) = monitor.set_vrr(backend, enabled) {
+            println!("✗ {} - Failed: {:#}", monitor.description, e);
+            println!("Rolling back...");
+            roll_back(backend, &batch, &toggles[..index]);
+            return Err(e.context("The changes were rolled back"));
+        }
+        println!("✓ {} - VRR {}", monitor.description, on_off(enabled));
+    }
+
+    confirm_or_revert(backend, &batch, &toggles, change)
+}
+
+/// Undoes an atomic change that failed after `batch` was applied and the VRR
+/// `toggles` were made, printing what was restored.
+fn roll_back(
+    backend: &dyn DisplayBackend,
+    batch: &[(&Monitor, DisplayMode)],
+    toggles: &[(&Monitor, bool)],
+) {
+    for &(monitor, enabled) in toggles.iter().rev() {
+        match monitor.set_vrr(backend, !enabled) {
+            Ok(()) => println!(
+                "✓ Turned VRR {} again for {}",
+                on_off(!enabled),
+                monitor.description
+            ),
+            Err(e) => println!("✗ {:#}", e),
+        }
+    }
+    // Only the monitors that still run another mode are switched back; one
+    // that can't be asked anymore, e.g. unplugged, can't be restored either
+    let mut previous: Vec<(&Monitor, DisplayMode)> = Vec::new();
+    for (monitor, _) in batch {
+        match backend.current_mode(&monitor.device_name) {
+            Ok(mode) if mode.same_timing(&monitor.current_mode) => println!(
+                "✓ {} is back at {}Hz",
+                monitor.description, monitor.current_mode
+            ),
+            Ok(_) => previous.push((*monitor, monitor.current_mode)),
+            Err(e) => println!("✗ {} - {:#}", monitor.description, e),
+        }
+    }
+    if previous.is_empty() {
+        return;
+    }
+    match Monitor::apply_modes(backend, &previous) {
+        Ok(()) => {
+            for (monitor, mode) in &previous {
+                println!("✓ Restored {} to {}Hz", monitor.description, mode);
+            }
+        }
+        Err(e) => println!("✗ {:#}", e),
+    }
+}
+
+fn create_profile_from_current(
+    backend: &dyn DisplayBackend,
+    name: &str,
+    force: bool,
+) -> Result<()> {
+    let mut config = Config::load()?;
+    if config.profiles.contains_key(name) && !force {
+        return Err(anyhow!(
+            "Profile '{}' already exists; use --force to replace it",
+            name
+        ));
+    }
+
+    let monitors = Monitor::enumerate_monitors(backend)?;
+    let profile = Profile::from_monitors(&monitors);
+    let lines = profile.describe();
+    config.profiles.insert(name.to_string(), profile);
+    let path = config.save()?;
+
+    println!("✓ Saved profile '{}' to {}", name, path.display());
+    for line in lines {
+        println!("  {}", line);
+    }
+    Ok(())
+}
+
+/// The raw EDID of the monitor `selector` picks.
+fn read_monitor_edid(backend: &dyn DisplayBackend, selector: &str) -> Result<(Monitor, Vec<u8>)> {
+    let monitors = Monitor::enumerate_monitors(backend)?;
+    let monitor = Monitor::select(&monitors, selector)?;
+    match backend.edid(&monitor.device_name)? {
+        Some(edid) => Ok((monitor.clone(), edid)),
+        None => Err(anyhow!(
+            "No EDID available for {} ({}) from the {} backend",
+            monitor.description,
+            monitor.device_name,
+            backend.name()
+        )),
+    }
+}
+
+fn dump_edid(backend: &dyn DisplayBackend, selector: &str, output: Option<&Path>) -> Result<()> {
+    let (monitor, edid) = read_monitor_edid(backend, selector)?;
+    match output {
+        Some(path) => {
+            fs::write(path, &edid)
+                .with_context(|| format!("Failed to write {}", path.display()))?;
+            println!(
+                "✓ Saved the {}-byte EDID of {} to {}",
+                edid.len(),
+                monitor.description,
+                path.display()
+            );
+        }
+        None => {
+            for (index, line) in edid.chunks(16).enumerate() {
+                let bytes: Vec<String> = line.iter().map(|byte| format!("{:02x}", byte)).collect();
+                println!("{:08x}: {}", index * 16, bytes.join(" "));
+            }
         }
     }
+    Ok(())
+}
+
+fn decode_monitor_edid(backend: &dyn DisplayBackend, selector: &str) -> Result<()> {
+    let (monitor, edid) = read_monitor_edid(backend, selector)?;
+    println!("{} ({})", monitor.description, monitor.device_name);
+    print!("{}", edid::report::report(&edid)?);
+    Ok(())
+}
 
+fn decode_edid_file(path: &Path) -> Result<()> {
+    let edid = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    println!("{}", path.display());
+    print!("{}", edid::report::report(&edid)?);
     Ok(())
 }
+
+/// Asks whether to keep the modes just applied and the VRR `toggles` just
+/// made, and restores the previous settings unless the user confirms within
+/// the timeout.
+fn confirm_or_revert(
+    backend: &dyn DisplayBackend,
+    changed: &[(&Monitor, DisplayMode)],
+    toggles: &[(&Monitor, bool)],
+    change: &ChangeOptions,
+) -> Result<()> {
+    // Nothing to confirm when every monitor kept its mode
+    let changed: Vec<&Monitor> = changed
+        .iter()
+        .filter(|(monitor, mode)| !mode.same_timing(&monitor.current_mode))
+        .map(|(monitor, _)| *monitor)
+        .collect();
+    if change.no_confirm || (changed.is_empty() && toggles.is_empty()) {
+        return Ok(());
+    }
+
+    if wait_for_confirmation(change.confirm_timeout) {
+        println!("✓ Keeping the new settings");
+        return Ok(());
+    }
+
+    println!("Restoring the previous settings...");
+    let mut errors = Vec::new();
+    for &(monitor, enabled) in toggles.iter().rev() {
+        match monitor.set_vrr(backend, !enabled) {
+            Ok(()) => println!(
+                "✓ Turned VRR {} again for {}",
+                on_off(!enabled),
+                monitor.description
+            ),
+            Err(e) => {
+                println!("✗ {:#}", e);
+                errors.push(e);
+            }
+        }
+    }
+    for monitor in changed {
+        match monitor.apply_mode(backend, &monitor.current_mode) {
+            Ok(()) => println!(
+                "✓ Restored {} to {}Hz",
+                monitor.description, monitor.current_mode
+            ),
+            Err(e) => {
+                println!("✗ {:#}", e);
+                errors.push(e);
+            }
+        }
+    }
+
+    if errors.is_empty() {
+        Err(anyhow!(
+            "The new settings were not confirmed and have been reverted"
+        ))
+    } else {
+        Err(anyhow!(
+            "The new settings were not confirmed and {} setting(s) could not be restored",
+            errors.len()
+        ))
+    }
+}
+
+/// Counts down `timeout` seconds waiting for Enter on stdin or for
+/// `hertzrate --confirm` from elsewhere. Answering `n` reverts right away.
+fn wait_for_confirmation(timeout: u64) -> bool {
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        let mut answer = String::new();
+        if io::stdin()
+            .read_line(&mut answer)
+            .is_ok_and(|read| read > 0)
+        {
+            let _ = sender.send(answer);
+        }
+    });
+    let pending = PendingConfirmation::start();
+    let mut stdin_open = true;
+
+    for remaining in (1..=timeout).rev() {
+        if stdin_open {
+            print!(
+                "\rKeep these settings? Reverting in {:>2}s [Enter = keep, n = revert] ",
+                remaining
+            );
+        } else {
+            print!(
+                "\rKeep these settings? Reverting in {:>2}s [hertzrate --confirm = keep] ",
+                remaining
+            );
+        }
+        io::stdout().flush().ok();
+
+        let answer = if stdin_open {
+            receiver.recv_timeout(Duration::from_secs(1))
+        } else {
+            thread::sleep(Duration::from_secs(1));
+            Err(RecvTimeoutError::Disconnected)
+        };
+        match answer {
+            Ok(answer) => {
+                println!();
+                return !answer.trim().eq_ignore_ascii_case("n");
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => stdin_open = false,
+        }
+        if pending.as_ref().is_some_and(PendingConfirmation::confirmed) {
+            println!();
+            println!("Confirmed by hertzrate --confirm");
+            return true;
+        }
+    }
+
+    println!();
+    false
+}
+
+/// The marker file a waiting `hertzrate` leaves in the config directory;
+/// `hertzrate --confirm` deletes it to keep the change.
+struct PendingConfirmation(PathBuf);
+
+impl PendingConfirmation {
+    /// Creates the marker, or returns `None` when the config directory isn't
+    /// writable, in which case only stdin can confirm.
+    fn start() -> Option<Self> {
+        let path = pending_confirmation_path().ok()?;
+        fs::create_dir_all(path.parent()?).ok()?;
+        fs::write(&path, format!("{}\n", std::process::id())).ok()?;
+        Some(Self(path))
+    }
+
+    fn confirmed(&self) -> bool {
+        !self.0.exists()
+    }
+}
+
+impl Drop for PendingConfirmation {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn pending_confirmation_path() -> Result<PathBuf> {
+    Ok(paths::config_dir()?.join("pending-confirmation"))
+}
+
+/// Confirms the change a running `hertzrate` is counting down on.
+fn confirm_pending_change() -> Result<()> {
+    let path = pending_confirmation_path()?;
+    match fs::remove_file(&path) {
+        Ok(()) => {
+            println!("✓ Confirmed the pending change");
+            Ok(())
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            Err(anyhow!("No change is waiting for confirmation"))
+        }
+        Err(e) => Err(e).with_context(|| format!("Failed to remove {}", path.display())),
+    }
+}
@@ -1,178 +1,671 @@
-use anyhow::{anyhow, Result};
-use std::ffi::OsString;
-use std::os::windows::ffi::OsStringExt;
-use windows::core::PCWSTR;
-use windows::Win32::Graphics::Gdi::{
-    ChangeDisplaySettingsW, EnumDisplayDevicesW, EnumDisplaySettingsW, CDS_UPDATEREGISTRY,
-    DEVMODEW, DISPLAY_DEVICEW, DISP_CHANGE_SUCCESSFUL, DM_DISPLAYFREQUENCY, DM_PELSHEIGHT,
-    DM_PELSWIDTH, ENUM_CURRENT_SETTINGS, ENUM_DISPLAY_SETTINGS_MODE,
+use crate::backend::{
+    DisplayBackend, DisplayMode, MonitorIdentity, Placement, Resolution, VrrCapability,
 };
+use crate::error::HertzRateError;
+use crate::refresh_rate::{RatePolicy, RefreshRate};
+use anyhow::{Context, Result};
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use std::fmt;
+
+/// How long the CLI and GUI wait for a new mode to be confirmed before
+/// restoring the previous one.
+pub const DEFAULT_CONFIRM_TIMEOUT_SECS: u64 = 15;
+
+/// Names a monitor independently of enumeration order: its EDID identity,
+/// e.g. `DEL-41A8-5QF8K93`, with the connector appended as `@DP-1` when
+/// another connected monitor reports the same identity. Monitors without an
+/// identity are named by their connector alone.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MonitorId {
+    identity: Option<MonitorIdentity>,
+    connector: Option<String>,
+}
 
 #[derive(Debug, Clone)]
 pub struct Monitor {
+    pub id: MonitorId,
     pub device_name: String,
     pub description: String,
     pub current_width: u32,
     pub current_height: u32,
-    pub current_refresh_rate: u32,
-    pub available_refresh_rates: Vec<u32>,
+    pub current_refresh_rate: RefreshRate,
+    pub available_refresh_rates: Vec<RefreshRate>,
+    pub current_mode: DisplayMode,
+    /// Every mode the monitor reports, largest resolution first
+    pub modes: Vec<DisplayMode>,
+    /// Desktop position and orientation, where the backend reports them
+    pub placement: Option<Placement>,
+    /// Variable refresh rate support; `None` for fixed-rate monitors and
+    /// where nothing says otherwise
+    pub vrr: Option<VrrCapability>,
+    /// Whether VRR is currently on, where the backend reports it
+    pub vrr_enabled: Option<bool>,
 }
 
 impl Monitor {
-    pub fn enumerate_monitors() -> Result<Vec<Monitor>> {
-        let mut monitors = Vec::new();
-        let mut device_index = 0;
-
-        loop {
-            let mut display_device = DISPLAY_DEVICEW {
-                cb: std::mem::size_of::<DISPLAY_DEVICEW>() as u32,
-                ..Default::default()
-            };
+    pub fn enumerate_monitors(backend: &dyn DisplayBackend) -> Result<Vec<Monitor>> {
+        let mut monitors: Vec<Monitor> = Vec::new();
 
-            let success = unsafe {
-                EnumDisplayDevicesW(PCWSTR::null(), device_index, &mut display_device, 0)
-            };
-
-            if !success.as_bool() {
-                break;
-            }
-
-            // Skip if not attached to desktop
-            if display_device.StateFlags & 0x00000001 == 0 {
-                device_index += 1;
-                continue;
-            }
-
-            let device_name = wide_string_to_string(&display_device.DeviceName);
-            let description = wide_string_to_string(&display_device.DeviceString);
-
-            if let Ok(monitor) = Self::get_monitor_info(&device_name, &description) {
+        for output in backend.enumerate_outputs()? {
+            if let Ok(monitor) =
+                Self::get_monitor_info(backend, &output.device_name, &output.description)
+            {
                 monitors.push(monitor);
             }
+        }
 
-            device_index += 1;
+        // Identical monitors without serials need the connector to tell them apart
+        for index in 0..monitors.len() {
+            let identity = monitors[index].id.identity.clone();
+            let shared = monitors
+                .iter()
+                .filter(|other| other.id.identity == identity)
+                .count()
+                > 1;
+            if identity.is_none() || shared {
+                monitors[index].id.connector = Some(monitors[index].device_name.clone());
+            }
         }
 
         Ok(monitors)
     }
 
-    fn get_monitor_info(device_name: &str, description: &str) -> Result<Monitor> {
-        let device_name_wide = string_to_wide(device_name);
+    /// Picks a monitor by enumeration index, ID, device name or a
+    /// case-insensitive part of its description, in that order.
+    pub fn select<'a>(monitors: &'a [Monitor], selector: &str) -> Result<&'a Monitor> {
+        if let Ok(index) = selector.parse::<usize>() {
+            return monitors.get(index).ok_or_else(|| {
+                HertzRateError::MonitorNotFound(format!(
+                    "Monitor index {} is out of range. Available monitors: {}",
+                    index,
+                    available(monitors)
+                ))
+                .into()
+            });
+        }
+
+        if let Some(monitor) = monitors
+            .iter()
+            .find(|m| m.id.to_string().eq_ignore_ascii_case(selector) || m.device_name == selector)
+        {
+            return Ok(monitor);
+        }
 
-        // Get current display settings
-        let mut current_mode = DEVMODEW {
-            dmSize: std::mem::size_of::<DEVMODEW>() as u16,
-            ..Default::default()
+        let needle = selector.to_lowercase();
+        let matches: Vec<(usize, &Monitor)> = monitors
+            .iter()
+            .enumerate()
+            .filter(|(_, m)| m.description.to_lowercase().contains(&needle))
+            .collect();
+        let message = match matches[..] {
+            [(_, monitor)] => return Ok(monitor),
+            [] => format!(
+                "No monitor matches '{}'. Available monitors: {}",
+                selector,
+                available(monitors)
+            ),
+            _ => format!(
+                "'{}' matches several monitors: {}. Use an ID or device name instead",
+                selector,
+                list_monitors(matches)
+            ),
         };
+        Err(HertzRateError::MonitorNotFound(message).into())
+    }
 
-        let success = unsafe {
-            EnumDisplaySettingsW(
-                PCWSTR(device_name_wide.as_ptr()),
-                ENUM_CURRENT_SETTINGS,
-                &mut current_mode,
-            )
-        };
+    fn get_monitor_info(
+        backend: &dyn DisplayBackend,
+        device_name: &str,
+        description: &str,
+    ) -> Result<Monitor> {
+        let current_mode = backend.current_mode(device_name)?;
 
-        if !success.as_bool() {
-            return Err(anyhow!(
-                "Failed to get current display settings for {}",
-                device_name
-            ));
+        let mut modes: Vec<DisplayMode> = Vec::new();
+        for mode in backend.query_modes(device_name)? {
+            if !modes.contains(&mode) {
+                modes.push(mode);
+            }
         }
+        modes.sort_by(|a, b| {
+            (b.width * b.height, b.width)
+                .cmp(&(a.width * a.height, a.width))
+                .then(a.refresh_rate.cmp(&b.refresh_rate))
+                .then(a.interlaced.cmp(&b.interlaced))
+                .then(b.bit_depth.cmp(&a.bit_depth))
+        });
+
+        // Refresh rates available at the current resolution
+        let mut available_refresh_rates: Vec<RefreshRate> = modes
+            .iter()
+            .filter(|m| m.width == current_mode.width && m.height == current_mode.height)
+            .map(|m| m.refresh_rate)
+            .collect();
+        available_refresh_rates.sort();
+        available_refresh_rates.dedup();
+
+        Ok(Monitor {
+            id: MonitorId {
+                identity: backend.identity(device_name).unwrap_or_default(),
+                connector: None,
+            },
+            device_name: device_name.to_string(),
+            description: description.to_string(),
+            current_width: current_mode.width,
+            current_height: current_mode.height,
+            current_refresh_rate: current_mode.refresh_rate,
+            available_refresh_rates,
+            current_mode,
+            modes,
+            placement: backend.placement(device_name).unwrap_or_default(),
+            vrr: backend.vrr(device_name).unwrap_or_default(),
+            vrr_enabled: backend.vrr_enabled(device_name).unwrap_or_default(),
+        })
+    }
 
-        let current_width = current_mode.dmPelsWidth;
-        let current_height = current_mode.dmPelsHeight;
-        let current_refresh_rate = current_mode.dmDisplayFrequency;
+    /// Switches to the rate `refresh_rate` picks at the current resolution
+    /// and returns the mode that was applied.
+    pub fn set_refresh_rate(
+        &self,
+        backend: &dyn DisplayBackend,
+        refresh_rate: RatePolicy,
+    ) -> Result<DisplayMode> {
+        let new_mode = self.resolve_refresh_rate(refresh_rate)?;
+        self.apply_mode(backend, &new_mode)?;
+        Ok(new_mode)
+    }
+
+    /// Switches to `resolution` at the rate `refresh_rate` picks, or at the
+    /// highest rate the resolution supports when no rate is given.
+    pub fn set_mode(
+        &self,
+        backend: &dyn DisplayBackend,
+        resolution: Resolution,
+        refresh_rate: Option<RatePolicy>,
+    ) -> Result<DisplayMode> {
+        let new_mode = self.resolve_mode(resolution, refresh_rate)?;
+        self.apply_mode(backend, &new_mode)?;
+        Ok(new_mode)
+    }
 
-        // Enumerate all available refresh rates for current resolution
-        let mut available_refresh_rates = Vec::new();
-        let mut mode_index = 0;
+    /// The mode `set_refresh_rate` would switch to.
+    pub fn resolve_refresh_rate(&self, refresh_rate: RatePolicy) -> Result<DisplayMode> {
+        let resolution = self.current_mode.resolution();
+        self.choose_rate(resolution, refresh_rate)
+            .and_then(|rate| self.find_mode(resolution, Some(rate)))
+            .ok_or_else(|| {
+                HertzRateError::RateUnavailable {
+                    monitor: self.description.clone(),
+                    rate: refresh_rate,
+                    available: self.available_refresh_rates.clone(),
+                }
+                .into()
+            })
+    }
 
-        loop {
-            let mut mode = DEVMODEW {
-                dmSize: std::mem::size_of::<DEVMODEW>() as u16,
-                ..Default::default()
+    /// The mode `set_mode` would switch to.
+    pub fn resolve_mode(
+        &self,
+        resolution: Resolution,
+        refresh_rate: Option<RatePolicy>,
+    ) -> Result<DisplayMode> {
+        let mode = match refresh_rate {
+            Some(policy) => self
+                .choose_rate(resolution, policy)
+                .and_then(|rate| self.find_mode(resolution, Some(rate))),
+            None => self.find_mode(resolution, None),
+        };
+        mode.ok_or_else(|| {
+            let requested = match refresh_rate {
+                Some(rate) => format!("{}@{}", resolution, rate),
+                None => resolution.to_string(),
             };
+            let alternatives: Vec<String> = self
+                .nearest_modes(resolution, refresh_rate.and_then(|rate| rate.rate()))
+                .iter()
+                .map(|mode| mode.to_string())
+                .collect();
+            HertzRateError::BadMode(format!(
+                "Mode {} is not available for monitor {}. Nearest alternatives: {}",
+                requested,
+                self.description,
+                alternatives.join(", ")
+            ))
+            .into()
+        })
+    }
 
-            let success = unsafe {
-                EnumDisplaySettingsW(
-                    PCWSTR(device_name_wide.as_ptr()),
-                    ENUM_DISPLAY_SETTINGS_MODE(mode_index),
-                    &mut mode,
+    /// Switches to `mode` and reads the mode back, since some drivers accept
+    /// a change but keep their old mode.
+    pub fn apply_mode(&self, backend: &dyn DisplayBackend, mode: &DisplayMode) -> Result<()> {
+        backend
+            .apply_mode(&self.device_name, mode)
+            .with_context(|| {
+                format!(
+                    "Failed to change mode to {}Hz for monitor {}",
+                    mode, self.description
                 )
-            };
+            })?;
+        self.verify_mode(backend, mode)
+    }
 
-            if !success.as_bool() {
-                break;
+    /// Fails when the backend reports a mode other than `mode` after a
+    /// change was accepted. Monitors that can't be queried are given the
+    /// benefit of the doubt.
+    pub fn verify_mode(&self, backend: &dyn DisplayBackend, mode: &DisplayMode) -> Result<()> {
+        match backend.current_mode(&self.device_name) {
+            Ok(actual) if !actual.same_timing(mode) => {
+                Err(HertzRateError::DriverRejected(format!(
+                    "The driver accepted {}Hz but monitor {} still runs {}Hz",
+                    mode, self.description, actual
+                ))
+                .into())
             }
+            _ => Ok(()),
+        }
+    }
 
-            // Only include modes with the same resolution as current
-            if mode.dmPelsWidth == current_width && mode.dmPelsHeight == current_height {
-                if !available_refresh_rates.contains(&mode.dmDisplayFrequency) {
-                    available_refresh_rates.push(mode.dmDisplayFrequency);
-                }
-            }
+    /// Switches every monitor in `targets` to its mode as one batch, so
+    /// either all of them change or none does; see
+    /// [`DisplayBackend::apply_modes`].
+    pub fn apply_modes(
+        backend: &dyn DisplayBackend,
+        targets: &[(&Monitor, DisplayMode)],
+    ) -> Result<()> {
+        backend
+            .apply_modes(&device_modes(targets))
+            .context("Failed to switch the monitors together")?;
+        targets
+            .iter()
+            .try_for_each(|(monitor, mode)| monitor.verify_mode(backend, mode))
+    }
+
+    /// Asks the backend whether all `targets` would be accepted together,
+    /// without applying them.
+    pub fn test_modes(
+        backend: &dyn DisplayBackend,
+        targets: &[(&Monitor, DisplayMode)],
+    ) -> Result<()> {
+        backend
+            .test_modes(&device_modes(targets))
+            .context("The new modes would be rejected")
+    }
 
-            mode_index += 1;
+    /// Checks that VRR can be turned on or off here; turning it on is
+    /// refused for monitors without VRR support.
+    pub fn check_vrr(&self, enabled: bool) -> Result<()> {
+        if enabled && self.vrr.is_none() {
+            return Err(HertzRateError::BackendUnsupported(format!(
+                "Monitor {} does not support variable refresh rate",
+                self.description
+            ))
+            .into());
         }
+        Ok(())
+    }
 
-        available_refresh_rates.sort();
+    /// Turns variable refresh rate on or off. Turning it on is refused for
+    /// monitors without VRR support.
+    pub fn set_vrr(&self, backend: &dyn DisplayBackend, enabled: bool) -> Result<()> {
+        self.check_vrr(enabled)?;
+        backend
+            .set_vrr(&self.device_name, enabled)
+            .with_context(|| {
+                format!(
+                    "Failed to turn VRR {} for monitor {}",
+                    on_off(enabled),
+                    self.description
+                )
+            })
+    }
 
-        Ok(Monitor {
-            device_name: device_name.to_string(),
-            description: description.to_string(),
-            current_width,
-            current_height,
-            current_refresh_rate,
-            available_refresh_rates,
+    /// Asks the backend whether `mode` would be accepted, without applying it.
+    pub fn test_mode(&self, backend: &dyn DisplayBackend, mode: &DisplayMode) -> Result<()> {
+        backend.test_mode(&self.device_name, mode).with_context(|| {
+            format!(
+                "Mode {}Hz would be rejected for monitor {}",
+                mode, self.description
+            )
         })
     }
 
-    pub fn set_refresh_rate(&self, refresh_rate: u32) -> Result<()> {
-        if !self.available_refresh_rates.contains(&refresh_rate) {
-            return Err(anyhow!(
-                "Refresh rate {}Hz is not available for monitor {}. Available rates: {:?}",
-                refresh_rate,
-                self.description,
-                self.available_refresh_rates
-            ));
+    /// Distinct resolutions the monitor supports, largest first.
+    pub fn resolutions(&self) -> Vec<Resolution> {
+        let mut resolutions: Vec<Resolution> = Vec::new();
+        for mode in &self.modes {
+            if !resolutions.contains(&mode.resolution()) {
+                resolutions.push(mode.resolution());
+            }
         }
+        resolutions
+    }
+
+    /// Refresh rates available at `resolution`, lowest first.
+    pub fn refresh_rates_at(&self, resolution: Resolution) -> Vec<RefreshRate> {
+        let mut rates: Vec<RefreshRate> = self
+            .modes
+            .iter()
+            .filter(|m| m.resolution() == resolution)
+            .map(|m| m.refresh_rate)
+            .collect();
+        rates.sort();
+        rates.dedup();
+        rates
+    }
+
+    /// The rate `policy` picks among those `resolution` supports.
+    pub fn choose_rate(&self, resolution: Resolution, policy: RatePolicy) -> Option<RefreshRate> {
+        policy.choose(&self.refresh_rates_at(resolution), self.native_rate())
+    }
+
+    /// Rate of the preferred (EDID native) timing, if the backend marks one.
+    pub fn native_rate(&self) -> Option<RefreshRate> {
+        self.modes
+            .iter()
+            .find(|mode| mode.preferred)
+            .map(|mode| mode.refresh_rate)
+    }
 
-        let new_mode = DEVMODEW {
-            dmSize: std::mem::size_of::<DEVMODEW>() as u16,
-            dmFields: DM_PELSWIDTH | DM_PELSHEIGHT | DM_DISPLAYFREQUENCY,
-            dmPelsWidth: self.current_width,
-            dmPelsHeight: self.current_height,
-            dmDisplayFrequency: refresh_rate,
-            ..Default::default()
+    /// Whether the monitor runs at a rate `policy` accepts: a matching
+    /// literal rate, any rate inside a range, or the rate a keyword picks.
+    pub fn runs_at(&self, policy: RatePolicy) -> bool {
+        let current = self.current_refresh_rate;
+        match policy {
+            RatePolicy::Exact(rate) => rate.matches(current),
+            RatePolicy::Range(low, high) => RatePolicy::in_range(low, high, current),
+            policy => self.choose_rate(self.current_mode.resolution(), policy) == Some(current),
+        }
+    }
+
+    /// Picks the mode at `resolution` whose rate is closest to `refresh_rate`
+    /// (so `60` prefers 60Hz over 59.94Hz), or the fastest one when no rate
+    /// is given. Ties keep the current bit depth and scaling where possible.
+    pub fn find_mode(
+        &self,
+        resolution: Resolution,
+        refresh_rate: Option<RefreshRate>,
+    ) -> Option<DisplayMode> {
+        let candidates = self.modes.iter().filter(|m| {
+            m.resolution() == resolution
+                && refresh_rate.is_none_or(|rate| rate.matches(m.refresh_rate))
+        });
+
+        match refresh_rate {
+            Some(rate) => candidates.min_by(|a, b| {
+                rate.distance(a.refresh_rate)
+                    .total_cmp(&rate.distance(b.refresh_rate))
+                    .then_with(|| self.attribute_score(b).cmp(&self.attribute_score(a)))
+            }),
+            None => candidates.max_by(|a, b| {
+                a.refresh_rate
+                    .cmp(&b.refresh_rate)
+                    .then_with(|| self.attribute_score(a).cmp(&self.attribute_score(b)))
+            }),
+        }
+        .copied()
+    }
+
+    /// Up to five supported modes closest to the requested one, for error
+    /// messages. 100 pixels of difference in size weigh as much as 1Hz.
+    pub fn nearest_modes(
+        &self,
+        resolution: Resolution,
+        refresh_rate: Option<RefreshRate>,
+    ) -> Vec<DisplayMode> {
+        // Without a rate, suggest each resolution at its fastest rate
+        let mut candidates: Vec<DisplayMode> = match refresh_rate {
+            Some(_) => self.modes.clone(),
+            None => self
+                .resolutions()
+                .into_iter()
+                .filter_map(|r| self.find_mode(r, None))
+                .collect(),
+        };
+        let score = |mode: &DisplayMode| {
+            let size =
+                mode.width.abs_diff(resolution.width) + mode.height.abs_diff(resolution.height);
+            let rate = refresh_rate.map_or(0.0, |rate| rate.distance(mode.refresh_rate));
+            size as f64 / 100.0 + rate
         };
 
-        let result = unsafe { ChangeDisplaySettingsW(Some(&new_mode), CDS_UPDATEREGISTRY) };
+        candidates.sort_by(|a, b| score(a).total_cmp(&score(b)));
+        let mut nearest: Vec<DisplayMode> = Vec::new();
+        for mode in candidates {
+            if nearest.len() == 5 {
+                break;
+            }
+            if !nearest.iter().any(|m| m.same_timing(&mode)) {
+                nearest.push(mode);
+            }
+        }
+        nearest
+    }
 
-        match result {
-            DISP_CHANGE_SUCCESSFUL => Ok(()),
-            _ => Err(anyhow!(
-                "Failed to change refresh rate to {}Hz for monitor {}. Error code: {}",
-                refresh_rate,
-                self.description,
-                result.0
-            )),
+    /// How many of the current mode's descriptive attributes `mode` keeps.
+    fn attribute_score(&self, mode: &DisplayMode) -> (bool, bool, bool) {
+        (
+            mode.interlaced == self.current_mode.interlaced,
+            mode.bit_depth == self.current_mode.bit_depth,
+            mode.scaling == self.current_mode.scaling,
+        )
+    }
+}
+
+impl MonitorId {
+    /// What the monitor reports about itself, if anything.
+    pub fn identity(&self) -> Option<&MonitorIdentity> {
+        self.identity.as_ref()
+    }
+
+    /// The connector, for monitors the identity alone doesn't name.
+    pub fn connector(&self) -> Option<&str> {
+        self.connector.as_deref()
+    }
+}
+
+/// Serialized as its string form, e.g. `DEL-41A8-5QF8K93`.
+impl Serialize for MonitorId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// The monitor object of the `list --format json` schema (see
+/// [`crate::listing`]). The current mode appears both as the flat
+/// `resolution` and `refresh_rate` and in full as `current_mode`.
+impl Serialize for Monitor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut monitor = serializer.serialize_struct("Monitor", 12)?;
+        monitor.serialize_field("id", &self.id)?;
+        monitor.serialize_field("identity", &self.id.identity)?;
+        monitor.serialize_field("device_name", &self.device_name)?;
+        monitor.serialize_field("description", &self.description)?;
+        monitor.serialize_field("resolution", &self.current_mode.resolution())?;
+        monitor.serialize_field("refresh_rate", &self.current_refresh_rate)?;
+        monitor.serialize_field("current_mode", &self.current_mode)?;
+        monitor.serialize_field("available_refresh_rates", &self.available_refresh_rates)?;
+        monitor.serialize_field("modes", &self.modes)?;
+        monitor.serialize_field("placement", &self.placement)?;
+        monitor.serialize_field("vrr", &self.vrr)?;
+        monitor.serialize_field("vrr_enabled", &self.vrr_enabled)?;
+        monitor.end()
+    }
+}
+
+impl fmt::Display for MonitorId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.identity, &self.connector) {
+            (Some(identity), Some(connector)) => write!(f, "{}@{}", identity, connector),
+            (Some(identity), None) => write!(f, "{}", identity),
+            (None, Some(connector)) => f.write_str(connector),
+            (None, None) => f.write_str("unknown"),
         }
     }
 }
 
-fn wide_string_to_string(wide_str: &[u16]) -> String {
-    let end = wide_str
+/// `on` or `off`, for VRR states in messages.
+pub fn on_off(enabled: bool) -> &'static str {
+    if enabled {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+fn available(monitors: &[Monitor]) -> String {
+    if monitors.is_empty() {
+        return "none".to_string();
+    }
+    list_monitors(monitors.iter().enumerate())
+}
+
+/// `0: Dell S2721DGF (DEL-41A8-5QF8K93), 1: ...` for error messages.
+fn list_monitors<'a>(monitors: impl IntoIterator<Item = (usize, &'a Monitor)>) -> String {
+    monitors
+        .into_iter()
+        .map(|(index, m)| format!("{}: {} ({})", index, m.description, m.id))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The backend's view of `targets`: device names with their modes.
+fn device_modes<'a>(targets: &[(&'a Monitor, DisplayMode)]) -> Vec<(&'a str, DisplayMode)> {
+    targets
         .iter()
-        .position(|&c| c == 0)
-        .unwrap_or(wide_str.len());
-    OsString::from_wide(&wide_str[..end])
-        .to_string_lossy()
-        .to_string()
+        .map(|(monitor, mode)| (monitor.device_name.as_str(), *mode))
+        .collect()
 }
 
-fn string_to_wide(s: &str) -> Vec<u16> {
-    let mut wide: Vec<u16> = s.encode_utf16().collect();
-    wide.push(0); // null terminator
-    wide
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::SimBackend;
+
+    fn desk() -> Vec<Monitor> {
+        let backend = SimBackend::from_toml(include_str!("../scenarios/desk.toml")).unwrap();
+        Monitor::enumerate_monitors(&backend).unwrap()
+    }
+
+    /// The rate `policy` picks at `resolution`, as text.
+    fn choose(monitor: &Monitor, resolution: &str, policy: &str) -> Option<String> {
+        monitor
+            .choose_rate(resolution.parse().unwrap(), policy.parse().unwrap())
+            .map(|rate| rate.to_string())
+    }
+
+    #[test]
+    fn max_and_min_pick_the_ends() {
+        let monitors = desk();
+        assert_eq!(
+            choose(&monitors[0], "2560x1440", "max").as_deref(),
+            Some("144")
+        );
+        assert_eq!(
+            choose(&monitors[0], "2560x1440", "min").as_deref(),
+            Some("60")
+        );
+        assert_eq!(
+            choose(&monitors[1], "1920x1080", "min").as_deref(),
+            Some("50")
+        );
+        assert_eq!(
+            choose(&monitors[1], "1920x1080", "max").as_deref(),
+            Some("60")
+        );
+        assert_eq!(choose(&monitors[1], "3840x2160", "max"), None);
+    }
+
+    #[test]
+    fn native_follows_the_preferred_mode() {
+        let monitors = desk();
+        assert_eq!(
+            choose(&monitors[0], "2560x1440", "native").as_deref(),
+            Some("144")
+        );
+        // 1920x1080 has 60 and 144, so the preferred 144 is still available
+        assert_eq!(
+            choose(&monitors[0], "1920x1080", "native").as_deref(),
+            Some("144")
+        );
+        assert_eq!(
+            choose(&monitors[1], "1920x1080", "native").as_deref(),
+            Some("60")
+        );
+        // Only the closest rate to 60 exists at 720p
+        assert_eq!(
+            choose(&monitors[1], "1280x720", "native").as_deref(),
+            Some("60")
+        );
+
+        let backend = SimBackend::from_toml(
+            r#"
+            [[monitor]]
+            device_name = "SIM-1"
+            description = "No preferred mode"
+            current = "1920x1080@60"
+            modes = ["1920x1080@60", "1920x1080@75"]
+            "#,
+        )
+        .unwrap();
+        let monitors = Monitor::enumerate_monitors(&backend).unwrap();
+        assert_eq!(choose(&monitors[0], "1920x1080", "native"), None);
+    }
+
+    #[test]
+    fn nearest_takes_the_lower_rate_on_a_tie() {
+        let monitors = desk();
+        assert_eq!(
+            choose(&monitors[0], "2560x1440", "nearest:100").as_deref(),
+            Some("120")
+        );
+        assert_eq!(
+            choose(&monitors[0], "2560x1440", "nearest:132").as_deref(),
+            Some("120")
+        );
+        assert_eq!(
+            choose(&monitors[0], "2560x1440", "nearest:240").as_deref(),
+            Some("144")
+        );
+        assert_eq!(
+            choose(&monitors[1], "1920x1080", "nearest:59.95").as_deref(),
+            Some("59.94")
+        );
+    }
+
+    #[test]
+    fn range_picks_the_highest_rate_inside() {
+        let monitors = desk();
+        assert_eq!(
+            choose(&monitors[0], "2560x1440", "60..=130").as_deref(),
+            Some("120")
+        );
+        assert_eq!(
+            choose(&monitors[0], "2560x1440", "60..=144").as_deref(),
+            Some("144")
+        );
+        // The ends match like literal rates, so 59.94 counts as 60
+        assert_eq!(
+            choose(&monitors[1], "1920x1080", "55..=59.94").as_deref(),
+            Some("59.94")
+        );
+        assert_eq!(choose(&monitors[0], "2560x1440", "61..=119"), None);
+
+        let error = monitors[0]
+            .resolve_refresh_rate("61..=119".parse().unwrap())
+            .unwrap_err();
+        assert!(matches!(
+            HertzRateError::find(&error),
+            Some(HertzRateError::RateUnavailable { .. })
+        ));
+    }
+
+    #[test]
+    fn runs_at_accepts_what_the_policy_would_pick() {
+        let monitors = desk();
+        assert!(monitors[0].runs_at("min".parse().unwrap()));
+        assert!(!monitors[0].runs_at("max".parse().unwrap()));
+        assert!(monitors[0].runs_at("59.9..=60".parse().unwrap()));
+        assert!(monitors[1].runs_at("native".parse().unwrap()));
+        assert!(monitors[1].runs_at("60".parse().unwrap()));
+    }
 }
@@ -0,0 +1,24 @@
+use anyhow::{anyhow, Result};
+use std::env;
+use std::path::PathBuf;
+
+/// Directory for hertzrate's own files: `%APPDATA%\hertzrate` on Windows,
+/// `$XDG_CONFIG_HOME/hertzrate` (or `~/.config/hertzrate`) elsewhere.
+/// `HERTZRATE_CONFIG_DIR` overrides both.
+pub fn config_dir() -> Result<PathBuf> {
+    if let Some(dir) = env::var_os("HERTZRATE_CONFIG_DIR").filter(|dir| !dir.is_empty()) {
+        return Ok(PathBuf::from(dir));
+    }
+
+    let base = if cfg!(windows) {
+        env::var_os("APPDATA").map(PathBuf::from)
+    } else {
+        env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| !dir.is_empty())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+    };
+
+    base.map(|base| base.join("hertzrate"))
+        .ok_or_else(|| anyhow!("Cannot find a configuration directory; set HERTZRATE_CONFIG_DIR"))
+}
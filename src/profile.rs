@@ -0,0 +1,403 @@
+use crate::backend::{DisplayMode, Resolution};
+use crate::monitor::{on_off, Monitor};
+use crate::refresh_rate::RatePolicy;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// A named set of modes from the config file, e.g.
+///
+/// ```toml
+/// [profile.battery]
+/// rate = "60"                      # every monitor not listed below
+///
+/// [profile.gaming]
+/// [[profile.gaming.monitor]]
+/// id = "DEL-41A8-5QF8K93"          # as shown by `hertzrate list`
+/// rate = "max"                     # or min, native, nearest:120, 120..=165
+/// vrr = true                       # turn variable refresh rate on
+///
+/// [[profile.gaming.monitor]]
+/// description = "LG"               # part of the monitor description
+/// resolution = "1920x1080"
+/// rate = "60"
+///
+/// [[profile.gaming.monitor]]
+/// device_name = "HDMI-1"
+/// resolution = "1920x1080"
+/// rate = "60"
+/// ```
+///
+/// Monitors are picked by ID, connector or description rather than by their
+/// enumeration index, which changes when cables are replugged.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Profile {
+    /// Rate for connected monitors that no `[[monitor]]` entry matches
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate: Option<RatePolicy>,
+    /// Resolution for connected monitors that no `[[monitor]]` entry matches
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<Resolution>,
+    /// VRR state for connected monitors that no `[[monitor]]` entry matches
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vrr: Option<bool>,
+    #[serde(default, rename = "monitor", skip_serializing_if = "Vec::is_empty")]
+    pub monitors: Vec<ProfileMonitor>,
+}
+
+/// Settings for the monitors matching `id`, `device_name` and `description`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProfileMonitor {
+    /// `MonitorId` or just its EDID identity, matched case-insensitively
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    /// Connector or device name, matched exactly
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_name: Option<String>,
+    /// Matched case-insensitively against part of the monitor description
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<Resolution>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rate: Option<RatePolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vrr: Option<bool>,
+}
+
+/// What applying a profile does to the connected monitors.
+pub struct ProfilePlan<'a> {
+    /// Monitors the profile sets, with the mode each resolves to
+    pub targets: Vec<(&'a Monitor, Result<DisplayMode>)>,
+    /// Monitors the profile turns VRR on or off for
+    pub vrr: Vec<(&'a Monitor, bool)>,
+    /// Entries that match no connected monitor
+    pub unmatched: Vec<String>,
+}
+
+impl Profile {
+    /// A profile that pins every monitor to its current resolution and rate,
+    /// and its VRR state where the backend reports it. Entries name the
+    /// monitor by its EDID identity, plus its connector when the identity
+    /// is missing or shared with another monitor, so they still match after
+    /// an identical monitor is plugged in or unplugged.
+    pub fn from_monitors(monitors: &[Monitor]) -> Self {
+        Self {
+            monitors: monitors
+                .iter()
+                .map(|monitor| ProfileMonitor {
+                    id: monitor.id.identity().map(|identity| identity.to_string()),
+                    device_name: monitor.id.connector().map(str::to_string),
+                    description: Some(monitor.description.clone()),
+                    resolution: Some(monitor.current_mode.resolution()),
+                    rate: Some(monitor.current_refresh_rate.into()),
+                    vrr: monitor.vrr_enabled,
+                })
+                .collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Each monitor takes its settings from the first entry that matches it,
+    /// or from the profile-wide `rate`, `resolution` and `vrr`. Monitors the
+    /// profile says nothing about are left out.
+    pub fn plan<'a>(&self, monitors: &'a [Monitor]) -> ProfilePlan<'a> {
+        let mut targets = Vec::new();
+        let mut vrr = Vec::new();
+        for monitor in monitors {
+            let (resolution, rate, enabled) =
+                match self.monitors.iter().find(|e| e.matches(monitor)) {
+                    Some(entry) => (entry.resolution, entry.rate, entry.vrr),
+                    None => (self.resolution, self.rate, self.vrr),
+                };
+            if let Some(mode) = resolve(monitor, resolution, rate) {
+                targets.push((monitor, mode));
+            }
+            if let Some(enabled) = enabled {
+                vrr.push((monitor, enabled));
+            }
+        }
+
+        let unmatched = self
+            .monitors
+            .iter()
+            .filter(|entry| !monitors.iter().any(|monitor| entry.matches(monitor)))
+            .map(|entry| entry.to_string())
+            .collect();
+
+        ProfilePlan {
+            targets,
+            vrr,
+            unmatched,
+        }
+    }
+
+    /// One line per setting, e.g. `DELL S2721DGF: 2560x1440@144Hz`.
+    pub fn describe(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self
+            .monitors
+            .iter()
+            .map(|entry| {
+                let settings = settings(entry.resolution, entry.rate, entry.vrr);
+                format!("{}: {}", entry, settings)
+            })
+            .collect();
+        if self.resolution.is_some() || self.rate.is_some() || self.vrr.is_some() {
+            let label = if self.monitors.is_empty() {
+                "All monitors"
+            } else {
+                "Other monitors"
+            };
+            lines.push(format!(
+                "{}: {}",
+                label,
+                settings(self.resolution, self.rate, self.vrr)
+            ));
+        }
+        lines
+    }
+}
+
+impl ProfileMonitor {
+    /// True when every field the entry sets matches `monitor`. An entry with
+    /// neither field matches nothing.
+    pub fn matches(&self, monitor: &Monitor) -> bool {
+        if self.id.is_none() && self.device_name.is_none() && self.description.is_none() {
+            return false;
+        }
+        let id_matches = self.id.as_ref().is_none_or(|id| {
+            id.eq_ignore_ascii_case(&monitor.id.to_string())
+                || monitor
+                    .id
+                    .identity()
+                    .is_some_and(|identity| id.eq_ignore_ascii_case(&identity.to_string()))
+        });
+        let device_matches = self
+            .device_name
+            .as_ref()
+            .is_none_or(|name| *name == monitor.device_name);
+        let description_matches = self.description.as_ref().is_none_or(|description| {
+            monitor
+                .description
+                .to_lowercase()
+                .contains(&description.to_lowercase())
+        });
+        id_matches && device_matches && description_matches
+    }
+}
+
+impl fmt::Display for ProfileMonitor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match (&self.id, &self.device_name) {
+            (Some(id), Some(device_name)) => Some(format!("{}@{}", id, device_name)),
+            (id, device_name) => id.clone().or_else(|| device_name.clone()),
+        };
+        match (&self.description, name) {
+            (Some(description), Some(name)) => write!(f, "{} ({})", description, name),
+            (Some(description), None) => f.write_str(description),
+            (None, Some(name)) => f.write_str(&name),
+            (None, None) => f.write_str("(no monitor)"),
+        }
+    }
+}
+
+/// The mode `monitor` switches to, or `None` when neither setting is given.
+/// A resolution without a rate takes the highest rate it supports.
+fn resolve(
+    monitor: &Monitor,
+    resolution: Option<Resolution>,
+    rate: Option<RatePolicy>,
+) -> Option<Result<DisplayMode>> {
+    match (resolution, rate) {
+        (Some(resolution), rate) => Some(monitor.resolve_mode(resolution, rate)),
+        (None, Some(rate)) => Some(monitor.resolve_refresh_rate(rate)),
+        (None, None) => None,
+    }
+}
+
+fn settings(resolution: Option<Resolution>, rate: Option<RatePolicy>, vrr: Option<bool>) -> String {
+    let mode = match (resolution, rate) {
+        (Some(resolution), Some(rate)) => Some(format!("{}@{}", resolution, rate)),
+        (Some(resolution), None) => Some(format!("{} at its highest rate", resolution)),
+        (None, Some(rate)) => Some(rate.to_string()),
+        (None, None) => None,
+    };
+    match (mode, vrr) {
+        (Some(mode), Some(enabled)) => format!("{}, VRR {}", mode, on_off(enabled)),
+        (Some(mode), None) => mode,
+        (None, Some(enabled)) => format!("VRR {}", on_off(enabled)),
+        (None, None) => "unchanged".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::SimBackend;
+
+    /// Two identical monitors without serials, one without any identity
+    /// and one with a unique one.
+    const DESK: &str = r#"
+        [[monitor]]
+        device_name = "DP-1"
+        description = "DELL S2721DGF"
+        current = "2560x1440@144"
+        modes = ["2560x1440@60", "2560x1440@144"]
+        manufacturer = "DEL"
+        product = "A1A5"
+
+        [[monitor]]
+        device_name = "DP-2"
+        description = "DELL S2721DGF"
+        current = "2560x1440@60"
+        modes = ["2560x1440@60", "2560x1440@144"]
+        manufacturer = "DEL"
+        product = "A1A5"
+
+        [[monitor]]
+        device_name = "HDMI-1"
+        description = "Projector"
+        current = "1920x1080@60"
+        modes = ["1920x1080@50", "1920x1080@60"]
+
+        [[monitor]]
+        device_name = "eDP-1"
+        description = "Laptop panel"
+        current = "1920x1080@60"
+        modes = ["1920x1080@48", "1920x1080@60"]
+        manufacturer = "BOE"
+        product = "0A1C"
+        serial = "1"
+    "#;
+
+    fn monitors(scenario: &str) -> Vec<Monitor> {
+        let backend = SimBackend::from_toml(scenario).unwrap();
+        Monitor::enumerate_monitors(&backend).unwrap()
+    }
+
+    /// The device names of the monitors each entry of `profile` matches.
+    fn matched(profile: &Profile, monitors: &[Monitor]) -> Vec<Vec<String>> {
+        profile
+            .monitors
+            .iter()
+            .map(|entry| {
+                monitors
+                    .iter()
+                    .filter(|monitor| entry.matches(monitor))
+                    .map(|monitor| monitor.device_name.clone())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn from_monitors_keeps_the_connector_only_where_needed() {
+        let profile = Profile::from_monitors(&monitors(DESK));
+        let names: Vec<(Option<&str>, Option<&str>)> = profile
+            .monitors
+            .iter()
+            .map(|entry| (entry.id.as_deref(), entry.device_name.as_deref()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                (Some("DEL-A1A5"), Some("DP-1")),
+                (Some("DEL-A1A5"), Some("DP-2")),
+                (None, Some("HDMI-1")),
+                (Some("BOE-0A1C-1"), None),
+            ]
+        );
+        assert_eq!(
+            profile.describe()[0],
+            "DELL S2721DGF (DEL-A1A5@DP-1): 2560x1440@144Hz"
+        );
+    }
+
+    #[test]
+    fn saved_entries_match_after_an_identical_monitor_is_unplugged() {
+        let profile = Profile::from_monitors(&monitors(DESK));
+        let all = monitors(DESK);
+        assert_eq!(
+            matched(&profile, &all),
+            [vec!["DP-1"], vec!["DP-2"], vec!["HDMI-1"], vec!["eDP-1"]]
+        );
+
+        // Alone, DP-2's monitor is named DEL-A1A5 without its connector
+        let single: String = DESK.split("[[monitor]]").nth(2).unwrap().to_string();
+        let alone = monitors(&format!("[[monitor]]{}", single));
+        assert_eq!(alone[0].id.to_string(), "DEL-A1A5");
+        assert_eq!(
+            matched(&profile, &alone),
+            [
+                Vec::<String>::new(),
+                vec!["DP-2".to_string()],
+                vec![],
+                vec![]
+            ]
+        );
+        let plan = profile.plan(&alone);
+        assert_eq!(plan.targets.len(), 1);
+        assert_eq!(
+            plan.targets[0].1.as_ref().unwrap().to_string(),
+            "2560x1440@60"
+        );
+    }
+
+    #[test]
+    fn ids_match_whole_or_by_their_identity() {
+        let all = monitors(DESK);
+        let entry = |id: &str| ProfileMonitor {
+            id: Some(id.to_string()),
+            ..ProfileMonitor::default()
+        };
+        let profile = Profile {
+            monitors: vec![
+                entry("del-a1a5@dp-2"),
+                entry("DEL-A1A5"),
+                entry("HDMI-1"),
+                entry("BOE-0A1C-1"),
+                entry("BOE-0A1C"),
+            ],
+            ..Profile::default()
+        };
+        assert_eq!(
+            matched(&profile, &all),
+            [
+                vec!["DP-2"],
+                vec!["DP-1", "DP-2"],
+                vec!["HDMI-1"],
+                vec!["eDP-1"],
+                vec![],
+            ]
+        );
+    }
+
+    #[test]
+    fn every_field_of_an_entry_must_match() {
+        let all = monitors(DESK);
+        let profile = Profile {
+            monitors: vec![
+                ProfileMonitor {
+                    description: Some("dell".to_string()),
+                    device_name: Some("DP-2".to_string()),
+                    ..ProfileMonitor::default()
+                },
+                ProfileMonitor {
+                    id: Some("BOE-0A1C-1".to_string()),
+                    description: Some("Projector".to_string()),
+                    ..ProfileMonitor::default()
+                },
+                ProfileMonitor::default(),
+            ],
+            ..Profile::default()
+        };
+        assert_eq!(
+            matched(&profile, &all),
+            [vec!["DP-2".to_string()], vec![], vec![]]
+        );
+        assert_eq!(
+            profile.plan(&all).unmatched,
+            ["Projector (BOE-0A1C-1)", "(no monitor)"]
+        );
+    }
+}
@@ -0,0 +1,440 @@
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+/// An exact refresh rate in Hz, stored as a reduced fraction so that NTSC
+/// rates such as 60000/1001 (59.94Hz) stay distinct from 60Hz.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RefreshRate {
+    numerator: u64,
+    denominator: u64,
+}
+
+impl RefreshRate {
+    pub fn new(numerator: u64, denominator: u64) -> Self {
+        if denominator == 0 {
+            return Self::from_hz(0);
+        }
+        let divisor = gcd(numerator, denominator);
+        Self {
+            numerator: numerator / divisor,
+            denominator: denominator / divisor,
+        }
+    }
+
+    pub fn from_hz(hz: u32) -> Self {
+        Self {
+            numerator: hz as u64,
+            denominator: 1,
+        }
+    }
+
+    pub fn from_millihertz(millihertz: u32) -> Self {
+        Self::new(millihertz as u64, 1000)
+    }
+
+    /// Best guess at the exact rate behind a rounded value, for APIs that
+    /// only report floats or millihertz. Whole and NTSC (N*1000/1001) rates
+    /// are recovered exactly, anything else is kept to the millihertz.
+    pub fn from_approximate(hz: f64) -> Self {
+        let whole = hz.round();
+        if (hz - whole).abs() < 0.0005 {
+            return Self::from_hz(whole as u32);
+        }
+        let ntsc = (hz * 1.001).round();
+        if (hz - ntsc / 1.001).abs() < 0.0005 {
+            return Self::new(ntsc as u64 * 1000, 1001);
+        }
+        Self::from_millihertz((hz * 1000.0).round() as u32)
+    }
+
+    /// Exact rate of a video timing: pixel clock over the total raster size.
+    pub fn from_timing(pixel_clock_hz: u64, htotal: u64, vtotal: u64) -> Self {
+        Self::new(pixel_clock_hz, htotal * vtotal)
+    }
+
+    pub fn numerator(&self) -> u64 {
+        self.numerator
+    }
+
+    pub fn denominator(&self) -> u64 {
+        self.denominator
+    }
+
+    pub fn as_f64(&self) -> f64 {
+        self.numerator as f64 / self.denominator as f64
+    }
+
+    /// Rounded to whole Hz, for APIs that only take integers.
+    pub fn round_hz(&self) -> u32 {
+        ((self.numerator + self.denominator / 2) / self.denominator) as u32
+    }
+
+    /// The exact fraction, `144` or `60000/1001`, as written to files.
+    pub fn to_exact_string(&self) -> String {
+        if self.denominator == 1 {
+            self.numerator.to_string()
+        } else {
+            format!("{}/{}", self.numerator, self.denominator)
+        }
+    }
+
+    pub fn millihertz(&self) -> u32 {
+        ((self.numerator * 1000 + self.denominator / 2) / self.denominator) as u32
+    }
+
+    /// Whether `candidate` is what someone asking for `self` means. A rate
+    /// that reduces to a terminating decimal matches at that precision, so
+    /// `60` (and `120/2`) matches anything less than 0.5Hz away and `59.94`
+    /// matches 60000/1001. Any other fraction, such as `60000/1001`, or one
+    /// too precise to compare, must match exactly.
+    pub fn matches(&self, candidate: RefreshRate) -> bool {
+        let Some(places) = decimal_places(self.denominator) else {
+            return *self == candidate;
+        };
+        // |candidate - self| < 0.5 / 10^places
+        let difference = (candidate.numerator as i128 * self.denominator as i128
+            - self.numerator as i128 * candidate.denominator as i128)
+            .unsigned_abs();
+        let scaled = 10u128
+            .checked_pow(places)
+            .and_then(|scale| difference.checked_mul(2)?.checked_mul(scale));
+        match scaled {
+            Some(scaled) => scaled < candidate.denominator as u128 * self.denominator as u128,
+            None => *self == candidate,
+        }
+    }
+
+    /// Distance to `other` in Hz, for picking the closest candidate.
+    pub fn distance(&self, other: RefreshRate) -> f64 {
+        (self.as_f64() - other.as_f64()).abs()
+    }
+}
+
+impl Ord for RefreshRate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.numerator as u128 * other.denominator as u128)
+            .cmp(&(other.numerator as u128 * self.denominator as u128))
+    }
+}
+
+impl PartialOrd for RefreshRate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for RefreshRate {
+    /// Whole rates print as integers, others with up to three decimals,
+    /// e.g. `60`, `59.94`, `23.976`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.denominator == 1 {
+            return write!(f, "{}", self.numerator);
+        }
+        let text = format!("{:.3}", self.as_f64());
+        let trimmed = text.trim_end_matches('0').trim_end_matches('.');
+        // Keep a rate that merely rounds to a whole number distinct from it
+        if trimmed.contains('.') {
+            f.write_str(trimmed)
+        } else {
+            f.write_str(&text)
+        }
+    }
+}
+
+impl Serialize for RefreshRate {
+    /// Serialized as the exact fraction, `144` or `60000/1001`, so nothing is
+    /// lost to rounding.
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_exact_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for RefreshRate {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl FromStr for RefreshRate {
+    type Err = anyhow::Error;
+
+    /// Accepts `144`, `59.94`, `60000/1001`, each optionally followed by `Hz`.
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || anyhow!("Invalid refresh rate '{}'", s);
+        let text = s.trim().trim_end_matches("Hz").trim();
+
+        if let Some((numerator, denominator)) = text.split_once('/') {
+            let numerator: u64 = numerator.trim().parse().map_err(|_| invalid())?;
+            let denominator: u64 = denominator.trim().parse().map_err(|_| invalid())?;
+            if denominator == 0 {
+                return Err(invalid());
+            }
+            return Ok(Self::new(numerator, denominator));
+        }
+
+        let (whole, fraction) = text.split_once('.').unwrap_or((text, ""));
+        if whole.is_empty() || fraction.len() > 6 {
+            return Err(invalid());
+        }
+        let digits = format!("{}{}", whole, fraction);
+        let numerator: u64 = digits.parse().map_err(|_| invalid())?;
+
+        Ok(Self::new(numerator, 10u64.pow(fraction.len() as u32)))
+    }
+}
+
+/// How a command names the rate it wants: a literal rate or a rule that
+/// picks one from what the monitor supports at the target resolution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RatePolicy {
+    /// `144`, `59.94`, `60000/1001`: the closest supported rate that
+    /// [`RefreshRate::matches`] it
+    Exact(RefreshRate),
+    /// `max`: the highest supported rate
+    Max,
+    /// `min`: the lowest supported rate
+    Min,
+    /// `native`: the rate of the monitor's preferred (EDID native) timing,
+    /// or the supported rate closest to it
+    Native,
+    /// `nearest:120`: the supported rate closest to the given one, however
+    /// far it is; the lower one on a tie
+    Nearest(RefreshRate),
+    /// `120..=165`: the highest supported rate in the inclusive range
+    Range(RefreshRate, RefreshRate),
+}
+
+impl RatePolicy {
+    /// Picks one of `rates`. `native` is the preferred timing's rate, if
+    /// known.
+    pub fn choose(
+        &self,
+        rates: &[RefreshRate],
+        native: Option<RefreshRate>,
+    ) -> Option<RefreshRate> {
+        let rates = rates.iter().copied();
+        match *self {
+            RatePolicy::Exact(rate) => closest(rate, rates.filter(|r| rate.matches(*r))),
+            RatePolicy::Max => rates.max(),
+            RatePolicy::Min => rates.min(),
+            RatePolicy::Native => closest(native?, rates),
+            RatePolicy::Nearest(rate) => closest(rate, rates),
+            RatePolicy::Range(low, high) => rates
+                .filter(|&rate| RatePolicy::in_range(low, high, rate))
+                .max(),
+        }
+    }
+
+    /// The rate the policy names literally, for suggesting alternatives.
+    pub fn rate(&self) -> Option<RefreshRate> {
+        match *self {
+            RatePolicy::Exact(rate) | RatePolicy::Nearest(rate) => Some(rate),
+            _ => None,
+        }
+    }
+
+    /// Whether `rate` lies in `low..=high`, with the ends matching like
+    /// literal rates so that `60..=144` includes 59.94.
+    pub fn in_range(low: RefreshRate, high: RefreshRate, rate: RefreshRate) -> bool {
+        (rate >= low || low.matches(rate)) && (rate <= high || high.matches(rate))
+    }
+}
+
+impl From<RefreshRate> for RatePolicy {
+    fn from(rate: RefreshRate) -> Self {
+        RatePolicy::Exact(rate)
+    }
+}
+
+impl fmt::Display for RatePolicy {
+    /// `144Hz`, `max`, `native`, `nearest:120Hz`, `120..=165Hz`; parses
+    /// back to the same policy.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RatePolicy::Exact(rate) => write!(f, "{}Hz", rate),
+            RatePolicy::Max => f.write_str("max"),
+            RatePolicy::Min => f.write_str("min"),
+            RatePolicy::Native => f.write_str("native"),
+            RatePolicy::Nearest(rate) => write!(f, "nearest:{}Hz", rate),
+            RatePolicy::Range(low, high) => write!(f, "{}..={}Hz", low, high),
+        }
+    }
+}
+
+impl Serialize for RatePolicy {
+    /// Serialized in the syntax `FromStr` reads, with exact rates.
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let text = match self {
+            RatePolicy::Exact(rate) => rate.to_exact_string(),
+            RatePolicy::Nearest(rate) => format!("nearest:{}", rate.to_exact_string()),
+            RatePolicy::Range(low, high) => {
+                format!("{}..={}", low.to_exact_string(), high.to_exact_string())
+            }
+            keyword => keyword.to_string(),
+        };
+        serializer.serialize_str(&text)
+    }
+}
+
+impl<'de> Deserialize<'de> for RatePolicy {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        text.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl FromStr for RatePolicy {
+    type Err = anyhow::Error;
+
+    /// Accepts a literal rate, `max`, `min`, `native`, `nearest:RATE` and
+    /// `LOW..=HIGH`.
+    fn from_str(s: &str) -> Result<Self> {
+        let text = s.trim();
+        let invalid = || {
+            anyhow!(
+                "Invalid refresh rate '{}', expected e.g. 144, 59.94, max, min, native, \
+                 nearest:120 or 120..=165",
+                s
+            )
+        };
+
+        match text.to_ascii_lowercase().as_str() {
+            "max" => return Ok(RatePolicy::Max),
+            "min" => return Ok(RatePolicy::Min),
+            "native" => return Ok(RatePolicy::Native),
+            _ => {}
+        }
+        if let Some(rate) = text.strip_prefix("nearest:") {
+            return Ok(RatePolicy::Nearest(rate.parse().map_err(|_| invalid())?));
+        }
+        if let Some((low, high)) = text.split_once("..=") {
+            let low: RefreshRate = low.parse().map_err(|_| invalid())?;
+            let high: RefreshRate = high.parse().map_err(|_| invalid())?;
+            if low > high {
+                return Err(anyhow!(
+                    "Invalid refresh rate range '{}': {}Hz is above {}Hz",
+                    s,
+                    low,
+                    high
+                ));
+            }
+            return Ok(RatePolicy::Range(low, high));
+        }
+        text.parse().map(RatePolicy::Exact).map_err(|_| invalid())
+    }
+}
+
+/// Formats a list of rates as `[59.94, 60, 144]`.
+pub fn format_rates(rates: &[RefreshRate]) -> String {
+    let rates: Vec<String> = rates.iter().map(|rate| rate.to_string()).collect();
+    format!("[{}]", rates.join(", "))
+}
+
+/// The rate in `rates` closest to `target`.
+fn closest(target: RefreshRate, rates: impl Iterator<Item = RefreshRate>) -> Option<RefreshRate> {
+    rates.min_by(|a, b| target.distance(*a).total_cmp(&target.distance(*b)))
+}
+
+/// Number of decimals needed to write `1 / denominator` exactly, or `None`
+/// when it isn't a terminating decimal.
+fn decimal_places(mut denominator: u64) -> Option<u32> {
+    let (mut twos, mut fives) = (0, 0);
+    while denominator.is_multiple_of(2) {
+        denominator /= 2;
+        twos += 1;
+    }
+    while denominator.is_multiple_of(5) {
+        denominator /= 5;
+        fives += 1;
+    }
+    (denominator == 1).then_some(twos.max(fives))
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a.max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rate(text: &str) -> RefreshRate {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_whole_decimal_and_fractional_rates() {
+        assert_eq!(rate("144"), RefreshRate::from_hz(144));
+        assert_eq!(rate(" 144Hz "), RefreshRate::from_hz(144));
+        assert_eq!(rate("59.94"), RefreshRate::new(2997, 50));
+        assert_eq!(rate("60000/1001"), RefreshRate::new(60000, 1001));
+        assert_eq!(rate("60000 / 1001 Hz"), RefreshRate::new(60000, 1001));
+        for invalid in ["", "Hz", ".5", "60/0", "60.1234567", "sixty", "-60"] {
+            assert!(invalid.parse::<RefreshRate>().is_err(), "{:?}", invalid);
+        }
+    }
+
+    #[test]
+    fn fractions_are_reduced() {
+        let half = rate("120/2");
+        assert_eq!((half.numerator(), half.denominator()), (60, 1));
+        assert_eq!(half, RefreshRate::from_hz(60));
+        assert_eq!(rate("59.940").denominator(), 50);
+        assert_eq!(
+            RefreshRate::from_timing(148_500_000, 2200, 1125),
+            rate("60")
+        );
+        assert_eq!(
+            RefreshRate::from_timing(148_351_648, 2200, 1125).to_string(),
+            "59.94"
+        );
+        assert_eq!(RefreshRate::new(60, 0), RefreshRate::from_hz(0));
+    }
+
+    #[test]
+    fn decimals_match_at_their_precision() {
+        let ntsc = RefreshRate::new(60000, 1001);
+        assert!(rate("60").matches(rate("59.51")));
+        assert!(rate("60").matches(ntsc));
+        assert!(!rate("60").matches(rate("60.5")));
+        assert!(rate("59.94").matches(ntsc));
+        assert!(!rate("59.94").matches(rate("60")));
+        assert!(rate("59.9401").matches(ntsc));
+        assert!(rate("120/2").matches(ntsc));
+        assert!(rate("60000/1001").matches(ntsc));
+        assert!(!rate("60000/1001").matches(rate("59.94")));
+    }
+
+    #[test]
+    fn very_precise_rates_match_exactly_instead_of_overflowing() {
+        let fine = RefreshRate::new(1, 1 << 62);
+        assert!(fine.matches(fine));
+        assert!(!fine.matches(RefreshRate::new(1, (1 << 62) - 1)));
+        assert!(!fine.matches(rate("0")));
+        let far = RefreshRate::new(u64::MAX, 1 << 40);
+        assert!(!far.matches(RefreshRate::from_hz(1)));
+    }
+
+    #[test]
+    fn displays_up_to_three_decimals() {
+        assert_eq!(rate("144").to_string(), "144");
+        assert_eq!(RefreshRate::new(60000, 1001).to_string(), "59.94");
+        assert_eq!(RefreshRate::new(24000, 1001).to_string(), "23.976");
+        assert_eq!(RefreshRate::from_millihertz(143_912).to_string(), "143.912");
+        // Rounds to 60 but isn't 60
+        assert_eq!(RefreshRate::new(600001, 10000).to_string(), "60.000");
+        assert_eq!(
+            RefreshRate::new(60000, 1001).to_exact_string(),
+            "60000/1001"
+        );
+        assert_eq!(RefreshRate::new(60000, 1001).millihertz(), 59940);
+        assert_eq!(RefreshRate::new(60000, 1001).round_hz(), 60);
+    }
+}
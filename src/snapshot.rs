@@ -0,0 +1,492 @@
+use crate::backend::{DisplayBackend, DisplayMode, Orientation, Placement};
+use crate::error::HertzRateError;
+use crate::monitor::{on_off, Monitor};
+use crate::paths;
+use crate::refresh_rate::RefreshRate;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+
+/// Format version written to new snapshot files. Files with a newer version
+/// are refused rather than half-restored.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Every monitor's mode and placement at one point in time, stored as
+/// `<config dir>/snapshots/<name>.toml`:
+///
+/// ```toml
+/// version = 1
+/// backend = "wayland"
+///
+/// [[monitor]]
+/// id = "DEL-41A8-5QF8K93"
+/// device_name = "DP-1"
+/// description = "Dell Inc. DELL S2721DGF"
+/// width = 2560
+/// height = 1440
+/// refresh_rate = "144"          # exact, e.g. "60000/1001"
+/// position = [0, 0]             # only where the backend reports placements
+/// orientation = "normal"
+/// vrr = true                    # only where the backend reports VRR state
+/// ```
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    /// Backend the snapshot was taken with
+    pub backend: String,
+    #[serde(default, rename = "monitor")]
+    pub monitors: Vec<SnapshotMonitor>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapshotMonitor {
+    /// `MonitorId` of the monitor when it was saved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub device_name: String,
+    pub description: String,
+    pub width: u32,
+    pub height: u32,
+    pub refresh_rate: RefreshRate,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub interlaced: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<[i32; 2]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub orientation: Option<Orientation>,
+    /// Whether variable refresh rate was on
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vrr: Option<bool>,
+}
+
+/// What restoring a snapshot changes on one connected monitor.
+#[derive(Debug, Clone)]
+pub struct RestoreStep {
+    pub monitor: Monitor,
+    /// New mode, if it differs from the current one
+    pub mode: Option<DisplayMode>,
+    /// New placement, if it differs from the current one
+    pub placement: Option<Placement>,
+    /// New VRR state, if it differs from the current one
+    pub vrr: Option<bool>,
+}
+
+/// The steps needed to restore a snapshot on the connected monitors.
+#[derive(Debug, Default)]
+pub struct RestorePlan {
+    pub steps: Vec<RestoreStep>,
+    /// Saved monitors that are no longer connected
+    pub missing: Vec<String>,
+    /// Saved monitors whose settings can't be restored, with the reason
+    pub unavailable: Vec<(String, anyhow::Error)>,
+}
+
+impl Snapshot {
+    /// The current settings of every connected monitor.
+    pub fn capture(backend: &dyn DisplayBackend) -> Result<Self> {
+        let monitors = Monitor::enumerate_monitors(backend)?;
+        Ok(Self::from_monitors(backend, &monitors))
+    }
+
+    pub fn from_monitors(backend: &dyn DisplayBackend, monitors: &[Monitor]) -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            backend: backend.name().to_string(),
+            monitors: monitors.iter().map(SnapshotMonitor::from).collect(),
+        }
+    }
+
+    /// Matches saved monitors to connected ones and works out what differs.
+    /// Connected monitors that aren't in the snapshot are left alone.
+    pub fn plan(&self, monitors: &[Monitor]) -> RestorePlan {
+        let mut plan = RestorePlan::default();
+        let mut used = vec![false; monitors.len()];
+
+        for saved in &self.monitors {
+            let Some(index) = saved.find(monitors, &used) else {
+                plan.missing.push(saved.to_string());
+                continue;
+            };
+            used[index] = true;
+            let monitor = &monitors[index];
+
+            let mode = match saved.resolve_mode(monitor) {
+                Ok(mode) => mode,
+                Err(e) => {
+                    plan.unavailable.push((saved.to_string(), e));
+                    continue;
+                }
+            };
+            let mode = Some(mode).filter(|mode| !mode.same_timing(&monitor.current_mode));
+            let placement = saved
+                .placement(monitor.placement)
+                .filter(|placement| Some(*placement) != monitor.placement);
+            // Only restored where the backend reports the current state
+            let vrr = saved
+                .vrr
+                .filter(|_| monitor.vrr_enabled.is_some() && saved.vrr != monitor.vrr_enabled);
+
+            if mode.is_some() || placement.is_some() || vrr.is_some() {
+                plan.steps.push(RestoreStep {
+                    monitor: monitor.clone(),
+                    mode,
+                    placement,
+                    vrr,
+                });
+            }
+        }
+
+        plan
+    }
+
+    /// Applies every step of the plan against the current monitors, carrying
+    /// on past failures. Returns the plan and one error per failed step.
+    pub fn restore(
+        &self,
+        backend: &dyn DisplayBackend,
+    ) -> Result<(RestorePlan, Vec<anyhow::Error>)> {
+        let monitors = Monitor::enumerate_monitors(backend)?;
+        let plan = self.plan(&monitors);
+        let errors = plan
+            .steps
+            .iter()
+            .filter_map(|step| step.apply(backend).err())
+            .collect();
+        Ok((plan, errors))
+    }
+
+    /// Writes the snapshot as `name`, refusing to replace an existing one
+    /// unless `overwrite` is set.
+    pub fn save(&self, name: &str, overwrite: bool) -> Result<PathBuf> {
+        let path = path(name)?;
+        if path.exists() && !overwrite {
+            return Err(anyhow!(
+                "Snapshot '{}' already exists; use --force to replace it",
+                name
+            ));
+        }
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let contents = toml::to_string(self)?;
+        fs::write(&path, contents)
+            .with_context(|| format!("Failed to write snapshot {}", path.display()))?;
+        Ok(path)
+    }
+
+    pub fn load(name: &str) -> Result<Self> {
+        let path = path(name)?;
+        if !path.exists() {
+            return Err(anyhow!(
+                "Snapshot '{}' not found (use 'snapshot list' to see saved snapshots)",
+                name
+            ));
+        }
+        let contents = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read snapshot {}", path.display()))?;
+        let snapshot: Snapshot = toml::from_str(&contents)
+            .with_context(|| format!("Invalid snapshot file {}", path.display()))?;
+        if snapshot.version > SNAPSHOT_VERSION {
+            return Err(anyhow!(
+                "Snapshot '{}' uses format version {}, but this hertzrate only reads up to version {}",
+                name,
+                snapshot.version,
+                SNAPSHOT_VERSION
+            ));
+        }
+        Ok(snapshot)
+    }
+}
+
+impl SnapshotMonitor {
+    /// Index of the connected monitor this entry describes: same ID, else
+    /// same connector and description, else the same description (the
+    /// monitor moved to another port), else the same connector.
+    fn find(&self, monitors: &[Monitor], used: &[bool]) -> Option<usize> {
+        let free = |index: &usize| !used[*index];
+        let matching = |f: &dyn Fn(&Monitor) -> bool| {
+            (0..monitors.len())
+                .filter(free)
+                .find(|&index| f(&monitors[index]))
+        };
+
+        let id = self.id.as_deref().unwrap_or_default();
+        matching(&|m| !id.is_empty() && m.id.to_string() == id)
+            .or_else(|| {
+                matching(&|m| {
+                    m.device_name == self.device_name && m.description == self.description
+                })
+            })
+            .or_else(|| matching(&|m| m.description == self.description))
+            .or_else(|| matching(&|m| m.device_name == self.device_name))
+    }
+
+    fn resolve_mode(&self, monitor: &Monitor) -> Result<DisplayMode> {
+        let saved = DisplayMode {
+            interlaced: self.interlaced,
+            ..DisplayMode::new(self.width, self.height, self.refresh_rate)
+        };
+        monitor
+            .modes
+            .iter()
+            .find(|mode| mode.same_timing(&saved))
+            .copied()
+            .ok_or_else(|| {
+                HertzRateError::BadMode(format!(
+                    "Mode {}Hz is no longer available for monitor {}",
+                    saved, monitor.description
+                ))
+                .into()
+            })
+    }
+
+    /// The saved placement, filling in whichever half wasn't saved from
+    /// `current`. `None` when neither was saved.
+    fn placement(&self, current: Option<Placement>) -> Option<Placement> {
+        if self.position.is_none() && self.orientation.is_none() {
+            return None;
+        }
+        let current = current.unwrap_or_default();
+        let [x, y] = self.position.unwrap_or([current.x, current.y]);
+        Some(Placement {
+            x,
+            y,
+            orientation: self.orientation.unwrap_or(current.orientation),
+        })
+    }
+}
+
+impl From<&Monitor> for SnapshotMonitor {
+    fn from(monitor: &Monitor) -> Self {
+        let mode = monitor.current_mode;
+        Self {
+            id: Some(monitor.id.to_string()),
+            device_name: monitor.device_name.clone(),
+            description: monitor.description.clone(),
+            width: mode.width,
+            height: mode.height,
+            refresh_rate: mode.refresh_rate,
+            interlaced: mode.interlaced,
+            position: monitor.placement.map(|p| [p.x, p.y]),
+            orientation: monitor.placement.map(|p| p.orientation),
+            vrr: monitor.vrr_enabled,
+        }
+    }
+}
+
+impl fmt::Display for SnapshotMonitor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({})", self.description, self.device_name)
+    }
+}
+
+impl RestoreStep {
+    /// Switches the mode first, then moves the monitor, since a new
+    /// resolution can shift the layout, and finally sets VRR.
+    pub fn apply(&self, backend: &dyn DisplayBackend) -> Result<()> {
+        if let Some(mode) = &self.mode {
+            self.monitor.apply_mode(backend, mode)?;
+        }
+        if let Some(placement) = &self.placement {
+            backend
+                .apply_placement(&self.monitor.device_name, placement)
+                .with_context(|| {
+                    format!(
+                        "Failed to move monitor {} to {}",
+                        self.monitor.description, placement
+                    )
+                })?;
+        }
+        if let Some(enabled) = self.vrr {
+            self.monitor.set_vrr(backend, enabled)?;
+        }
+        Ok(())
+    }
+
+    /// Checks the mode change with the backend without applying anything.
+    /// Placements and VRR changes can't be validated in advance.
+    pub fn test(&self, backend: &dyn DisplayBackend) -> Result<()> {
+        if let Some(mode) = &self.mode {
+            self.monitor.test_mode(backend, mode)?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for RestoreStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut changes = Vec::new();
+        if let Some(mode) = &self.mode {
+            changes.push(format!("{}Hz -> {}Hz", self.monitor.current_mode, mode));
+        }
+        if let Some(placement) = &self.placement {
+            match self.monitor.placement {
+                Some(current) => changes.push(format!("{} -> {}", current, placement)),
+                None => changes.push(format!("move to {}", placement)),
+            }
+        }
+        if let Some(enabled) = self.vrr {
+            changes.push(format!("VRR {}", on_off(enabled)));
+        }
+        write!(f, "{}: {}", self.monitor.description, changes.join(", "))
+    }
+}
+
+/// Names of the saved snapshots, sorted.
+pub fn list() -> Result<Vec<String>> {
+    let dir = snapshot_dir()?;
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut names: Vec<String> = fs::read_dir(&dir)
+        .with_context(|| format!("Failed to read {}", dir.display()))?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let path = entry.path();
+            let is_toml = path.extension().is_some_and(|ext| ext == "toml");
+            is_toml
+                .then(|| path.file_stem()?.to_str().map(str::to_string))
+                .flatten()
+        })
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+fn snapshot_dir() -> Result<PathBuf> {
+    Ok(paths::config_dir()?.join("snapshots"))
+}
+
+/// File for snapshot `name`. Names are limited to letters, digits, `-`, `_`
+/// and `.` so they can't point outside the snapshot directory.
+fn path(name: &str) -> Result<PathBuf> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    if !valid {
+        return Err(anyhow!(
+            "Invalid snapshot name '{}': use letters, digits, '-', '_' and '.'",
+            name
+        ));
+    }
+    Ok(snapshot_dir()?.join(format!("{}.toml", name)))
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::SimBackend;
+
+    fn desk() -> Vec<Monitor> {
+        let backend = SimBackend::from_toml(include_str!("../scenarios/desk.toml")).unwrap();
+        Monitor::enumerate_monitors(&backend).unwrap()
+    }
+
+    fn snapshot(contents: &str) -> Snapshot {
+        toml::from_str(contents).unwrap()
+    }
+
+    #[test]
+    fn plan_lists_changes_missing_and_unavailable_monitors() {
+        let plan = snapshot(
+            r#"
+            version = 1
+            backend = "sim"
+
+            [[monitor]]
+            id = "SIM-0144-A1"
+            device_name = "SIM-1"
+            description = "Simulated 27in 144Hz"
+            width = 2560
+            height = 1440
+            refresh_rate = "144"
+            position = [1920, 0]
+
+            [[monitor]]
+            device_name = "SIM-2"
+            description = "Simulated 24in 60Hz"
+            width = 3840
+            height = 2160
+            refresh_rate = "60"
+
+            [[monitor]]
+            device_name = "SIM-3"
+            description = "Unplugged TV"
+            width = 1920
+            height = 1080
+            refresh_rate = "50"
+            "#,
+        )
+        .plan(&desk());
+
+        assert_eq!(plan.steps.len(), 1);
+        assert_eq!(
+            plan.steps[0].to_string(),
+            "Simulated 27in 144Hz: 2560x1440@60Hz -> 2560x1440@144Hz, +0+0 -> +1920+0"
+        );
+        assert_eq!(plan.missing, ["Unplugged TV (SIM-3)"]);
+        assert_eq!(plan.unavailable.len(), 1);
+        let (name, error) = &plan.unavailable[0];
+        assert_eq!(name, "Simulated 24in 60Hz (SIM-2)");
+        assert!(matches!(
+            HertzRateError::find(error),
+            Some(HertzRateError::BadMode(_))
+        ));
+    }
+
+    #[test]
+    fn saved_monitors_are_found_on_another_port() {
+        let plan = snapshot(
+            r#"
+            version = 1
+            backend = "sim"
+
+            [[monitor]]
+            device_name = "SIM-7"
+            description = "Simulated 24in 60Hz"
+            width = 1920
+            height = 1080
+            refresh_rate = "50"
+            "#,
+        )
+        .plan(&desk());
+        assert!(plan.missing.is_empty());
+        assert_eq!(plan.steps.len(), 1);
+        assert_eq!(plan.steps[0].monitor.device_name, "SIM-2");
+    }
+
+    #[test]
+    fn unchanged_monitors_need_no_step() {
+        let monitors = desk();
+        let backend = SimBackend::from_toml(include_str!("../scenarios/desk.toml")).unwrap();
+        let plan = Snapshot::from_monitors(&backend, &monitors).plan(&monitors);
+        assert!(plan.steps.is_empty());
+        assert!(plan.missing.is_empty());
+        assert!(plan.unavailable.is_empty());
+    }
+
+    #[test]
+    fn names_stay_inside_the_snapshot_directory() {
+        for name in ["work", "before-update_2", "v1.0"] {
+            assert!(path(name).is_ok(), "{:?}", name);
+        }
+        for name in ["", ".hidden", "..", "../escape", "a/b", "a\\b", "two words"] {
+            let error = path(name).unwrap_err();
+            assert!(
+                error.to_string().starts_with("Invalid snapshot name"),
+                "{:?}",
+                name
+            );
+        }
+    }
+}
@@ -0,0 +1,432 @@
+//! Runs the `mutter` backend against a mock `org.gnome.Mutter.DisplayConfig`
+//! served on a private session bus. The tests need `dbus-daemon` on `PATH`
+//! and skip themselves without it.
+#![cfg(target_os = "linux")]
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader};
+use std::process::{Child, Command, Output, Stdio};
+use std::sync::{Arc, Mutex};
+use zbus::blocking::{Connection, ConnectionBuilder};
+use zbus::dbus_interface;
+use zbus::zvariant::{OwnedValue, Value};
+
+type Properties = HashMap<String, OwnedValue>;
+type MonitorSpec = (String, String, String, String);
+type MonitorMode = (String, i32, i32, f64, f64, Vec<f64>, Properties);
+type PhysicalMonitor = (MonitorSpec, Vec<MonitorMode>, Properties);
+type LogicalMonitor = (i32, i32, f64, u32, bool, Vec<MonitorSpec>, Properties);
+type MonitorAssignment = (String, String, Properties);
+type LogicalMonitorConfig = (i32, i32, f64, u32, bool, Vec<MonitorAssignment>);
+
+/// What the mock Mutter runs and what it was asked to do.
+struct MockState {
+    serial: u32,
+    /// The current mode id of each connector
+    current: HashMap<String, String>,
+    /// The position and scale of each connector's logical monitor
+    layout: HashMap<String, (i32, i32, f64)>,
+    /// The method of each `ApplyMonitorsConfig` call
+    methods: Vec<u32>,
+    /// Refuse every configuration like Mutter does for untrusted clients
+    deny: bool,
+}
+
+struct MockMutter(Arc<Mutex<MockState>>);
+
+/// (connector, vendor, product, modes as (width, height, rate))
+type MockMonitor = (
+    &'static str,
+    &'static str,
+    &'static str,
+    &'static [(i32, i32, f64)],
+);
+
+/// DP-1 is a 1440p monitor that also runs 1080p, eDP-1 a laptop panel whose
+/// 48Hz mode Mutter refuses.
+const MONITORS: [MockMonitor; 2] = [
+    (
+        "DP-1",
+        "DEL",
+        "DELL S2721DGF",
+        &[
+            (2560, 1440, 59.951),
+            (2560, 1440, 143.912),
+            (1920, 1080, 60.0),
+        ],
+    ),
+    (
+        "eDP-1",
+        "BOE",
+        "0x0a1c",
+        &[(1920, 1080, 60.008), (1920, 1080, 48.0)],
+    ),
+];
+
+fn mode_id(width: i32, height: i32, rate: f64) -> String {
+    format!("{}x{}@{:.3}", width, height, rate)
+}
+
+fn mode_size(mode_id: &str) -> (i32, i32) {
+    let (size, _) = mode_id.split_once('@').unwrap();
+    let (width, height) = size.split_once('x').unwrap();
+    (width.parse().unwrap(), height.parse().unwrap())
+}
+
+/// 1080p modes can't be scaled, like on panels too small for 2x.
+fn supported_scales(width: i32, height: i32) -> Vec<f64> {
+    if (width, height) == (1920, 1080) {
+        vec![1.0]
+    } else {
+        vec![1.0, 2.0]
+    }
+}
+
+/// Refuses layouts like Mutter's `meta_verify_monitors_config`: logical
+/// monitors, as (x, y, width, height), must start at the origin, must not
+/// overlap and must each touch another one.
+fn verify_layout(rects: &[(i32, i32, i32, i32)]) -> zbus::fdo::Result<()> {
+    let invalid = |message: &str| Err(zbus::fdo::Error::InvalidArgs(message.to_string()));
+    let min_x = rects.iter().map(|rect| rect.0).min().unwrap_or(0);
+    let min_y = rects.iter().map(|rect| rect.1).min().unwrap_or(0);
+    if (min_x, min_y) != (0, 0) {
+        return invalid("Logical monitor positions are offset");
+    }
+    let overlap = |a: i32, a_len: i32, b: i32, b_len: i32| a < b + b_len && b < a + a_len;
+    for (i, a) in rects.iter().enumerate() {
+        let mut adjacent = rects.len() == 1;
+        for (j, b) in rects.iter().enumerate() {
+            if i == j {
+                continue;
+            }
+            if overlap(a.0, a.2, b.0, b.2) && overlap(a.1, a.3, b.1, b.3) {
+                return invalid("Logical monitors overlap");
+            }
+            adjacent |= ((a.0 + a.2 == b.0 || b.0 + b.2 == a.0) && overlap(a.1, a.3, b.1, b.3))
+                || ((a.1 + a.3 == b.1 || b.1 + b.3 == a.1) && overlap(a.0, a.2, b.0, b.2));
+        }
+        if !adjacent {
+            return invalid("Logical monitors not adjacent");
+        }
+    }
+    Ok(())
+}
+
+fn spec(connector: &str, vendor: &str, product: &str) -> MonitorSpec {
+    (
+        connector.to_string(),
+        vendor.to_string(),
+        product.to_string(),
+        "0x00000000".to_string(),
+    )
+}
+
+#[dbus_interface(name = "org.gnome.Mutter.DisplayConfig")]
+impl MockMutter {
+    fn get_current_state(&self) -> (u32, Vec<PhysicalMonitor>, Vec<LogicalMonitor>, Properties) {
+        let state = self.0.lock().unwrap();
+        let mut monitors = Vec::new();
+        let mut logical_monitors = Vec::new();
+        for (connector, vendor, product, modes) in MONITORS {
+            let current = &state.current[connector];
+            let modes: Vec<MonitorMode> = modes
+                .iter()
+                .map(|&(width, height, rate)| {
+                    let id = mode_id(width, height, rate);
+                    let mut properties = Properties::new();
+                    if id == *current {
+                        properties.insert("is-current".to_string(), Value::Bool(true).into());
+                    }
+                    let scales = supported_scales(width, height);
+                    (id, width, height, rate, 1.0, scales, properties)
+                })
+                .collect();
+            let (x, y, scale) = state.layout[connector];
+            logical_monitors.push((
+                x,
+                y,
+                scale,
+                0,
+                (x, y) == (0, 0),
+                vec![spec(connector, vendor, product)],
+                Properties::new(),
+            ));
+            let mut properties = Properties::new();
+            properties.insert(
+                "display-name".to_string(),
+                Value::from(format!("{} {}", vendor, product)).into(),
+            );
+            monitors.push((spec(connector, vendor, product), modes, properties));
+        }
+        (state.serial, monitors, logical_monitors, Properties::new())
+    }
+
+    fn apply_monitors_config(
+        &mut self,
+        serial: u32,
+        method: u32,
+        logical_monitors: Vec<LogicalMonitorConfig>,
+        _properties: Properties,
+    ) -> zbus::fdo::Result<()> {
+        let mut state = self.0.lock().unwrap();
+        state.methods.push(method);
+        if state.deny {
+            return Err(zbus::fdo::Error::AccessDenied(
+                "The requested configuration is not allowed".to_string(),
+            ));
+        }
+        if serial != state.serial {
+            return Err(zbus::fdo::Error::AccessDenied(
+                "The requested configuration is based on stale information".to_string(),
+            ));
+        }
+        let mut rects = Vec::new();
+        let mut changes = Vec::new();
+        for (x, y, scale, _, _, assignments) in logical_monitors {
+            for (connector, mode, _) in assignments {
+                if mode.contains("@48") {
+                    return Err(zbus::fdo::Error::InvalidArgs(
+                        "Invalid mode for the monitor".to_string(),
+                    ));
+                }
+                let (width, height) = mode_size(&mode);
+                if !supported_scales(width, height).contains(&scale) {
+                    return Err(zbus::fdo::Error::InvalidArgs(format!(
+                        "Scale {} not valid for resolution {}x{}",
+                        scale, width, height
+                    )));
+                }
+                let logical_size = |size: i32| (f64::from(size) / scale).round() as i32;
+                rects.push((x, y, logical_size(width), logical_size(height)));
+                changes.push((connector, mode, (x, y, scale)));
+            }
+        }
+        verify_layout(&rects)?;
+        if method != 0 {
+            for (connector, mode, layout) in changes {
+                state.current.insert(connector.clone(), mode);
+                state.layout.insert(connector, layout);
+            }
+            state.serial += 1;
+        }
+        Ok(())
+    }
+}
+
+/// A private session bus with the mock Mutter on it, torn down on drop.
+struct Bus {
+    daemon: Child,
+    address: String,
+    state: Arc<Mutex<MockState>>,
+    _mutter: Connection,
+}
+
+impl Bus {
+    /// Starts the bus, or returns `None` when `dbus-daemon` is not installed.
+    fn start() -> Option<Self> {
+        let mut daemon = match Command::new("dbus-daemon")
+            .args(["--session", "--nofork", "--nopidfile", "--print-address=1"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+        {
+            Ok(daemon) => daemon,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                eprintln!("Skipping: dbus-daemon is not installed");
+                return None;
+            }
+            Err(e) => panic!("Failed to start dbus-daemon: {}", e),
+        };
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take().unwrap())
+            .read_line(&mut address)
+            .unwrap();
+        let address = address.trim().to_string();
+
+        let state = Arc::new(Mutex::new(MockState {
+            serial: 7,
+            current: HashMap::from([
+                ("DP-1".to_string(), mode_id(2560, 1440, 59.951)),
+                ("eDP-1".to_string(), mode_id(1920, 1080, 60.008)),
+            ]),
+            layout: HashMap::from([
+                ("DP-1".to_string(), (0, 0, 1.0)),
+                ("eDP-1".to_string(), (2560, 0, 1.0)),
+            ]),
+            methods: Vec::new(),
+            deny: false,
+        }));
+        let mutter = ConnectionBuilder::address(address.as_str())
+            .unwrap()
+            .name("org.gnome.Mutter.DisplayConfig")
+            .unwrap()
+            .serve_at(
+                "/org/gnome/Mutter/DisplayConfig",
+                MockMutter(Arc::clone(&state)),
+            )
+            .unwrap()
+            .build()
+            .unwrap();
+        Some(Self {
+            daemon,
+            address,
+            state,
+            _mutter: mutter,
+        })
+    }
+
+    fn hertzrate(&self, args: &[&str]) -> Output {
+        let config = tempfile::tempdir().unwrap();
+        Command::new(env!("CARGO_BIN_EXE_hertzrate"))
+            .args(["--backend", "mutter"])
+            .args(args)
+            .env("DBUS_SESSION_BUS_ADDRESS", &self.address)
+            .env("HERTZRATE_CONFIG_DIR", config.path())
+            .output()
+            .unwrap()
+    }
+
+    /// The methods of the `ApplyMonitorsConfig` calls since the last look.
+    fn take_methods(&self) -> Vec<u32> {
+        std::mem::take(&mut self.state.lock().unwrap().methods)
+    }
+
+    fn current(&self, connector: &str) -> String {
+        self.state.lock().unwrap().current[connector].clone()
+    }
+
+    fn layout(&self, connector: &str) -> (i32, i32, f64) {
+        self.state.lock().unwrap().layout[connector]
+    }
+}
+
+impl Drop for Bus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn current_state_is_listed() {
+    let Some(bus) = Bus::start() else { return };
+    let output = bus.hertzrate(&["list"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let list = stdout(&output);
+    assert!(list.contains("Device: DP-1"), "{}", list);
+    assert!(list.contains("Resolution: 2560x1440"), "{}", list);
+    assert!(list.contains("Current Refresh Rate: 59.951Hz"), "{}", list);
+    assert!(list.contains("Available Refresh Rates: [59.951, 143.912]Hz"));
+    assert!(list.contains("Device: eDP-1"));
+    assert!(list.contains("Available Refresh Rates: [48, 60.008]Hz"));
+    assert!(bus.take_methods().is_empty());
+}
+
+#[test]
+fn dry_runs_verify_and_changes_persist_unless_temporary() {
+    let Some(bus) = Bus::start() else { return };
+    let output = bus.hertzrate(&["set", "--monitor", "DP-1", "--rate", "max", "--dry-run"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(bus.take_methods(), [0]);
+    assert_eq!(bus.current("DP-1"), "2560x1440@59.951");
+
+    let output = bus.hertzrate(&["set", "--monitor", "DP-1", "--rate", "max", "--no-confirm"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(bus.take_methods(), [2]);
+    assert_eq!(bus.current("DP-1"), "2560x1440@143.912");
+
+    let output = bus.hertzrate(&[
+        "--temporary",
+        "set",
+        "--monitor",
+        "DP-1",
+        "--resolution",
+        "1920x1080",
+        "--no-confirm",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(bus.take_methods(), [1]);
+    assert_eq!(bus.current("DP-1"), "1920x1080@60.000");
+    assert_eq!(bus.current("eDP-1"), "1920x1080@60.008");
+}
+
+#[test]
+fn rejected_modes_are_driver_errors() {
+    let Some(bus) = Bus::start() else { return };
+    let output = bus.hertzrate(&["set", "--monitor", "eDP-1", "--rate", "48", "--no-confirm"]);
+    assert_eq!(output.status.code(), Some(6), "{}", stderr(&output));
+    assert!(
+        stderr(&output).contains("Mutter rejected mode 1920x1080@48 for eDP-1"),
+        "{}",
+        stderr(&output)
+    );
+    assert_eq!(bus.current("eDP-1"), "1920x1080@60.008");
+}
+
+#[test]
+fn access_denied_is_a_permission_error() {
+    let Some(bus) = Bus::start() else { return };
+    bus.state.lock().unwrap().deny = true;
+    let output = bus.hertzrate(&["set", "--monitor", "DP-1", "--rate", "max", "--no-confirm"]);
+    assert_eq!(output.status.code(), Some(7), "{}", stderr(&output));
+    assert!(stderr(&output).contains("The requested configuration is not allowed"));
+    assert_eq!(bus.current("DP-1"), "2560x1440@59.951");
+}
+
+#[test]
+fn atomic_changes_are_one_configuration() {
+    let Some(bus) = Bus::start() else { return };
+    let output = bus.hertzrate(&["set-all", "--rate", "max", "--atomic", "--no-confirm"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(bus.take_methods(), [0, 2]);
+    assert_eq!(bus.current("DP-1"), "2560x1440@143.912");
+
+    let output = bus.hertzrate(&["set-all", "--rate", "min", "--atomic", "--no-confirm"]);
+    assert_eq!(output.status.code(), Some(6), "{}", stderr(&output));
+    assert!(stderr(&output).starts_with("Error: Nothing was changed"));
+    assert_eq!(bus.take_methods(), [0]);
+    assert_eq!(bus.current("DP-1"), "2560x1440@143.912");
+}
+
+#[test]
+fn neighbours_follow_a_resolution_change() {
+    let Some(bus) = Bus::start() else { return };
+    // DP-1 at 2x is 1280 logical pixels wide, but its 1080p mode only runs at 1x
+    bus.state.lock().unwrap().layout.extend([
+        ("DP-1".to_string(), (0, 0, 2.0)),
+        ("eDP-1".to_string(), (1280, 0, 1.0)),
+    ]);
+
+    let output = bus.hertzrate(&[
+        "set",
+        "--monitor",
+        "DP-1",
+        "--mode",
+        "1920x1080@60",
+        "--no-confirm",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(bus.current("DP-1"), "1920x1080@60.000");
+    assert_eq!(bus.layout("DP-1"), (0, 0, 1.0));
+    assert_eq!(bus.layout("eDP-1"), (1920, 0, 1.0));
+
+    let output = bus.hertzrate(&[
+        "set",
+        "--monitor",
+        "DP-1",
+        "--mode",
+        "2560x1440@59.951",
+        "--no-confirm",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(bus.layout("DP-1"), (0, 0, 1.0));
+    assert_eq!(bus.layout("eDP-1"), (2560, 0, 1.0));
+}
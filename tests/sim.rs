@@ -0,0 +1,762 @@
+//! Runs the `hertzrate` binary against the scenarios under `scenarios/`.
+
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::thread;
+use std::time::Duration;
+
+fn hertzrate(scenario: &str, args: &[&str]) -> Output {
+    let config = tempfile::tempdir().unwrap();
+    hertzrate_with_config(config.path(), scenario, args)
+}
+
+/// Runs with `config` as the configuration directory, for commands that
+/// read or write snapshots and profiles. `scenario` is a file under
+/// `scenarios/` or an absolute path.
+fn hertzrate_with_config(config: &Path, scenario: &str, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_hertzrate"))
+        .args(["--backend", "sim", "--scenario"])
+        .arg(
+            Path::new(env!("CARGO_MANIFEST_DIR"))
+                .join("scenarios")
+                .join(scenario),
+        )
+        .args(args)
+        .env("HERTZRATE_CONFIG_DIR", config)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+fn list_shows_every_monitor_and_its_rates() {
+    let output = hertzrate("desk.toml", &["list"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stdout = stdout(&output);
+    assert!(
+        stdout.contains("Monitor 0: Simulated 27in 144Hz"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("Available Refresh Rates: [60, 120, 144]Hz"));
+    assert!(stdout.contains("Monitor 1: Simulated 24in 60Hz"));
+    assert!(stdout.contains("Available Refresh Rates: [50, 59.94, 60]Hz"));
+}
+
+#[test]
+fn list_prints_exact_rates_in_machine_formats() {
+    let output = hertzrate("desk.toml", &["list", "--format", "csv"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stdout = stdout(&output);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert_eq!(
+        lines[0],
+        "schema_version,index,id,manufacturer,product,serial,device_name,description,\
+         width,height,interlaced,refresh_rate,refresh_hz,available_refresh_rates,modes,\
+         x,y,orientation,vrr_min,vrr_max,vrr_source,vrr_enabled"
+    );
+    assert_eq!(
+        lines[1],
+        "1,0,SIM-0144-A1,SIM,0144,A1,SIM-1,Simulated 27in 144Hz,2560,1440,false,60,60.0,\
+         60 120 144,2560x1440@60 2560x1440@120 2560x1440@144 1920x1080@60 1920x1080@144,\
+         0,0,normal,48,144,driver,false"
+    );
+    assert_eq!(
+        lines[2],
+        "1,1,SIM-2,,,,SIM-2,Simulated 24in 60Hz,1920,1080,false,60,60.0,\
+         50 60000/1001 60,1920x1080@50 1920x1080@60000/1001 1920x1080@60 1920x1080i@60 1280x720@60,\
+         2560,0,normal,,,,"
+    );
+    assert_eq!(lines.len(), 3, "{}", stdout);
+
+    let output = hertzrate("desk.toml", &["list", "--format", "json"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let list: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(list["schema_version"], 1);
+    assert_eq!(list["backend"], "sim");
+    let monitors = list["monitors"].as_array().unwrap();
+    assert_eq!(monitors.len(), 2);
+    assert_eq!(monitors[0]["id"], "SIM-0144-A1");
+    assert_eq!(monitors[0]["vrr"]["range"], serde_json::json!([48, 144]));
+    assert_eq!(monitors[0]["vrr"]["source"], "driver");
+    assert_eq!(monitors[0]["vrr_enabled"], false);
+    assert_eq!(
+        monitors[1]["available_refresh_rates"],
+        serde_json::json!(["50", "60000/1001", "60"])
+    );
+    assert_eq!(monitors[1]["modes"][1]["refresh_rate"], "60000/1001");
+    assert_eq!(monitors[1]["modes"][1]["refresh_hz"], 59.94);
+    assert_eq!(monitors[1]["modes"][3]["interlaced"], true);
+    assert!(monitors[1]["vrr"].is_null());
+    assert!(monitors[1]["vrr_enabled"].is_null());
+}
+
+#[test]
+fn set_switches_the_rate() {
+    let output = hertzrate(
+        "desk.toml",
+        &["set", "--monitor", "SIM-1", "--rate", "144", "--no-confirm"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("✓ Successfully changed refresh rate to 144Hz"));
+}
+
+#[test]
+fn set_reports_a_rejected_mode() {
+    let output = hertzrate(
+        "failures.toml",
+        &[
+            "set",
+            "--monitor",
+            "SIM-REJECT",
+            "--rate",
+            "165",
+            "--no-confirm",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(6));
+    assert!(stderr(&output).contains("Driver rejected mode 2560x1440@165"));
+    assert!(!stdout(&output).contains("Successfully"));
+}
+
+#[test]
+fn set_reports_an_unavailable_rate_as_json() {
+    let output = hertzrate(
+        "desk.toml",
+        &[
+            "--error-format",
+            "json",
+            "set",
+            "--monitor",
+            "SIM-2",
+            "--rate",
+            "75",
+            "--no-confirm",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(4));
+    assert!(stdout(&output).is_empty(), "{}", stdout(&output));
+    let report: serde_json::Value = serde_json::from_slice(&output.stderr).unwrap();
+    let error = &report["error"];
+    assert_eq!(error["kind"], "rate_unavailable");
+    assert_eq!(error["exit_code"], 4);
+    assert_eq!(error["monitor"], "Simulated 24in 60Hz");
+    assert_eq!(error["rate"], "75");
+    assert_eq!(
+        error["available"],
+        serde_json::json!(["50", "60000/1001", "60"])
+    );
+    assert!(error["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("Refresh rate 75Hz is not available"));
+}
+
+#[test]
+fn set_reports_a_monitor_that_disconnects() {
+    let output = hertzrate(
+        "failures.toml",
+        &[
+            "set",
+            "--monitor",
+            "SIM-UNPLUG",
+            "--rate",
+            "75",
+            "--no-confirm",
+        ],
+    );
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Device SIM-UNPLUG was disconnected"));
+    assert!(!stdout(&output).contains("Successfully"));
+}
+
+#[test]
+fn set_notices_a_driver_that_ignores_the_change() {
+    let output = hertzrate(
+        "failures.toml",
+        &[
+            "set",
+            "--monitor",
+            "SIM-STUCK",
+            "--rate",
+            "120",
+            "--no-confirm",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(6));
+    assert!(
+        stderr(&output).contains("still runs 1920x1080@60Hz"),
+        "{}",
+        stderr(&output)
+    );
+    assert!(!stdout(&output).contains("Successfully"));
+}
+
+#[test]
+fn set_all_leaves_monitors_at_the_rate_alone() {
+    // SIM-2 already runs 60Hz, its fastest rate, so only SIM-1 changes and
+    // would count down to a revert
+    let output = hertzrate("desk.toml", &["set-all", "--rate", "max", "--no-confirm"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stdout = stdout(&output);
+    assert!(
+        stdout.contains("✓ Monitor 0: Simulated 27in 144Hz - 2560x1440@144Hz"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("✓ Monitor 1: Simulated 24in 60Hz - already 1920x1080@60Hz"));
+    assert!(stdout.contains("Summary: 1/1 monitors updated successfully, 1 unchanged"));
+}
+
+#[test]
+fn set_all_reports_monitors_already_at_the_rate() {
+    let output = hertzrate("desk.toml", &["set-all", "--rate", "60", "--no-confirm"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("Summary: all 2 monitors already run 60Hz"));
+}
+
+#[test]
+fn set_all_reports_each_failure() {
+    let output = hertzrate(
+        "failures.toml",
+        &["set-all", "--rate", "max", "--no-confirm"],
+    );
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = stdout(&output);
+    assert!(
+        stdout.contains("Summary: 0/3 monitors updated successfully"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("Monitor 0: Failed to change mode to 2560x1440@165Hz"));
+    assert!(stdout.contains("Monitor 1: Failed to change mode to 1920x1080@75Hz"));
+    assert!(stdout.contains("Monitor 2: The driver accepted 1920x1080@120Hz"));
+}
+
+#[test]
+fn set_all_dry_run_fails_when_a_monitor_would_reject() {
+    let output = hertzrate("failures.toml", &["set-all", "--rate", "max", "--dry-run"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stdout(&output).contains("Summary: 2/3 monitors would be updated"));
+    assert!(stderr(&output).contains("1 monitor(s) would not accept the new refresh rate"));
+}
+
+#[test]
+fn vrr_on_a_fixed_rate_monitor_is_unsupported() {
+    let output = hertzrate("desk.toml", &["vrr", "--monitor", "SIM-2", "on"]);
+    assert_eq!(output.status.code(), Some(8));
+    assert!(stderr(&output).contains("does not support variable refresh rate"));
+}
+
+#[test]
+fn snapshots_are_saved_and_listed() {
+    let config = tempfile::tempdir().unwrap();
+    let output = hertzrate_with_config(config.path(), "desk.toml", &["snapshot", "save", "desk"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let saved = fs::read_to_string(config.path().join("snapshots/desk.toml")).unwrap();
+    assert!(
+        saved.starts_with("version = 1\nbackend = \"sim\"\n"),
+        "{}",
+        saved
+    );
+
+    let output = hertzrate_with_config(config.path(), "desk.toml", &["snapshot", "list"]);
+    assert!(stdout(&output).contains("desk"));
+
+    let output = hertzrate_with_config(config.path(), "desk.toml", &["snapshot", "save", "desk"]);
+    assert!(stderr(&output).contains("Snapshot 'desk' already exists"));
+}
+
+#[test]
+fn snapshots_from_a_newer_version_are_refused() {
+    let config = tempfile::tempdir().unwrap();
+    fs::create_dir_all(config.path().join("snapshots")).unwrap();
+    fs::write(
+        config.path().join("snapshots/future.toml"),
+        "version = 2\nbackend = \"sim\"\n",
+    )
+    .unwrap();
+    let output = hertzrate_with_config(
+        config.path(),
+        "desk.toml",
+        &["snapshot", "restore", "future", "--no-confirm"],
+    );
+    assert!(!output.status.success());
+    assert!(
+        stderr(&output)
+            .contains("uses format version 2, but this hertzrate only reads up to version 1"),
+        "{}",
+        stderr(&output)
+    );
+}
+
+#[test]
+fn snapshot_names_are_validated() {
+    let output = hertzrate("desk.toml", &["snapshot", "save", "../outside"]);
+    assert!(!output.status.success());
+    assert!(stderr(&output).contains("Invalid snapshot name '../outside'"));
+}
+
+#[test]
+fn a_zero_confirm_timeout_is_rejected() {
+    let output = hertzrate(
+        "desk.toml",
+        &[
+            "set",
+            "--monitor",
+            "SIM-1",
+            "--rate",
+            "144",
+            "--confirm-timeout",
+            "0",
+        ],
+    );
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn unconfirmed_changes_are_reverted() {
+    let output = hertzrate(
+        "desk.toml",
+        &[
+            "set",
+            "--monitor",
+            "SIM-1",
+            "--rate",
+            "144",
+            "--confirm-timeout",
+            "1",
+        ],
+    );
+    assert!(!output.status.success());
+    assert!(stdout(&output).contains("✓ Restored Simulated 27in 144Hz to 2560x1440@60Hz"));
+    assert!(stderr(&output).contains("The new settings were not confirmed and have been reverted"));
+}
+
+#[test]
+fn confirm_keeps_a_change_waiting_in_another_process() {
+    let config = tempfile::tempdir().unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_hertzrate"))
+        .arg("--confirm")
+        .env("HERTZRATE_CONFIG_DIR", config.path())
+        .output()
+        .unwrap();
+    assert!(stderr(&output).contains("No change is waiting for confirmation"));
+
+    let waiting = Command::new(env!("CARGO_BIN_EXE_hertzrate"))
+        .args(["--backend", "sim", "--scenario"])
+        .arg(format!(
+            "{}/scenarios/desk.toml",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .args([
+            "set",
+            "--monitor",
+            "SIM-1",
+            "--rate",
+            "144",
+            "--confirm-timeout",
+            "30",
+        ])
+        .env("HERTZRATE_CONFIG_DIR", config.path())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let marker = config.path().join("pending-confirmation");
+    for _ in 0..100 {
+        if marker.exists() {
+            break;
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+    let output = Command::new(env!("CARGO_BIN_EXE_hertzrate"))
+        .arg("--confirm")
+        .env("HERTZRATE_CONFIG_DIR", config.path())
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+
+    let output = waiting.wait_with_output().unwrap();
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("✓ Keeping the new settings"));
+}
+
+#[test]
+fn reverting_a_profile_turns_vrr_back() {
+    let config = tempfile::tempdir().unwrap();
+    fs::write(
+        config.path().join("config.toml"),
+        "[profile.game]\n[[profile.game.monitor]]\ndevice_name = \"SIM-1\"\nrate = \"144\"\nvrr = true\n",
+    )
+    .unwrap();
+    for atomic in [false, true] {
+        let mut args = vec!["profile", "apply", "game", "--confirm-timeout", "1"];
+        if atomic {
+            args.push("--atomic");
+        }
+        let output = hertzrate_with_config(config.path(), "desk.toml", &args);
+        let stdout = stdout(&output);
+        assert!(!output.status.success());
+        assert!(
+            stdout.contains("✓ Simulated 27in 144Hz - VRR on"),
+            "{}",
+            stdout
+        );
+        assert!(
+            stdout.contains("✓ Turned VRR off again for Simulated 27in 144Hz"),
+            "{}",
+            stdout
+        );
+        assert!(stdout.contains("✓ Restored Simulated 27in 144Hz to 2560x1440@60Hz"));
+    }
+}
+
+#[test]
+fn atomic_set_all_changes_nothing_when_a_monitor_would_reject() {
+    let output = hertzrate(
+        "failures.toml",
+        &["set-all", "--rate", "max", "--atomic", "--no-confirm"],
+    );
+    assert_eq!(output.status.code(), Some(6));
+    assert!(stderr(&output).starts_with("Error: Nothing was changed"));
+    assert!(!stdout(&output).contains("✓"));
+}
+
+#[test]
+fn atomic_set_all_rolls_back_when_a_driver_ignores_the_change() {
+    let config = tempfile::tempdir().unwrap();
+    let scenario = config.path().join("stuck.toml");
+    fs::write(
+        &scenario,
+        r#"
+        [[monitor]]
+        device_name = "SIM-1"
+        description = "Main"
+        current = "2560x1440@60"
+        modes = ["2560x1440@60", "2560x1440@144"]
+
+        [[monitor]]
+        device_name = "SIM-STUCK"
+        description = "Stuck"
+        current = "1920x1080@60"
+        modes = ["1920x1080@60", "1920x1080@120"]
+        ignore_apply = true
+        "#,
+    )
+    .unwrap();
+    let output = hertzrate_with_config(
+        config.path(),
+        scenario.to_str().unwrap(),
+        &["set-all", "--rate", "max", "--atomic", "--no-confirm"],
+    );
+    assert_eq!(output.status.code(), Some(6));
+    let stdout = stdout(&output);
+    assert!(stdout.contains("Rolling back..."), "{}", stdout);
+    assert!(stdout.contains("✓ Restored Main to 2560x1440@60Hz"));
+    assert!(stderr(&output).contains("The changes were rolled back"));
+}
+
+#[test]
+fn atomic_set_all_rolls_back_when_a_monitor_disconnects() {
+    let config = tempfile::tempdir().unwrap();
+    let scenario = config.path().join("unplug.toml");
+    fs::write(
+        &scenario,
+        r#"
+        [[monitor]]
+        device_name = "SIM-1"
+        description = "Main"
+        current = "2560x1440@60"
+        modes = ["2560x1440@60", "2560x1440@144"]
+
+        [[monitor]]
+        device_name = "SIM-UNPLUG"
+        description = "Unplugged"
+        current = "1920x1080@60"
+        modes = ["1920x1080@60", "1920x1080@75"]
+        disconnect_on_apply = true
+        "#,
+    )
+    .unwrap();
+    let output = hertzrate_with_config(
+        config.path(),
+        scenario.to_str().unwrap(),
+        &["set-all", "--rate", "max", "--atomic", "--no-confirm"],
+    );
+    assert_eq!(output.status.code(), Some(1), "{}", stderr(&output));
+    let stdout = stdout(&output);
+    assert!(stdout.contains("Rolling back..."), "{}", stdout);
+    assert!(
+        stdout.contains("✓ Main is back at 2560x1440@60Hz"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("✗ Unplugged - "), "{}", stdout);
+    assert!(!stdout.contains("✓ Main - 2560x1440@144Hz"), "{}", stdout);
+    let stderr = stderr(&output);
+    assert!(
+        stderr.contains("The changes were rolled back"),
+        "{}",
+        stderr
+    );
+    assert!(
+        stderr.contains("Device SIM-UNPLUG was disconnected"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn get_prints_exact_values_in_machine_formats() {
+    let output = hertzrate(
+        "desk.toml",
+        &[
+            "get",
+            "--monitor",
+            "SIM-1",
+            "--field",
+            "mode",
+            "--format",
+            "csv",
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "schema_version,backend,id,device_name,field,value\n\
+         1,sim,SIM-0144-A1,SIM-1,mode,2560x1440@60\n"
+    );
+
+    let output = hertzrate(
+        "desk.toml",
+        &[
+            "get",
+            "--monitor",
+            "SIM-2",
+            "--field",
+            "vrr",
+            "--format",
+            "json",
+        ],
+    );
+    let stdout = stdout(&output);
+    assert!(stdout.contains("\"schema_version\": 1"), "{}", stdout);
+    assert!(stdout.contains("\"value\": \"unsupported\""), "{}", stdout);
+}
+
+#[test]
+fn snapshot_and_profile_lists_have_machine_formats() {
+    let config = tempfile::tempdir().unwrap();
+    hertzrate_with_config(config.path(), "desk.toml", &["snapshot", "save", "desk"]);
+    let output = hertzrate_with_config(
+        config.path(),
+        "desk.toml",
+        &["snapshot", "list", "--format", "csv"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "schema_version,name,backend,monitors,error\n1,desk,sim,2,\n"
+    );
+
+    fs::write(
+        config.path().join("config.toml"),
+        "[profile.game]\nrate = \"max\"\n[[profile.game.monitor]]\ndevice_name = \"SIM-1\"\nrate = \"144\"\nvrr = true\n",
+    )
+    .unwrap();
+    let output = hertzrate_with_config(
+        config.path(),
+        "desk.toml",
+        &["profile", "list", "--format", "csv"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "schema_version,profile,scope,id,device_name,description,resolution,rate,vrr\n\
+         1,game,default,,,,,max,\n\
+         1,game,monitor,,SIM-1,,,144,true\n"
+    );
+
+    let output = hertzrate_with_config(
+        config.path(),
+        "desk.toml",
+        &["profile", "list", "--format", "json"],
+    );
+    let stdout = stdout(&output);
+    assert!(stdout.contains("\"name\": \"game\""), "{}", stdout);
+    assert!(stdout.contains("\"resolution\": null"), "{}", stdout);
+}
+
+#[test]
+fn harmonize_reports_each_monitor_in_machine_formats() {
+    let output = hertzrate(
+        "desk.toml",
+        &["harmonize", "--format", "csv", "--no-confirm"],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(
+        stdout(&output),
+        "schema_version,id,device_name,description,from,to,status,error\n\
+         1,SIM-0144-A1,SIM-1,Simulated 27in 144Hz,2560x1440@60,2560x1440@60,unchanged,\n\
+         1,SIM-2,SIM-2,Simulated 24in 60Hz,1920x1080@60,1920x1080@60,unchanged,\n"
+    );
+
+    let output = hertzrate(
+        "failures.toml",
+        &[
+            "harmonize",
+            "--prefer",
+            "multiple-of-fastest",
+            "--format",
+            "json",
+            "--dry-run",
+        ],
+    );
+    let stdout = stdout(&output);
+    assert!(stdout.contains("\"dry_run\": true"), "{}", stdout);
+    assert!(!stdout.contains("Dry run"), "{}", stdout);
+}
+
+#[test]
+fn harmonize_needs_no_prompt_for_machine_formats() {
+    let output = hertzrate("desk.toml", &["harmonize", "--format", "json"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stderr(&output).contains("need --dry-run or --no-confirm"));
+    assert!(stdout(&output).is_empty());
+}
+
+fn edid_fixture(name: &str) -> std::path::PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("fixtures/edid/synthetic")
+        .join(name)
+}
+
+#[test]
+fn edid_decode_reports_every_block_of_a_file() {
+    let output = hertzrate(
+        "desk.toml",
+        &[
+            "edid",
+            "decode",
+            edid_fixture("dell-s2721dgf.bin").to_str().unwrap(),
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stdout = stdout(&output);
+    assert!(
+        stdout.contains("EDID 1.4, 256 bytes (1 extension blocks announced)"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("Block 0: base block\n  Checksum: 0x9D (valid)"));
+    assert!(stdout.contains(
+        "    1. Detailed timing (preferred): 2560x1440@165Hz, 653.40 MHz, \
+         h 2560+8+32+40, v 1440+3+5+52, 597x336 mm"
+    ));
+    assert!(stdout.contains(
+        "    3. Range limits: 48-165 Hz vertical, 30-255 kHz horizontal, max pixel clock 660 MHz"
+    ));
+    assert!(stdout.contains("    4. Monitor name: DELL S2721DGF"));
+    assert!(stdout.contains("Block 1: CTA-861 extension, revision 3\n  Checksum: 0xB8 (valid)"));
+    assert!(stdout.contains("    VIC  16: 1920x1080@60 (native)"));
+    assert!(stdout.contains("  AMD FreeSync vendor block: version 1, 48-165 Hz"));
+    assert!(stdout.contains("\nNo warnings\n"));
+}
+
+#[test]
+fn edid_decode_warns_about_damaged_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut edid = fs::read(edid_fixture("dell-s2721dgf.bin")).unwrap();
+    edid[127] ^= 0xff;
+    let corrupt = dir.path().join("corrupt.bin");
+    fs::write(&corrupt, &edid).unwrap();
+    let output = hertzrate("desk.toml", &["edid", "decode", corrupt.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let decoded = stdout(&output);
+    assert!(
+        decoded.contains("  Checksum: 0x62 (INVALID)"),
+        "{}",
+        decoded
+    );
+    assert!(decoded.contains("Warnings:\n  - Block 0 (base) has an invalid checksum"));
+
+    let truncated = dir.path().join("truncated.bin");
+    fs::write(&truncated, &edid[..200]).unwrap();
+    let output = hertzrate(
+        "desk.toml",
+        &["edid", "decode", truncated.to_str().unwrap()],
+    );
+    let stdout = stdout(&output);
+    assert!(
+        stdout.contains("  - The base block announces 1 extension blocks but only 0 are present"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("  - 72 trailing bytes after the last whole block are ignored"));
+
+    fs::write(&truncated, &edid[..1]).unwrap();
+    let output = hertzrate(
+        "desk.toml",
+        &["edid", "decode", truncated.to_str().unwrap()],
+    );
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("shorter than the 128-byte base block"));
+}
+
+#[test]
+fn edid_dump_round_trips_through_decode() {
+    let dir = tempfile::tempdir().unwrap();
+    let scenario = dir.path().join("dell.toml");
+    fs::write(
+        &scenario,
+        format!(
+            "[[monitor]]\ndevice_name = \"SIM-1\"\ndescription = \"Dell\"\n\
+             current = \"2560x1440@60\"\nmodes = [\"2560x1440@60\"]\nedid = {:?}\n",
+            edid_fixture("dell-s2721dgf.bin")
+        ),
+    )
+    .unwrap();
+    let scenario = scenario.to_str().unwrap();
+    let dumped = dir.path().join("dumped.bin");
+    let output = hertzrate(
+        scenario,
+        &[
+            "edid",
+            "dump",
+            "--monitor",
+            "SIM-1",
+            "-o",
+            dumped.to_str().unwrap(),
+        ],
+    );
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("✓ Saved the 256-byte EDID of Dell"));
+    assert_eq!(
+        fs::read(&dumped).unwrap(),
+        fs::read(edid_fixture("dell-s2721dgf.bin")).unwrap()
+    );
+
+    let output = hertzrate(scenario, &["edid", "decode", dumped.to_str().unwrap()]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let stdout = stdout(&output);
+    assert!(
+        stdout.contains("    4. Monitor name: DELL S2721DGF"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("\nNo warnings\n"));
+
+    let output = hertzrate("desk.toml", &["edid", "dump", "--monitor", "SIM-1"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).contains("No EDID available"));
+}
@@ -0,0 +1,147 @@
+//! Runs the `wayland` backend against a headless sway. The tests need `sway`
+//! on `PATH`, so they only run when asked for:
+//! `cargo test --test wayland -- --ignored`.
+//!
+//! Weston doesn't implement `zwlr_output_management_v1`, so it can't stand in
+//! for sway here. A headless output has no fixed modes, so wlroots advertises
+//! the custom mode it runs as its only mode.
+#![cfg(target_os = "linux")]
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Child, Command, Output, Stdio};
+use std::thread;
+use std::time::Duration;
+use tempfile::TempDir;
+
+/// A headless sway with its own runtime directory, killed on drop.
+struct Sway {
+    child: Child,
+    runtime_dir: TempDir,
+    socket: String,
+}
+
+impl Sway {
+    fn start() -> Self {
+        let runtime_dir = tempfile::tempdir().unwrap();
+        let config = runtime_dir.path().join("config");
+        fs::write(&config, "").unwrap();
+        let child = Command::new("sway")
+            .arg("--config")
+            .arg(&config)
+            .env("XDG_RUNTIME_DIR", runtime_dir.path())
+            .env("WLR_BACKENDS", "headless")
+            .env("WLR_LIBINPUT_NO_DEVICES", "1")
+            .env("WLR_RENDERER", "pixman")
+            .env_remove("WAYLAND_DISPLAY")
+            .env_remove("DISPLAY")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("sway is not installed");
+
+        // The runtime directory is private, so sway's is the only socket in it
+        let mut sway = Self {
+            child,
+            runtime_dir,
+            socket: String::new(),
+        };
+        for _ in 0..100 {
+            if let Some(socket) = sway.find_socket() {
+                sway.socket = socket;
+                return sway;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        panic!("sway did not open a Wayland socket");
+    }
+
+    fn find_socket(&self) -> Option<String> {
+        fs::read_dir(self.runtime_dir.path())
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| PathBuf::from(entry.file_name()))
+            .filter_map(|name| name.to_str().map(str::to_string))
+            .find(|name| name.starts_with("wayland-") && !name.ends_with(".lock"))
+    }
+
+    fn hertzrate(&self, args: &[&str]) -> Output {
+        let config = tempfile::tempdir().unwrap();
+        Command::new(env!("CARGO_BIN_EXE_hertzrate"))
+            .args(["--backend", "wayland"])
+            .args(args)
+            .env("XDG_RUNTIME_DIR", self.runtime_dir.path())
+            .env("WAYLAND_DISPLAY", &self.socket)
+            .env("HERTZRATE_CONFIG_DIR", config.path())
+            .output()
+            .unwrap()
+    }
+}
+
+impl Drop for Sway {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+#[ignore = "needs sway"]
+fn headless_outputs_are_listed_tested_and_applied() {
+    let sway = Sway::start();
+
+    let output = sway.hertzrate(&["list"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    let list = stdout(&output);
+    assert!(list.contains("Device: HEADLESS-1"), "{}", list);
+    assert!(list.contains("Available Refresh Rates: [60]Hz"), "{}", list);
+
+    let output = sway.hertzrate(&[
+        "set",
+        "--monitor",
+        "HEADLESS-1",
+        "--rate",
+        "60",
+        "--dry-run",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("✓ The driver accepts"));
+
+    let output = sway.hertzrate(&[
+        "set",
+        "--monitor",
+        "HEADLESS-1",
+        "--rate",
+        "60",
+        "--no-confirm",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("✓ Successfully changed refresh rate to 60Hz"));
+
+    let output = sway.hertzrate(&["set-all", "--rate", "60", "--atomic", "--no-confirm"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+}
+
+#[test]
+#[ignore = "needs sway"]
+fn modes_the_head_does_not_advertise_are_refused() {
+    let sway = Sway::start();
+    let output = sway.hertzrate(&[
+        "set",
+        "--monitor",
+        "HEADLESS-1",
+        "--rate",
+        "144",
+        "--no-confirm",
+    ]);
+    assert_eq!(output.status.code(), Some(4), "{}", stderr(&output));
+    assert!(stderr(&output).contains("Available rates: [60]"));
+}
@@ -0,0 +1,181 @@
+//! Runs the `x11` backend against a headless Xvfb server. The tests need
+//! `Xvfb` on `PATH`, so they only run when asked for:
+//! `cargo test --test x11 -- --ignored`.
+#![cfg(target_os = "linux")]
+
+use std::io::{BufRead, BufReader};
+use std::process::{Child, Command, Output, Stdio};
+use x11rb::connection::Connection;
+use x11rb::protocol::randr::{self, ConnectionExt as _};
+use x11rb::protocol::xproto::ConnectionExt as _;
+use x11rb::rust_connection::RustConnection;
+
+/// An Xvfb server on a free display, killed on drop.
+struct Xvfb {
+    child: Child,
+    display: String,
+}
+
+impl Xvfb {
+    /// Starts a server whose screen can grow up to its initial 2560x1440.
+    fn start() -> Self {
+        let mut child = Command::new("Xvfb")
+            .args(["-displayfd", "1", "-nolisten", "tcp"])
+            .args(["-screen", "0", "2560x1440x24"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Xvfb is not installed");
+        let mut line = String::new();
+        BufReader::new(child.stdout.take().unwrap())
+            .read_line(&mut line)
+            .unwrap();
+        Self {
+            child,
+            display: format!(":{}", line.trim()),
+        }
+    }
+
+    fn connect(&self) -> RustConnection {
+        x11rb::connect(Some(&self.display)).unwrap().0
+    }
+
+    fn hertzrate(&self, args: &[&str]) -> Output {
+        let config = tempfile::tempdir().unwrap();
+        Command::new(env!("CARGO_BIN_EXE_hertzrate"))
+            .args(["--backend", "x11"])
+            .args(args)
+            .env("DISPLAY", &self.display)
+            .env("HERTZRATE_CONFIG_DIR", config.path())
+            .output()
+            .unwrap()
+    }
+}
+
+impl Drop for Xvfb {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Adds a mode with the given active size and totals to Xvfb's only
+/// output; the dot clock makes the rate exact.
+fn add_mode(conn: &RustConnection, width: u16, height: u16, htotal: u16, vtotal: u16, hz: u32) {
+    let root = conn.setup().roots[0].root;
+    let resources = conn
+        .randr_get_screen_resources_current(root)
+        .unwrap()
+        .reply()
+        .unwrap();
+    let name = format!("{}x{}_{}", width, height, hz);
+    let mode = randr::ModeInfo {
+        width,
+        height,
+        dot_clock: u32::from(htotal) * u32::from(vtotal) * hz,
+        hsync_start: width + 8,
+        hsync_end: width + 40,
+        htotal,
+        vsync_start: height + 3,
+        vsync_end: height + 8,
+        vtotal,
+        name_len: name.len() as u16,
+        ..Default::default()
+    };
+    let mode = conn
+        .randr_create_mode(root, mode, name.as_bytes())
+        .unwrap()
+        .reply()
+        .unwrap()
+        .mode;
+    conn.randr_add_output_mode(resources.outputs[0], mode)
+        .unwrap()
+        .check()
+        .unwrap();
+}
+
+fn screen_size(conn: &RustConnection) -> (u16, u16) {
+    let geometry = conn
+        .get_geometry(conn.setup().roots[0].root)
+        .unwrap()
+        .reply()
+        .unwrap();
+    (geometry.width, geometry.height)
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stderr).into_owned()
+}
+
+#[test]
+#[ignore = "needs Xvfb"]
+fn modes_change_and_the_screen_follows() {
+    let xvfb = Xvfb::start();
+    let conn = xvfb.connect();
+    add_mode(&conn, 1920, 1080, 2080, 1144, 120);
+    add_mode(&conn, 2560, 1440, 2720, 1481, 60);
+
+    let output = xvfb.hertzrate(&["list"]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("screen"), "{}", stdout(&output));
+
+    let output = xvfb.hertzrate(&[
+        "set",
+        "--monitor",
+        "screen",
+        "--mode",
+        "1920x1080@120",
+        "--dry-run",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).contains("this check is a heuristic"));
+    assert_eq!(screen_size(&conn), (2560, 1440));
+
+    let output = xvfb.hertzrate(&[
+        "set",
+        "--monitor",
+        "screen",
+        "--mode",
+        "1920x1080@120",
+        "--no-confirm",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(screen_size(&conn), (1920, 1080));
+
+    let output = xvfb.hertzrate(&[
+        "set",
+        "--monitor",
+        "screen",
+        "--mode",
+        "2560x1440@60",
+        "--no-confirm",
+    ]);
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert_eq!(screen_size(&conn), (2560, 1440));
+}
+
+#[test]
+#[ignore = "needs Xvfb"]
+fn modes_larger_than_the_screen_can_grow_are_rejected() {
+    let xvfb = Xvfb::start();
+    add_mode(&xvfb.connect(), 3840, 2160, 4000, 2222, 60);
+
+    let output = xvfb.hertzrate(&[
+        "set",
+        "--monitor",
+        "screen",
+        "--mode",
+        "3840x2160@60",
+        "--dry-run",
+    ]);
+    assert_eq!(output.status.code(), Some(6));
+    assert!(
+        stderr(&output).contains("the X server allows at most 2560x1440"),
+        "{}",
+        stderr(&output)
+    );
+}